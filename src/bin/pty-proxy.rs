@@ -1,27 +1,97 @@
-use std::ffi::{ OsStr, c_void };
-use std::os::windows::ffi::OsStrExt;
-use std::os::windows::io::{ AsRawHandle, OwnedHandle, FromRawHandle };
-use std::ptr::null_mut;
 use std::fs;
 use std::io::{ self, Read, Write };
 use std::path::Path;
 use std::process::exit;
-use std::sync::{ Arc, Mutex };
+use std::sync::{ Arc, Mutex, mpsc };
 use std::thread;
-use std::mem::{ zeroed, size_of };
+use std::time::{ Duration, Instant };
+
+use winptyrs::PTYBackend;
+use pty_proxy::child;
+use pty_proxy::cli::{ self, Command as CliCommand };
+use pty_proxy::config::{ self, Config, LogLevel };
+use pty_proxy::output::{ EscapeStripper, OutputMode };
+use pty_proxy::protocol::{ Message, MessageReader, MessageWriter, Signal };
+use pty_proxy::transport::{ self, Direction, Transport, TransportKind };
 
-use uuid::Uuid;
-use toml::Table;
-use windows_sys::{
-    Win32::Foundation::*,
-    Win32::Storage::FileSystem::*,
-    Win32::System::Pipes::*,
-    Win32::System::Threading::*,
+#[cfg(windows)]
+use std::ffi::{ OsStr, c_void };
+#[cfg(windows)]
+use std::os::windows::ffi::OsStrExt;
+#[cfg(windows)]
+use std::os::windows::io::{ AsRawHandle, OwnedHandle, FromRawHandle };
+#[cfg(windows)]
+use std::ptr::null_mut;
+#[cfg(windows)]
+use std::mem::{ zeroed, size_of };
+#[cfg(windows)]
+use windows_sys::{ core::BOOL, Win32::Foundation::*, Win32::System::Threading::* };
+#[cfg(windows)]
+use windows_sys::Win32::System::JobObjects::{
+    AssignProcessToJobObject,
+    CreateJobObjectW,
+    SetInformationJobObject,
+    JobObjectExtendedLimitInformation,
+    JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+    JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+};
+#[cfg(windows)]
+use std::sync::OnceLock;
+#[cfg(windows)]
+use windows_sys::Win32::System::Console::{
+    GetConsoleScreenBufferInfo,
+    GetStdHandle,
+    SetConsoleCtrlHandler,
+    CONSOLE_SCREEN_BUFFER_INFO,
+    STD_OUTPUT_HANDLE,
+    CTRL_CLOSE_EVENT,
+    CTRL_LOGOFF_EVENT,
+    CTRL_SHUTDOWN_EVENT,
 };
+#[cfg(windows)]
+use winptyrs::pty::cmdline::join_command_line;
 
-#[cfg(feature = "color")]
+#[cfg(unix)]
+use std::os::unix::process::{ CommandExt, ExitStatusExt };
+#[cfg(unix)]
+use std::process::{ Child, Command, Stdio };
+
+#[cfg(all(windows, feature = "color"))]
 use windows_sys::Win32::System::Console::*;
 
+/// stdin 关闭后等待目标程序处理完剩余输入的时间，之后让 pty-proxy-child 结束它
+const STDIN_CLOSED_GRACE: Duration = Duration::from_secs(3);
+
+/// 退出前等待剩余输出转发完的最长时间
+const OUTPUT_DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// 向 pty-proxy-child 发送心跳的间隔
+const PING_INTERVAL: Duration = Duration::from_secs(5);
+
+/// 心跳超过这个时间没有回复时，报告 pty-proxy-child 没有响应
+const PING_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// 无法建立和 pty-proxy-child 的通信、无法启动或等待它时的退出代码
+const FAILURE_EXIT_CODE: i32 = 1;
+
+/// 检查控制台窗口大小的间隔，为什么轮询见 Windows 上的 [`handle_signals`]
+#[cfg(windows)]
+const RESIZE_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// 主线程处理的事件
+enum Event {
+    /// 要求结束目标程序：stdin 关闭后的等待时间已过，或者收到了结束信号
+    Stop,
+    /// 本进程所在终端的大小可能改变了
+    Resized,
+    /// 该发送心跳了
+    Ping,
+    /// 收到了 pty-proxy-child 对心跳的回复
+    Pong(u32),
+    /// pty-proxy-child 已退出，附带退出代码
+    Exited(io::Result<i32>),
+}
+
 macro_rules! debug_println {
     ($($arg:tt)*) => {
         #[cfg(feature = "debug_mode")]
@@ -31,11 +101,21 @@ macro_rules! debug_println {
     };
 }
 
+/// 配置的日志级别 `$log_level` 不低于 `$level` 时在 stderr 上显示日志
+macro_rules! log_println {
+    ($log_level:expr, $level:expr, $($arg:tt)*) => {
+        if $log_level >= $level {
+            eprintln!("pty-proxy: {}", format_args!($($arg)*));
+        }
+    };
+}
+
+#[cfg(windows)]
 fn to_wstr(s: &str) -> Vec<u16> {
     // 将 Rust 字符串转换为 OsStr
     let os_str = OsStr::new(s);
     // 将 OsStr 转换为 UTF-16 编码的宽字符数组
-    let wide_chars: Vec<u16> = os_str.encode_wide().chain(Some(0).into_iter()).collect();
+    let wide_chars: Vec<u16> = os_str.encode_wide().chain(Some(0)).collect();
     // 返回宽字符数组
     wide_chars
 }
@@ -43,12 +123,17 @@ fn to_wstr(s: &str) -> Vec<u16> {
 /// 启动一个独立的进程
 ///
 /// # 参数
-/// - `command`: 要执行的命令（包括路径和参数）
+/// - `program`: 要执行的程序路径
+/// - `args`: 传给程序的参数，按 MSVCRT 的规则加上引号后拼接成命令行
+/// - `inherit_handles`: 是否让进程继承可继承的句柄
+/// - `kill_tree`: 是否在本进程退出（包括被强制结束）时结束该进程及其所有后代进程
 ///
 /// # 返回值
 /// - `Ok(进程句柄)` 如果成功
 /// - `Err(错误信息)` 如果失败
-fn create_independent_process(command: &String) -> Result<OwnedHandle, String> {
+#[cfg(windows)]
+fn create_independent_process(program: &str, args: &[&str], inherit_handles: bool, kill_tree: bool) -> Result<OwnedHandle, String> {
+    let command = join_command_line(program, args);
     debug_println!("启动独立进程，命令行：{}", command);
     let mut command_line = to_wstr(command.as_str());
 
@@ -64,33 +149,288 @@ fn create_independent_process(command: &String) -> Result<OwnedHandle, String> {
 
     let mut process_info: PROCESS_INFORMATION = unsafe { zeroed() };
 
+    // 先挂起进程，加入作业对象后再恢复，这样它启动的进程也都在作业对象中
+    let mut creation_flags = CREATE_NEW_PROCESS_GROUP | CREATE_NEW_CONSOLE;
+    if kill_tree {
+        creation_flags |= CREATE_SUSPENDED;
+    }
+
     let success = unsafe {
         CreateProcessW(
             null_mut(), // 使用命令行而不是应用程序名称
             command_line.as_mut_ptr(), // 命令行
             null_mut(), // 进程安全属性
             null_mut(), // 线程安全属性
-            false as i32, // 不继承句柄
-            CREATE_NEW_PROCESS_GROUP | CREATE_NEW_CONSOLE, // 标志
+            inherit_handles as i32, // 是否继承句柄
+            creation_flags, // 标志
             null_mut(), // 使用父进程的环境
             null_mut(), // 使用父进程的工作目录
-            &mut startup_info, // 启动信息
+            &startup_info, // 启动信息
             &mut process_info // 进程信息
         )
     };
 
     if success == 0 {
         let error = io::Error::last_os_error();
-        Err(format!("无法启动进程: {}", error))
+        return Err(format!("无法启动进程: {}", error));
+    }
+
+    unsafe {
+        let process = OwnedHandle::from_raw_handle(process_info.hProcess);
+        if kill_tree {
+            if let Err(error) = kill_on_exit(process_info.hProcess) {
+                TerminateProcess(process_info.hProcess, 1);
+                CloseHandle(process_info.hThread);
+                return Err(format!("无法将进程加入作业对象: {}", error));
+            }
+            ResumeThread(process_info.hThread);
+        }
+        CloseHandle(process_info.hThread);
+        Ok(process)
+    }
+}
+
+/// 把进程放进一个关闭时结束所有进程的作业对象
+///
+/// 作业对象的句柄故意不关闭，本进程退出时由系统关闭，进程及其后代进程随之被结束。
+#[cfg(windows)]
+unsafe fn kill_on_exit(process: HANDLE) -> io::Result<()> {
+    let job = CreateJobObjectW(null_mut(), null_mut());
+    if job.is_null() {
+        return Err(io::Error::last_os_error());
+    }
+    let mut info: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = zeroed();
+    info.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+    let success = SetInformationJobObject(
+        job,
+        JobObjectExtendedLimitInformation,
+        &info as *const _ as *const c_void,
+        size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as u32
+    ) != 0 && AssignProcessToJobObject(job, process) != 0;
+    if !success {
+        let error = io::Error::last_os_error();
+        CloseHandle(job);
+        return Err(error);
+    }
+    Ok(())
+}
+
+/// 启动一个独立的进程
+///
+/// # 参数
+/// - `program`: 要执行的程序路径
+/// - `args`: 传给程序的参数
+/// - `_inherit_handles`: Unix 上没有清除 close-on-exec 标志的描述符总会被继承，无需处理
+/// - `kill_tree`: 是否在本进程退出（包括被强制结束）时向该进程发送 SIGTERM，只有 Linux 支持，
+///   pty-proxy-child 收到后会结束目标程序及其后代进程
+///
+/// # 返回值
+/// - `Ok(子进程)` 如果成功
+/// - `Err(错误信息)` 如果失败
+#[cfg(unix)]
+fn create_independent_process(program: &str, args: &[&str], _inherit_handles: bool, kill_tree: bool) -> Result<Child, String> {
+    debug_println!("启动独立进程，程序：{}，参数：{:?}", program, args);
+
+    // 子进程没有自己的窗口，不能让它的输出混进本进程的 stdout
+    // 放进单独的进程组，终端的 Ctrl+C 只发给本进程，由本进程负责结束目标程序
+    let mut command = Command::new(program);
+    command.args(args).stdin(Stdio::null()).stdout(Stdio::null()).process_group(0);
+    // 本进程屏蔽了结束信号（见 handle_signals），屏蔽字会被继承，要在子进程中解除
+    let parent = unsafe { libc::getpid() };
+    unsafe {
+        command.pre_exec(move || {
+            let mut set: libc::sigset_t = std::mem::zeroed();
+            libc::sigemptyset(&mut set);
+            libc::pthread_sigmask(libc::SIG_SETMASK, &set, std::ptr::null_mut());
+            // 启动它的线程（主线程）退出时由内核发送 SIGTERM，如果本进程已经退出则直接结束
+            #[cfg(target_os = "linux")]
+            if kill_tree {
+                if libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGTERM) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                if libc::getppid() != parent {
+                    libc::_exit(128 + libc::SIGTERM);
+                }
+            }
+            #[cfg(not(target_os = "linux"))]
+            let _ = (kill_tree, parent);
+            Ok(())
+        });
+    }
+
+    #[cfg(not(feature = "debug_mode"))]
+    command.stderr(Stdio::null());
+
+    command.spawn().map_err(|error| format!("无法启动进程: {}", error))
+}
+
+/// 显示错误信息后以退出代码 2 结束本进程，用于配置和命令行参数有误的情况
+fn exit_with_error(message: &str) -> ! {
+    eprintln!("{}", message);
+    exit(2);
+}
+
+/// 显示运行时的错误后以 [`FAILURE_EXIT_CODE`] 结束本进程，用来代替 panic
+fn exit_with_failure(log_level: LogLevel, message: &str) -> ! {
+    log_println!(log_level, LogLevel::Error, "{}", message);
+    exit(FAILURE_EXIT_CODE);
+}
+
+/// 读取并检查配置文件，有误时以退出代码 2 结束本进程，只有警告时显示出来后继续
+fn read_config(path: &Path) -> Config {
+    let config = fs::read_to_string(path)
+        .map_err(|error| format!("无法读取配置文件：{}", error))
+        .and_then(|content| Config::from_toml(&content))
+        .unwrap_or_else(|error| exit_with_error(&format!("配置文件 {} 有误：{}", path.display(), error)));
+    for warning in &config.warnings {
+        log_println!(config.log_level, LogLevel::Error, "配置文件 {}：{}", path.display(), warning);
+    }
+    config
+}
+
+/// 等待进程结束并返回它的退出代码
+#[cfg(windows)]
+fn wait_for_process(process: OwnedHandle) -> io::Result<i32> {
+    unsafe {
+        WaitForSingleObject(process.as_raw_handle() as HANDLE, INFINITE);
+    }
+    // 获取进程退出代码
+    let mut exit_code: u32 = 0;
+    let success = unsafe {
+        GetExitCodeProcess(process.as_raw_handle() as HANDLE, &mut exit_code)
+    };
+    if success != 0 {
+        Ok(exit_code as i32)
     } else {
-        unsafe {
-            CloseHandle(process_info.hThread);
-            Ok(OwnedHandle::from_raw_handle(process_info.hProcess as *mut c_void))
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// 等待进程结束并返回它的退出代码
+#[cfg(unix)]
+fn wait_for_process(mut process: Child) -> io::Result<i32> {
+    let status = process.wait()?;
+    // 被信号终止的进程没有退出代码，按 shell 的约定报告
+    match (status.code(), status.signal()) {
+        (Some(code), _) => Ok(code),
+        (None, Some(signal)) => {
+            eprintln!("pty-proxy-child 被信号 {} 终止", signal);
+            Ok(128 + signal)
+        }
+        (None, None) => Ok(101),
+    }
+}
+
+/// 把 SIGINT、SIGTERM 和 SIGHUP 转换成 [`Event::Stop`]，把 SIGWINCH 转换成 [`Event::Resized`]
+///
+/// 必须在创建其他线程之前调用，这样所有线程都屏蔽了这些信号，只由专门的线程用 sigwait 接收。
+#[cfg(unix)]
+fn handle_signals(events: mpsc::Sender<Event>) {
+    let set = unsafe {
+        let mut set: libc::sigset_t = std::mem::zeroed();
+        libc::sigemptyset(&mut set);
+        libc::sigaddset(&mut set, libc::SIGINT);
+        libc::sigaddset(&mut set, libc::SIGTERM);
+        libc::sigaddset(&mut set, libc::SIGHUP);
+        libc::sigaddset(&mut set, libc::SIGWINCH);
+        libc::pthread_sigmask(libc::SIG_BLOCK, &set, std::ptr::null_mut());
+        set
+    };
+    thread::spawn(move || {
+        loop {
+            let mut signal = 0;
+            if unsafe { libc::sigwait(&set, &mut signal) } != 0 {
+                break;
+            }
+            let event = if signal == libc::SIGWINCH {
+                Event::Resized
+            } else {
+                debug_println!("收到信号 {}，结束目标程序", signal);
+                Event::Stop
+            };
+            if events.send(event).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// 控制台事件处理函数使用的发送端
+#[cfg(windows)]
+static STOP_EVENTS: OnceLock<mpsc::Sender<Event>> = OnceLock::new();
+
+#[cfg(windows)]
+unsafe extern "system" fn console_ctrl_handler(ctrl_type: u32) -> BOOL {
+    if let Some(events) = STOP_EVENTS.get() {
+        let _ = events.send(Event::Stop);
+    }
+    // 关闭窗口、注销和关机时处理函数返回后进程就会被结束，在这里等目标程序退出
+    if matches!(ctrl_type, CTRL_CLOSE_EVENT | CTRL_LOGOFF_EVENT | CTRL_SHUTDOWN_EVENT) {
+        thread::sleep(child::SHUTDOWN_GRACE);
+    }
+    TRUE
+}
+
+/// 把 Ctrl+C、Ctrl+Break 和关闭控制台窗口等事件转换成 [`Event::Stop`]，
+/// 控制台窗口的大小改变时发送 [`Event::Resized`]
+///
+/// 窗口大小每隔 [`RESIZE_POLL_INTERVAL`] 检查一次，而不是用 `ReadConsoleInputW` 等待 `WINDOW_BUFFER_SIZE_EVENT`：
+/// 这个事件和按键一起放在控制台的输入队列里，转发 stdin 的线程用 `ReadConsoleW` 读取同一个队列，
+/// 会把它丢掉，另开一个线程读取输入事件又会和它抢按键；而且传统控制台只在缓冲区大小改变时发出这个事件，
+/// 只拖动窗口（缓冲区比窗口高时）不会发出，[`terminal_size`] 报告的却是窗口大小。
+/// 输出没有连接到控制台时没有可以跟随的大小，pty-proxy-child 使用固定大小，所以不启动检查线程。
+#[cfg(windows)]
+fn handle_signals(events: mpsc::Sender<Event>) {
+    let _ = STOP_EVENTS.set(events.clone());
+    unsafe {
+        SetConsoleCtrlHandler(Some(console_ctrl_handler), TRUE);
+    }
+    let Some(mut size) = terminal_size() else {
+        return;
+    };
+    thread::spawn(move || {
+        loop {
+            thread::sleep(RESIZE_POLL_INTERVAL);
+            if let Some(current) = terminal_size().filter(|current| *current != size) {
+                size = current;
+                if events.send(Event::Resized).is_err() {
+                    break;
+                }
+            }
         }
+    });
+}
+
+/// 本进程输出所在控制台窗口的大小（列数, 行数），输出没有连接到控制台时返回 None
+#[cfg(windows)]
+fn terminal_size() -> Option<(u16, u16)> {
+    let mut info: CONSOLE_SCREEN_BUFFER_INFO = unsafe { zeroed() };
+    if unsafe { GetConsoleScreenBufferInfo(GetStdHandle(STD_OUTPUT_HANDLE), &mut info) } == 0 {
+        return None;
+    }
+    let cols = info.srWindow.Right - info.srWindow.Left + 1;
+    let rows = info.srWindow.Bottom - info.srWindow.Top + 1;
+    (cols > 0 && rows > 0).then_some((cols as u16, rows as u16))
+}
+
+/// 本进程输出所在终端的大小（列数, 行数），输出没有连接到终端时返回 None
+#[cfg(unix)]
+fn terminal_size() -> Option<(u16, u16)> {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } != 0 {
+        return None;
     }
+    (size.ws_col > 0 && size.ws_row > 0).then_some((size.ws_col, size.ws_row))
 }
 
 fn main() {
+    // 由自身重新启动时运行子程序的逻辑
+    let mut args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some(child::SUBCOMMAND) {
+        args.remove(1);
+        child::main(args);
+    }
+
     #[cfg(feature = "debug_mode")]
     {
         use std::panic;
@@ -105,7 +445,7 @@ fn main() {
         );
     }
     // 设置控制台属性
-    #[cfg(feature = "color")]
+    #[cfg(all(windows, feature = "color"))]
     unsafe {
         let handle = GetStdHandle(STD_OUTPUT_HANDLE);
         let mut console_mode: CONSOLE_MODE = 0;
@@ -121,8 +461,6 @@ fn main() {
         .expect("无法获取可执行文件名")
         .to_str()
         .expect("无法将可执行文件名转换为字符串");
-    let child_exe_path = exe_dir.join("pty-proxy-child.exe");
-    let child_program: String = String::from(child_exe_path.to_str().expect("无法获取子程序路径"));
 
     // 构造配置文件名
     let config_file_name = format!("{}.toml", exe_name);
@@ -135,242 +473,320 @@ fn main() {
     );
     debug_println!("self_exe_dir: {}", exe_dir.to_str().expect("无法调试输出可执行文件目录"));
     debug_println!("self_exe_name: {}", exe_name);
-    debug_println!("child_program: {}", child_program);
     debug_println!();
 
-    // 检查配置文件是否存在
-    let (target_program, target_args) = if config_file_path.exists() {
-        // 读取配置文件内容
-        let config_content = fs::read_to_string(config_file_path).expect("无法读取配置文件");
-
-        // 解析 TOML 配置文件
-        let config: Table = config_content.parse().expect("无法解析配置文件");
-
-        // 从配置中获取 target_program 和 args
-        let target_program = config["target_program"]
-            .as_str()
-            .expect("配置文件中缺少target_program或内容无效");
-
-        let target_args = config["args"]
-            .as_array()
-            .expect("配置文件中缺少args或内容无效")
-            .iter()
-            .map(|v| v.as_str().expect("配置文件中的参数无效"))
-            .collect::<Vec<&str>>()
-            .join(" "); // 将参数列表拼接成一个字符串
-
-        (String::from(target_program), target_args)
+    // 可执行文件旁边有同名的配置文件时直接使用，命令行参数不做解析（重命名后当作目标程序使用），
+    // 否则从命令行读取选项，--config 指定的配置文件中的值可以被命令行上的选项覆盖
+    let config = if config_file_path.exists() {
+        read_config(&config_file_path)
     } else {
-        // 解析命令行参数
-        let args: Vec<String> = std::env::args().collect();
-        if args.len() < 2 {
-            eprintln!(
-                concat!(
-                    "用法: {} <target_program> [args...]\n\n",
-                    "或者在 {}.toml 中编写配置，示例：\n",
-                    "target_program = \"cmd.exe\"\n",
-                    "args = [\"/C\", \"echo helloworld\"]"
-                ),
-                exe_name,
-                exe_name
-            );
-            panic!("至少要1个命令行参数或编写配置文件才能运行！");
-        }
-
-        // 获取目标程序路径和参数
-        let target_program = args[1].clone();
-        let target_args = args[2..].join(" "); // 将参数列表拼接成一个字符串
-
-        (target_program, target_args)
+        let options = match cli::parse(&args[1..]) {
+            Ok(CliCommand::Run(options)) => options,
+            Ok(CliCommand::Help) => {
+                println!("{}", cli::usage(exe_name));
+                exit(0);
+            }
+            Ok(CliCommand::Version) => {
+                println!("{}", cli::version());
+                exit(0);
+            }
+            Err(error) => exit_with_error(&format!("{}\n运行 {} --help 查看用法", error, exe_name)),
+        };
+        let file = options.config_file.as_deref().map(|path| read_config(Path::new(path)));
+        options.into_config(file).unwrap_or_else(|error| {
+            eprintln!("{}\n", cli::usage(exe_name));
+            exit_with_error(&error)
+        })
     };
 
-    debug_println!("配置信息：");
-    debug_println!("target_program: {}", target_program);
-    debug_println!("target_args: {}", target_args);
-    debug_println!();
-
-    // 生成唯一的命名管道名称
-    let pipe_uuid_read: String = format!("{}", Uuid::new_v4()).replace("-", "");
-    let pipe_uuid_write: String = format!("{}", Uuid::new_v4()).replace("-", "");
-    let pipe_name_read: String = format!(r"\\.\pipe\ptyproxy{}", pipe_uuid_read);
-    let pipe_name_write: String = format!(r"\\.\pipe\ptyproxy{}", pipe_uuid_write);
-
-    // 创建命名管道
-    let pipe_handle_read: Arc<Mutex<OwnedHandle>> = Arc::new(
-        Mutex::new(create_named_pipe_read(&pipe_name_read).expect("无法创建命名管道读端"))
-    ); // 包装为线程安全
-    let pipe_handle_write = Arc::new(
-        Mutex::new(create_named_pipe_write(&pipe_name_write).expect("无法创建命名管道写端"))
-    ); // 包装为线程安全
-
-    // 连接命名管道
-    let pipe_handle_connect_read = Arc::clone(&pipe_handle_read);
-    let pipe_handle_connect_write = Arc::clone(&pipe_handle_write);
-    let connect_pipe_thread_handle_read = thread::spawn(move || {
-        connect_named_pipe(&pipe_handle_connect_read).expect("无法连接命名管道读端");
-    });
-    let connect_pipe_thread_handle_write = thread::spawn(move || {
-        connect_named_pipe(&pipe_handle_connect_write).expect("无法连接命名管道写端");
-    });
-
-    debug_println!("开始连接命名管道和启动 pty-proxy-child");
-
-    // 启动 pty-proxy-child
+    // 在创建任何线程之前接管结束信号
+    let (events_tx, events_rx) = mpsc::channel();
+    handle_signals(events_tx.clone());
+
+    // 没有配置大小时，交互使用的终端跟随本进程所在终端的大小，
+    // 没有终端（例如输出被重定向）时使用 pty-proxy-child 默认的固定大小
+    let follow_size = config.size.is_none();
+    let mut size = config.size.or_else(terminal_size);
+    let size_arg = size.map(|(cols, rows)| format!("{}x{}", cols, rows));
+    let timeout_arg = config.timeout.to_string();
+    let agent_flags_arg = config::agent_flag_names(config.agent_config);
+    let env_args: Vec<String> = config.env.iter().map(|(name, value)| format!("{}={}", name, value)).collect();
+
+    let log_level = config.log_level;
+    log_println!(log_level, LogLevel::Info, "配置：{:?}", config);
+    log_println!(log_level, LogLevel::Info, "终端大小：{:?}", size);
+
+    let transport_kind = config.transport;
+    let target_program = config.target_program;
+    let target_args = config.args;
+    let kill_tree = config.kill_tree;
+
+    // 创建通信端点
+    let listen = |direction, name| {
+        transport::listen(transport_kind, direction)
+            .unwrap_or_else(|error| exit_with_failure(log_level, &format!("无法创建{}：{}", name, error)))
+    };
+    let listener_read = listen(Direction::Inbound, "通信读端");
+    let listener_write = listen(Direction::Outbound, "通信写端");
+    let listener_stderr = listen(Direction::Inbound, "标准错误读端");
+    let endpoint_read = listener_read.endpoint();
+    let endpoint_write = listener_write.endpoint();
+    let endpoint_stderr = listener_stderr.endpoint();
+
+    debug_println!("开始启动 pty-proxy-child 和连接通信端点");
+
+    // 启动 pty-proxy-child，没有指定子程序时用隐藏子命令重新启动自身
+    let mut child_args = Vec::new();
+    let child_program = match config.child_program {
+        Some(program) => exe_dir.join(program),
+        None => {
+            child_args.push(child::SUBCOMMAND);
+            exe_path.clone()
+        }
+    };
+    let child_program = child_program.to_str().expect("无法获取子程序路径");
+    if config.backend != PTYBackend::Auto {
+        child_args.extend(["--backend", config::backend_name(config.backend)]);
+    }
+    if kill_tree {
+        child_args.push("--kill-tree");
+    }
+    child_args.extend(["--stderr", endpoint_stderr.as_str()]);
+    if let Some(size_arg) = &size_arg {
+        child_args.extend(["--size", size_arg.as_str()]);
+    }
+    child_args.extend([
+        "--mouse-mode", config::mouse_mode_name(config.mouse_mode),
+        "--timeout", timeout_arg.as_str(),
+        "--agent-flags", agent_flags_arg.as_str(),
+        "--log-level", log_level.name(),
+    ]);
+    if let Some(cwd) = &config.cwd {
+        child_args.extend(["--cwd", cwd.as_str()]);
+    }
+    for env_arg in &env_args {
+        child_args.extend(["--env", env_arg.as_str()]);
+    }
+    child_args.extend([endpoint_read.as_str(), endpoint_write.as_str(), target_program.as_str()]);
+    child_args.extend(target_args.iter().map(String::as_str));
+    log_println!(log_level, LogLevel::Debug, "启动 pty-proxy-child：{} {:?}", child_program, child_args);
     let child_process = create_independent_process(
-        &format!(
-            "\"{}\" {} {} \"{}\" {}",
-            child_program,
-            pipe_uuid_read,
-            pipe_uuid_write,
-            target_program,
-            target_args
-        )
-    ).expect("无法启动 pty-proxy-child.exe");
+        child_program,
+        &child_args,
+        transport_kind == TransportKind::AnonymousPipe,
+        kill_tree
+    ).unwrap_or_else(|error| exit_with_failure(log_level, &format!("启动 pty-proxy-child {} 时出错：{}", child_program, error)));
+
+    // 连接通信端点，匿名管道要在子进程继承之后才能关闭另一端，所以放在启动之后
+    let connect_thread_handle_read = thread::spawn(move || listener_read.accept());
+    let connect_thread_handle_write = thread::spawn(move || listener_write.accept());
+    let connect_thread_handle_stderr = thread::spawn(move || listener_stderr.accept());
+    let connected = |result: io::Result<Box<dyn Transport>>, name| {
+        result.unwrap_or_else(|error| exit_with_failure(log_level, &format!("无法连接{}：{}", name, error)))
+    };
+
+    // 包装为线程安全
+    let pipe_handle_read: Arc<dyn Transport> = Arc::from(
+        connected(connect_thread_handle_read.join().expect("无法 join 读端连接线程"), "通信读端")
+    );
+    let pipe_handle_write: Arc<dyn Transport> = Arc::from(
+        connected(connect_thread_handle_write.join().expect("无法 join 写端连接线程"), "通信写端")
+    );
+    let pipe_handle_stderr: Arc<dyn Transport> = Arc::from(
+        connected(connect_thread_handle_stderr.join().expect("无法 join 标准错误读端连接线程"), "标准错误读端")
+    );
+    debug_println!("连接通信端点完成");
 
-    connect_pipe_thread_handle_read.join().expect("无法 join 读管道连接线程");
-    connect_pipe_thread_handle_write.join().expect("无法 join 写管道连接线程");
-    debug_println!("连接命名管道完成");
+    // 输入流放在共享的槽里，取走后最后一个引用被释放，输入流随之关闭，
+    // pty-proxy-child 看到输入结束就会结束目标程序
+    let pipe_handle_write = MessageWriter::new(pipe_handle_write)
+        .unwrap_or_else(|error| exit_with_failure(log_level, &format!("无法写入通信管道：{}", error)));
+    let pipe_handle_write = Arc::new(Mutex::new(Some(Arc::new(pipe_handle_write))));
 
     // 启动线程监听 stdin 并转发给 pty-proxy-child
     let pipe_handle_stdin = Arc::clone(&pipe_handle_write);
+    let stdin_events = events_tx.clone();
     thread::spawn(move || {
         let mut stdin = io::stdin();
         let mut buffer = [0u8; 1024];
         loop {
-            let n = stdin.read(&mut buffer).expect("无法读取 stdin");
+            let n = match stdin.read(&mut buffer) {
+                Ok(n) => n,
+                Err(e) => {
+                    eprintln!("无法读取 stdin: {e:?}");
+                    0
+                }
+            };
             if n == 0 {
                 break;
             }
-            write_to_pipe(&pipe_handle_stdin, &buffer[..n]).expect("无法写入命名管道");
-            debug_println!("写入命名管道成功");
+            // 每次写入时取一个引用，不影响其他线程关闭输入流
+            let Some(pipe) = pipe_handle_stdin.lock().unwrap().clone() else {
+                return;
+            };
+            if let Err(e) = pipe.send_data(&buffer[..n]) {
+                eprintln!("无法写入通信管道: {e:?}");
+                return;
+            }
+            debug_println!("写入通信管道成功");
         }
+        // 给目标程序留出处理剩余输入的时间，之后再让它退出
+        log_println!(log_level, LogLevel::Debug, "stdin 已关闭");
+        thread::sleep(STDIN_CLOSED_GRACE);
+        let _ = stdin_events.send(Event::Stop);
     });
 
-    // 启动线程接收来自 pty-proxy-child 的数据并输出到 stdout
-    let pipe_handle_stdout = Arc::clone(&pipe_handle_read);
+    // 启动线程接收来自 pty-proxy-child 的消息，把输出写到 stdout，把目标程序的退出状态交给主线程，
+    // 两个输出线程都结束时 output_done 全部被释放，主线程据此得知输出已经转发完
+    let (output_done, output_drained) = mpsc::channel();
+    let stderr_done = output_done.clone();
+    let mut pipe_handle_stdout = MessageReader::new(Arc::clone(&pipe_handle_read));
+    let pipe_handle_reply = Arc::clone(&pipe_handle_write);
+    let pong_events = events_tx.clone();
+    let output_mode = config.output_mode;
     thread::spawn(move || {
         let mut stdout = io::stdout();
-        let mut buffer = [0u8; 1024];
+        let mut stripper = (output_mode == OutputMode::Plain).then(EscapeStripper::new);
         loop {
-            let n = read_from_pipe(&pipe_handle_stdout, &mut buffer).expect("无法读取命名管道");
-            if n == 0 {
-                break;
+            let message = match pipe_handle_stdout.read_message() {
+                Ok(Some(message)) => message,
+                Ok(None) => break,
+                Err(e) => {
+                    eprintln!("无法读取通信管道: {e}");
+                    break;
+                }
+            };
+            match message {
+                Message::Data(bytes) => {
+                    let bytes = match &mut stripper {
+                        Some(stripper) => stripper.strip(&bytes),
+                        None => bytes,
+                    };
+                    stdout.write_all(&bytes).expect("无法写入 stdout");
+                    stdout.flush().expect("无法刷新 stdout");
+                }
+                Message::Exit(status) => {
+                    debug_println!("目标程序的退出状态：{}", status);
+                    let _ = output_done.send(status);
+                }
+                Message::Error(text) | Message::Log(text) => eprintln!("pty-proxy-child: {}", text),
+                Message::Ping(value) => {
+                    if let Some(pipe) = pipe_handle_reply.lock().unwrap().clone() {
+                        let _ = pipe.send(&Message::Pong(value));
+                    }
+                }
+                Message::Pong(value) => {
+                    let _ = pong_events.send(Event::Pong(value));
+                }
+                _ => {}
             }
-            stdout.write_all(&buffer[..n]).expect("无法写入 stdout");
-            stdout.flush().expect("无法刷新 stdout");
         }
     });
 
-    // 等待 pty-proxy-child 进程结束
-    unsafe {
-        WaitForSingleObject(child_process.as_raw_handle() as HANDLE, INFINITE);
-    }
-    // 获取进程退出代码
-    let mut exit_code: u32 = 0;
-    let success = unsafe {
-        GetExitCodeProcess(child_process.as_raw_handle() as HANDLE, &mut exit_code)
-    };
-    if success != 0 {
-        debug_println!("子进程退出，退出代码：{}，本进程也跟随退出...", exit_code);
-        exit(exit_code as i32);
-    } else {
-        panic!("获取进程退出代码失败：{:?}", io::Error::last_os_error());
-    }
-}
-
-// 创建命名管道
-fn create_named_pipe_read(pipe_name: &str) -> io::Result<OwnedHandle> {
-    let pipe_name = to_wstr(pipe_name);
-    let pipe_handle: HANDLE = unsafe {
-        CreateNamedPipeW(
-            pipe_name.as_ptr(),
-            PIPE_ACCESS_INBOUND,
-            PIPE_READMODE_BYTE | PIPE_WAIT,
-            1,
-            4096,
-            4096,
-            0,
-            null_mut()
-        )
-    };
-    if pipe_handle == INVALID_HANDLE_VALUE {
-        return Err(io::Error::last_os_error());
-    }
-    unsafe { Ok(OwnedHandle::from_raw_handle(pipe_handle as *mut c_void)) }
-}
-fn create_named_pipe_write(pipe_name: &str) -> io::Result<OwnedHandle> {
-    let pipe_name = to_wstr(pipe_name);
-    let pipe_handle: HANDLE = unsafe {
-        CreateNamedPipeW(
-            pipe_name.as_ptr(),
-            PIPE_ACCESS_OUTBOUND,
-            PIPE_TYPE_BYTE | PIPE_WAIT,
-            1,
-            4096,
-            4096,
-            0,
-            null_mut()
-        )
-    };
-    if pipe_handle == INVALID_HANDLE_VALUE {
-        return Err(io::Error::last_os_error());
-    }
-    unsafe { Ok(OwnedHandle::from_raw_handle(pipe_handle as *mut c_void)) }
-}
-
-// 连接命名管道
-fn connect_named_pipe(pipe_handle: &Arc<Mutex<OwnedHandle>>) -> io::Result<()> {
-    let pipe_handle = pipe_handle.lock().unwrap();
-    let result = unsafe { ConnectNamedPipe(pipe_handle.as_raw_handle() as HANDLE, null_mut()) };
-    if result == 0 {
-        return Err(io::Error::last_os_error());
-    }
-    Ok(())
-}
+    // 启动线程接收目标程序单独的标准错误并输出到 stderr
+    let mut pipe_handle_stderr = MessageReader::new(pipe_handle_stderr);
+    thread::spawn(move || {
+        let _stderr_done = stderr_done;
+        let mut stderr = io::stderr();
+        let mut stripper = (output_mode == OutputMode::Plain).then(EscapeStripper::new);
+        loop {
+            match pipe_handle_stderr.read_message() {
+                Ok(Some(Message::Data(bytes))) => {
+                    let bytes = match &mut stripper {
+                        Some(stripper) => stripper.strip(&bytes),
+                        None => bytes,
+                    };
+                    stderr.write_all(&bytes).expect("无法写入 stderr");
+                    stderr.flush().expect("无法刷新 stderr");
+                }
+                Ok(Some(Message::Error(text))) => eprintln!("pty-proxy-child: {}", text),
+                Ok(Some(_)) => {}
+                Ok(None) => break,
+                Err(e) => {
+                    eprintln!("无法读取标准错误管道: {e}");
+                    break;
+                }
+            }
+        }
+    });
 
-// 写入命名管道
-fn write_to_pipe(pipe_handle: &Arc<Mutex<OwnedHandle>>, data: &[u8]) -> io::Result<()> {
-    let pipe_handle = pipe_handle.lock().unwrap();
-    let mut bytes_written: u32 = 0;
-    let result = unsafe {
-        WriteFile(
-            pipe_handle.as_raw_handle() as HANDLE,
-            data.as_ptr() as *const _,
-            data.len() as u32,
-            &mut bytes_written,
-            null_mut()
-        )
-    };
-    if result == 0 {
-        return Err(io::Error::last_os_error());
-    }
-    Ok(())
-}
+    // 启动线程定时发送心跳
+    let ping_events = events_tx.clone();
+    thread::spawn(move || {
+        loop {
+            thread::sleep(PING_INTERVAL);
+            if ping_events.send(Event::Ping).is_err() {
+                break;
+            }
+        }
+    });
 
-// 从命名管道读取
-fn read_from_pipe(pipe_handle: &Arc<Mutex<OwnedHandle>>, buffer: &mut [u8]) -> io::Result<usize> {
-    let pipe_handle = pipe_handle.lock().unwrap();
-    let mut bytes_read: u32 = 0;
-    let result = unsafe {
-        ReadFile(
-            pipe_handle.as_raw_handle() as HANDLE,
-            buffer.as_mut_ptr() as *mut _,
-            buffer.len() as u32,
-            &mut bytes_read,
-            null_mut()
-        )
-    };
+    // 启动线程等待 pty-proxy-child 进程结束
+    thread::spawn(move || {
+        let _ = events_tx.send(Event::Exited(wait_for_process(child_process)));
+    });
 
-    if result == 0 {
-        let error = io::Error::last_os_error();
-        if error.raw_os_error() == Some(ERROR_BROKEN_PIPE as i32) {
-            // 管道已断开连接，返回 0
-            return Ok(0);
-        } else {
-            // 其他错误，返回错误
-            return Err(error);
+    // 同一时间只有一个心跳等待回复，记录它的数值和发送时间。
+    // 没有响应时只报告，不结束目标程序：目标程序不读取输入时 pty-proxy-child 也可能暂时收不到心跳
+    let mut ping = 0u32;
+    let mut ping_sent: Option<Instant> = None;
+    let mut unresponsive = false;
+    for event in events_rx {
+        match event {
+            Event::Stop => {
+                log_println!(log_level, LogLevel::Debug, "请求结束目标程序");
+                let mut pipe = pipe_handle_write.lock().unwrap();
+                // 发送失败时关闭输入流，pty-proxy-child 同样会结束目标程序
+                if pipe.as_ref().is_some_and(|pipe| pipe.send(&Message::Signal(Signal::Terminate)).is_err()) {
+                    pipe.take();
+                }
+            }
+            Event::Resized if !follow_size => {}
+            Event::Resized => {
+                let Some((cols, rows)) = terminal_size().filter(|current| Some(*current) != size) else {
+                    continue;
+                };
+                size = Some((cols, rows));
+                log_println!(log_level, LogLevel::Debug, "终端大小改变为 {}x{}", cols, rows);
+                if let Some(pipe) = pipe_handle_write.lock().unwrap().as_ref() {
+                    let _ = pipe.send(&Message::Resize { cols, rows });
+                }
+            }
+            Event::Ping => match ping_sent {
+                Some(sent) if !unresponsive && sent.elapsed() >= PING_TIMEOUT => {
+                    log_println!(log_level, LogLevel::Error, "pty-proxy-child 已经 {} 秒没有响应", sent.elapsed().as_secs());
+                    unresponsive = true;
+                }
+                Some(_) => {}
+                None => {
+                    ping = ping.wrapping_add(1);
+                    ping_sent = Some(Instant::now());
+                    if let Some(pipe) = pipe_handle_write.lock().unwrap().as_ref() {
+                        let _ = pipe.send(&Message::Ping(ping));
+                    }
+                }
+            },
+            Event::Pong(value) if value != ping => {}
+            Event::Pong(_) => {
+                if unresponsive {
+                    log_println!(log_level, LogLevel::Error, "pty-proxy-child 恢复响应");
+                    unresponsive = false;
+                }
+                ping_sent = None;
+            }
+            Event::Exited(Ok(mut exit_code)) => {
+                // 优先使用 pty-proxy-child 报告的目标程序退出状态
+                let deadline = Instant::now() + OUTPUT_DRAIN_TIMEOUT;
+                while let Ok(status) = output_drained.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                    exit_code = status.exit_code() as i32;
+                }
+                log_println!(log_level, LogLevel::Debug, "pty-proxy-child 已退出，退出代码：{}", exit_code);
+                exit(exit_code);
+            }
+            Event::Exited(Err(error)) => {
+                exit_with_failure(log_level, &format!("获取 pty-proxy-child 的退出代码失败：{}", error));
+            }
         }
     }
-
-    Ok(bytes_read as usize)
+    unreachable!("事件通道不会在子进程退出前关闭");
 }
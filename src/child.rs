@@ -0,0 +1,487 @@
+//! pty-proxy-child 的逻辑
+//!
+//! 既可以由独立的 pty-proxy-child 运行，也可以由 pty-proxy 通过隐藏子命令
+//! [`SUBCOMMAND`] 重新启动自身来运行，这样发行时只需要一个可执行文件。
+
+use std::process::exit;
+use std::sync::{ Arc, Weak };
+use std::time::{ Duration, Instant };
+#[cfg(feature = "debug_mode")]
+use std::io;
+
+use winptyrs::{ AgentConfig, ExitStatus, MouseMode, PTY, PTYEvent, PTYBackend, PtyCommand, ReadOutcome };
+
+use crate::config::{ self, DEFAULT_TIMEOUT, LogLevel };
+use crate::protocol::{ Message, MessageReader, MessageWriter, Signal };
+use crate::transport::{ self, Direction, Transport };
+
+/// pty-proxy 重新启动自身时使用的隐藏子命令，后面跟着子程序的参数
+pub const SUBCOMMAND: &str = "__pty_proxy_child";
+
+/// 输入流关闭后留给目标程序自行退出的时间，超时后强制结束
+pub const SHUTDOWN_GRACE: Duration = Duration::from_secs(3);
+
+/// 无法启动目标程序时的退出代码，和 shell 找不到命令时相同
+pub const SPAWN_FAILED_EXIT_CODE: i32 = 127;
+
+/// 没有指定 --size 时终端的大小，足够宽的一行不会让输出被折行
+pub const DEFAULT_SIZE: (u16, u16) = (1024, 2);
+
+macro_rules! debug_println {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "debug_mode")]
+        {
+            println!($($arg)*);
+        }
+    };
+}
+macro_rules! debug_pause {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "debug_mode")]
+        {
+            println!($($arg)*);
+            let mut buf = vec![0;1];
+            use std::io::Read;
+            io::stdin().lock().read_exact(&mut buf).unwrap();
+        }
+    };
+}
+
+/// 子程序的入口，`args` 与独立的 pty-proxy-child 收到的命令行参数相同（包括程序名）
+///
+/// 在 PTY 中启动目标程序，通过 pty-proxy 传来的端点用 [`crate::protocol`] 的消息转发输入输出，
+/// 目标程序退出后把退出状态发给 pty-proxy，并以相同的退出代码结束本进程。
+/// 选项无效时以 2 退出，无法启动目标程序时以 [`SPAWN_FAILED_EXIT_CODE`] 退出，原因都会发给 pty-proxy。
+///
+/// 收到 [`Signal::Terminate`] 或者 pty-proxy 关闭输入流（例如它被强制结束）时，
+/// 先请求目标程序退出，[`SHUTDOWN_GRACE`] 后仍未退出则强制结束。
+/// Unix 上收到 SIGINT、SIGTERM 或 SIGHUP 时也一样。
+pub fn main(mut args: Vec<String>) -> ! {
+    #[cfg(feature = "debug_mode")]
+    {
+        use std::panic;
+        panic::set_hook(
+            Box::new(|panic_info| {
+                println!("\n{}", panic_info);
+                debug_pause!("Press enter to exit...");
+            })
+        );
+    }
+    // 可选的 --backend <后端> 指定后端，默认依次尝试各个伪终端后端，
+    // pipes 表示不使用伪终端，直接用管道启动目标进程
+    let mut pty_backend = PTYBackend::Auto;
+    // 可选的 --kill-tree 表示本进程退出时结束目标程序启动的所有进程
+    let mut kill_tree = false;
+    // 可选的 --stderr <端点> 表示目标程序的标准错误单独转发到这个端点
+    let mut endpoint_stderr = None;
+    // 可选的 --size <列数>x<行数> 指定终端的初始大小
+    let mut size = DEFAULT_SIZE;
+    // 可选的 --mouse-mode <鼠标模式>、--timeout <毫秒> 和 --agent-flags <标志,...> 是 winpty 的设置，
+    // 标志中有 conerr 时伪终端后端也把标准错误和标准输出分开
+    let mut mouse_mode = MouseMode::WINPTY_MOUSE_MODE_NONE;
+    let mut timeout = DEFAULT_TIMEOUT;
+    let mut agent_config = AgentConfig::WINPTY_FLAG_COLOR_ESCAPES;
+    // 可选的 --cwd <目录> 和 --env <名称>=<值>（可以重复）设置目标程序的工作目录和环境变量
+    let mut cwd = None;
+    let mut env = Vec::new();
+    // 可选的 --log-level <级别> 指定哪些日志要发给 pty-proxy 显示
+    let mut log_level = LogLevel::default();
+    // 无效的选项不能马上报告，要先连接到 pty-proxy，所以只记下第一个错误，继续找出通信端点
+    let mut error = None;
+    loop {
+        match args.get(1).map(String::as_str) {
+            Some("--backend") if args.len() >= 3 => {
+                parse_option(config::parse_backend(&args[2]), &mut pty_backend, &mut error);
+                args.drain(1..3);
+            }
+            Some("--kill-tree") => {
+                kill_tree = true;
+                args.remove(1);
+            }
+            Some("--stderr") if args.len() >= 3 => {
+                endpoint_stderr = Some(args[2].clone());
+                args.drain(1..3);
+            }
+            Some("--size") if args.len() >= 3 => {
+                let value = parse_size(&args[2]).ok_or_else(|| format!("无效的终端大小 {:?}，格式为 <列数>x<行数>", args[2]));
+                parse_option(value, &mut size, &mut error);
+                args.drain(1..3);
+            }
+            Some("--mouse-mode") if args.len() >= 3 => {
+                parse_option(config::parse_mouse_mode(&args[2]), &mut mouse_mode, &mut error);
+                args.drain(1..3);
+            }
+            Some("--timeout") if args.len() >= 3 => {
+                let value = args[2].parse().ok().filter(|&timeout| timeout > 0)
+                    .ok_or_else(|| format!("无效的等待时间 {:?}", args[2]));
+                parse_option(value, &mut timeout, &mut error);
+                args.drain(1..3);
+            }
+            Some("--agent-flags") if args.len() >= 3 => {
+                let value = config::parse_agent_flags(args[2].split(',').filter(|name| !name.is_empty()));
+                parse_option(value, &mut agent_config, &mut error);
+                args.drain(1..3);
+            }
+            Some("--cwd") if args.len() >= 3 => {
+                cwd = Some(args[2].clone());
+                args.drain(1..3);
+            }
+            Some("--env") if args.len() >= 3 => {
+                match config::parse_env(&args[2]) {
+                    Ok(item) => env.push(item),
+                    Err(e) => {
+                        error.get_or_insert(e);
+                    }
+                }
+                args.drain(1..3);
+            }
+            Some("--log-level") if args.len() >= 3 => {
+                parse_option(args[2].parse(), &mut log_level, &mut error);
+                args.drain(1..3);
+            }
+            _ => break,
+        }
+    }
+
+    if args.len() < 4 {
+        eprintln!(
+            "用法: pty-proxy-child [--backend <auto|conpty|winpty|unix|pipes>] [--kill-tree] [--stderr <endpoint_stderr>] [--size <cols>x<rows>] [--mouse-mode <none|auto|force>] [--timeout <ms>] [--agent-flags <plain_output,color_escapes,conerr>] [--cwd <dir>] [--env <name>=<value>]... [--log-level <error|info|debug>] <endpoint_write> <endpoint_read> <target_program> [args...]"
+        );
+        eprintln!("至少要3个命令行参数才能运行！");
+        exit(2);
+    }
+
+    let endpoint_write = &args[1];
+    let endpoint_read = &args[2];
+    let target_program = &args[3];
+    let target_args = &args[4..];
+
+    // 在创建任何线程之前屏蔽结束信号
+    #[cfg(unix)]
+    let stop_signals = block_stop_signals();
+
+    println!("虚拟终端代理-子程序  请不要关闭本窗口！");
+    println!("pty-proxy-child  please DO NOT close this window!");
+    println!();
+    println!("endpoint_write: {}", endpoint_write);
+    println!("endpoint_read: {}", endpoint_read);
+    println!("endpoint_stderr: {:?}", endpoint_stderr);
+    println!("target_program: {}", target_program);
+    println!("target_args: {:?}", target_args);
+    println!("size: {}x{}", size.0, size.1);
+    println!("cwd: {:?}", cwd);
+    println!("env: {:?}", env);
+    println!();
+
+    // 连接到 pty-proxy，发送的每条流都以握手消息开头
+    let pipe_handle_write: Arc<dyn Transport> = Arc::from(
+        transport::connect(endpoint_write, Direction::Inbound).expect("无法连接到通信写端")
+    ); // 包装为线程安全
+    let pipe_handle_write = Arc::new(MessageWriter::new(pipe_handle_write).expect("无法写入通信管道"));
+    let pipe_handle_read: Arc<dyn Transport> = Arc::from(
+        transport::connect(endpoint_read, Direction::Outbound).expect("无法连接到通信读端")
+    ); // 包装为线程安全
+    let pipe_handle_stderr: Option<Arc<MessageWriter>> = endpoint_stderr.map(|endpoint| {
+        let transport = transport::connect(&endpoint, Direction::Inbound).expect("无法连接到标准错误端点");
+        Arc::new(MessageWriter::new(Arc::from(transport)).expect("无法写入标准错误管道"))
+    });
+
+    // 选项无效时不启动目标程序，和 pty-proxy 检查命令行出错时一样以 2 退出
+    if let Some(error) = error {
+        report_error(&pipe_handle_write, error);
+        exit(2);
+    }
+
+    println!("工作中...");
+    println!("working...");
+
+    // 日志显示在本进程的窗口中，级别够高时也发给 pty-proxy，显示在它的 stderr 上
+    let log = |level: LogLevel, text: String| {
+        println!("{}", text);
+        if log_level >= level {
+            let _ = pipe_handle_write.send(&Message::Log(text));
+        }
+    };
+
+    // 创建 PTY 并启动目标进程，参数由 PtyCommand 按后端的规则加上引号
+    // 在主线程中启动，这样 Linux 上目标程序只会在本进程退出时才被内核结束，
+    // 目标程序启动的其他进程在 PTY 被释放时结束
+    let mut command = PtyCommand::new(target_program);
+    command
+        .args(target_args)
+        .envs(env)
+        .size(size.0 as i32, size.1 as i32)
+        .backend(pty_backend)
+        .mouse_mode(mouse_mode)
+        .timeout(timeout)
+        .agent_config(agent_config)
+        .kill_on_drop(kill_tree);
+    if let Some(cwd) = &cwd {
+        command.current_dir(cwd);
+    }
+    let pty = match command.spawn() {
+        Ok(pty) => pty,
+        Err(e) => {
+            report_error(&pipe_handle_write, format!("无法在 PTY 中启动目标程序 {}: {}", target_program, e));
+            exit(SPAWN_FAILED_EXIT_CODE);
+        }
+    };
+
+    // 自动选择时说明用了哪个后端，以及前面的后端为什么不能用
+    for (backend, error) in pty.backend_fallbacks() {
+        log(LogLevel::Info, format!("后端 {} 不可用：{}", config::backend_name(*backend), error));
+    }
+    log(LogLevel::Info, format!("使用后端 {}", config::backend_name(pty.get_backend())));
+    let use_pipes = pty.get_backend() == PTYBackend::Pipes;
+
+    debug_println!("目标进程启动成功");
+
+    // PTY 的各个方法都只需要共享引用，读写线程之间不再需要加锁
+    let pty = Arc::new(pty);
+
+    #[cfg(unix)]
+    handle_stop_signals(stop_signals, Arc::downgrade(&pty));
+
+    // 订阅 PTY 输出，读取线程一拿到数据就会送过来，不需要轮询
+    let events = pty.subscribe().expect("无法订阅 PTY 输出");
+
+    // 管道后端（以及标志中有 conerr 的伪终端后端）的标准错误是单独的，用单独的线程阻塞读取，
+    // 转发到 pty-proxy 的标准错误端点，没有这个端点时和输出混在一起
+    let stderr_thread_handle = if use_pipes || agent_config.contains(AgentConfig::WINPTY_FLAG_CONERR) {
+        let pty_stderr = pty.clone();
+        let pipe_handle_stderr = pipe_handle_stderr.unwrap_or_else(|| Arc::clone(&pipe_handle_write));
+        Some(
+            std::thread::spawn(move || {
+                // 阻塞读取只会返回数据或 EOF，读到 EOF 说明标准错误已经全部转发，
+                // 和输出一样按原始字节转发
+                let mut buf = [0u8; 4096];
+                loop {
+                    match pty_stderr.try_read_stderr_bytes(&mut buf, true) {
+                        Ok(ReadOutcome::Data(count)) => {
+                            if let Err(e) = pipe_handle_stderr.send_data(&buf[..count]) {
+                                eprintln!("无法写入通信管道: {e:?}");
+                                break;
+                            }
+                        }
+                        Ok(ReadOutcome::WouldBlock) => continue,
+                        Ok(ReadOutcome::Eof) => break,
+                        Err(e) => {
+                            report_error(&pipe_handle_stderr, format!("无法读取标准错误: {e:?}"));
+                            break;
+                        }
+                    }
+                }
+            })
+        )
+    } else {
+        None
+    };
+
+    // 启动线程转发 PTY 输出到 pty-proxy，输出全部转发完时返回 true
+    let pipe_handle_output = Arc::clone(&pipe_handle_write);
+    let ptyread_thread_handle = std::thread::spawn(move || {
+        let mut output_ended = false;
+        // 输出按原始字节转发，不做任何编码转换
+        for event in events {
+            match event {
+                PTYEvent::Output(bytes) => {
+                    debug_println!("收到数据，转发..");
+                    // pty-proxy 已经不在了，输出无处可去，由主线程结束目标进程
+                    if let Err(e) = pipe_handle_output.send_data(&bytes) {
+                        eprintln!("无法写入通信管道: {e:?}");
+                        break;
+                    }
+                }
+                PTYEvent::Error(e) => {
+                    report_error(&pipe_handle_output, format!("无法读取 PTY 输出: {e:?}"));
+                    break;
+                }
+                PTYEvent::Exit(_) => {
+                    debug_println!("监听到进程退出");
+                    output_ended = true;
+                    break;
+                }
+            }
+        }
+        // 等待剩下的标准错误转发完
+        if let Some(handle) = stderr_thread_handle {
+            let _ = handle.join();
+        }
+        output_ended
+    });
+
+    // 启动线程从 pty-proxy 读取消息，把输入写入 PTY
+    // 只持有弱引用，主线程退出前释放 PTY 时不用等这个线程
+    let pty_input = Arc::downgrade(&pty);
+    let mut pipe_handle_input = MessageReader::new(Arc::clone(&pipe_handle_read));
+    let pipe_handle_reply = Arc::clone(&pipe_handle_write);
+    std::thread::spawn(move || {
+        loop {
+            let message = match pipe_handle_input.read_message() {
+                Ok(Some(message)) => message,
+                Ok(None) => break,
+                Err(e) => {
+                    report_error(&pipe_handle_reply, format!("无法读取通信管道: {e}"));
+                    break;
+                }
+            };
+            let Some(pty_input) = pty_input.upgrade() else {
+                return;
+            };
+            match message {
+                Message::Data(bytes) => {
+                    debug_println!("收到输入数据");
+                    if let Err(e) = pty_input.write_bytes(&bytes) {
+                        // 目标进程已经退出时写入会失败，交给读取线程收尾
+                        report_error(&pipe_handle_reply, format!("无法写入 PTY: {e:?}"));
+                        return;
+                    }
+                    debug_println!("成功写入PTY");
+                }
+                Message::Resize { cols, rows } => {
+                    debug_println!("调整终端大小为 {}x{}", cols, rows);
+                    if let Err(e) = pty_input.set_size(cols as i32, rows as i32) {
+                        report_error(&pipe_handle_reply, format!("无法调整终端大小: {e:?}"));
+                    }
+                }
+                Message::Signal(signal) => {
+                    debug_println!("收到信号 {:?}", signal);
+                    let result = match signal {
+                        Signal::Interrupt => pty_input.send_interrupt(),
+                        Signal::Terminate => pty_input.terminate(SHUTDOWN_GRACE).map(|_| ()),
+                        Signal::Kill => pty_input.kill(),
+                    };
+                    if let Err(e) = result {
+                        report_error(&pipe_handle_reply, format!("无法向目标进程发送信号: {e:?}"));
+                    }
+                }
+                Message::Ping(value) => {
+                    let _ = pipe_handle_reply.send(&Message::Pong(value));
+                }
+                _ => {}
+            }
+        }
+        // 输入流关闭说明 pty-proxy 要结束了，让目标程序也退出
+        debug_println!("输入流已关闭，结束目标进程");
+        stop_target(&pty_input);
+    });
+
+    // 等待输出全部转发完，读取线程在目标进程退出或者输出无处可去时结束
+    let output_ended = ptyread_thread_handle.join().unwrap_or(false);
+    // 输出结束时目标进程不一定已经退出（Windows 上两者并不同步），再等它一会儿
+    let mut status = if output_ended {
+        pty.wait_timeout(SHUTDOWN_GRACE).unwrap_or(None)
+    } else {
+        None
+    };
+    if status.is_none() {
+        debug_println!("输出已无法转发或目标进程没有退出，结束目标进程");
+        let _ = pty.terminate(SHUTDOWN_GRACE);
+        status = pty.exit_status().unwrap_or(None);
+    }
+
+    // 退出代码沿用 shell 的约定，被信号终止时为 128 加信号编号
+    let exit_code = match status {
+        Some(status) => {
+            log(LogLevel::Info, describe_exit_status(status));
+            let _ = pipe_handle_write.send(&Message::Exit(status));
+            status.exit_code() as i32
+        }
+        None => {
+            report_error(&pipe_handle_write, String::from("无法获取目标程序的退出状态"));
+            101
+        }
+    };
+    // exit 不会运行析构函数，要先释放 PTY
+    release_pty(pty);
+
+    debug_pause!("进程即将退出，退出代码：{}，按回车键退出...", exit_code);
+    exit(exit_code);
+}
+
+/// 解析 `<列数>x<行数>` 格式的终端大小，两者都不能为 0
+pub fn parse_size(size: &str) -> Option<(u16, u16)> {
+    let (cols, rows) = size.split_once('x')?;
+    let (cols, rows) = (cols.parse().ok()?, rows.parse().ok()?);
+    (cols > 0 && rows > 0).then_some((cols, rows))
+}
+
+/// 释放 PTY，开启了 --kill-tree 时目标程序启动的进程随之结束
+///
+/// 其他线程可能正临时持有 PTY（例如正在结束目标程序），最多等它们 [`SHUTDOWN_GRACE`]。
+fn release_pty(mut pty: Arc<PTY>) {
+    let deadline = Instant::now() + SHUTDOWN_GRACE;
+    while let Err(shared) = Arc::try_unwrap(pty) {
+        if Instant::now() >= deadline {
+            return;
+        }
+        pty = shared;
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+/// 解析成功时保存选项的值，否则记下错误，只保留第一个
+fn parse_option<T>(value: Result<T, String>, option: &mut T, error: &mut Option<String>) {
+    match value {
+        Ok(value) => *option = value,
+        Err(e) => {
+            error.get_or_insert(e);
+        }
+    }
+}
+
+/// 显示错误，并把它发给 pty-proxy，本进程没有自己的标准错误时也能看到
+fn report_error(pipe: &MessageWriter, text: String) {
+    eprintln!("{}", text);
+    let _ = pipe.send(&Message::Error(text));
+}
+
+/// 请求目标程序退出，[`SHUTDOWN_GRACE`] 后仍未退出则强制结束
+fn stop_target(pty: &Weak<PTY>) {
+    let Some(pty) = pty.upgrade() else {
+        return;
+    };
+    if let Err(e) = pty.terminate(SHUTDOWN_GRACE) {
+        eprintln!("无法结束目标进程: {e:?}");
+    }
+}
+
+/// 屏蔽 SIGINT、SIGTERM 和 SIGHUP，必须在创建其他线程之前调用
+///
+/// 目标程序启动时会解除屏蔽，不受影响。
+#[cfg(unix)]
+fn block_stop_signals() -> libc::sigset_t {
+    unsafe {
+        let mut set: libc::sigset_t = std::mem::zeroed();
+        libc::sigemptyset(&mut set);
+        libc::sigaddset(&mut set, libc::SIGINT);
+        libc::sigaddset(&mut set, libc::SIGTERM);
+        libc::sigaddset(&mut set, libc::SIGHUP);
+        libc::pthread_sigmask(libc::SIG_BLOCK, &set, std::ptr::null_mut());
+        set
+    }
+}
+
+/// 用专门的线程接收 [`block_stop_signals`] 屏蔽的信号，收到后结束目标程序
+///
+/// pty-proxy 开启 --kill-tree 后退出时，内核会向本进程发送 SIGTERM。
+#[cfg(unix)]
+fn handle_stop_signals(set: libc::sigset_t, pty: Weak<PTY>) {
+    std::thread::spawn(move || {
+        let mut signal = 0;
+        if unsafe { libc::sigwait(&set, &mut signal) } == 0 {
+            debug_println!("收到信号 {}，结束目标进程", signal);
+            stop_target(&pty);
+        }
+    });
+}
+
+/// 描述目标程序结束的原因
+fn describe_exit_status(status: ExitStatus) -> String {
+    match status {
+        ExitStatus::Exited(code) => format!("目标程序已退出，退出代码：{}", code),
+        ExitStatus::Signaled(signal) => format!("目标程序被信号 {} 终止", signal),
+        ExitStatus::NtStatus(code) => format!("目标程序崩溃，NTSTATUS：{:#010X}", code),
+        ExitStatus::Killed => String::from("目标程序没有在规定时间内退出，已被强制结束"),
+    }
+}
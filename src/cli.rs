@@ -0,0 +1,196 @@
+//! pty-proxy 的命令行
+//!
+//! 目标程序之前的参数是本程序的选项，`--` 之后的参数是目标程序和它的参数，原样传给目标程序。
+//! 不写 `--` 时从第一个不以 `-` 开头的参数开始就是目标程序，和以前的版本兼容。
+//!
+//! 可执行文件旁边有同名的配置文件时不解析命令行（见 pty-proxy 的 `main`），
+//! 重命名后当作目标程序使用的 pty-proxy 会把命令行参数全部留给配置文件中的目标程序。
+
+use winptyrs::{ AgentConfig, PTYBackend };
+
+use crate::config::{ self, Config, LogLevel };
+use crate::output::OutputMode;
+use crate::protocol::PROTOCOL_VERSION;
+use crate::transport::TransportKind;
+
+/// 命令行要求做的事
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// 运行目标程序
+    Run(Options),
+    /// 显示用法
+    Help,
+    /// 显示版本
+    Version,
+}
+
+/// 命令行上的选项，没有给出的选项使用配置文件中的值
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Options {
+    /// `--config` 指定的配置文件
+    pub config_file: Option<String>,
+    pub transport: Option<TransportKind>,
+    pub backend: Option<PTYBackend>,
+    pub child_program: Option<String>,
+    pub kill_tree: bool,
+    pub conerr: bool,
+    /// `--cols` 和 `--rows` 指定的终端大小
+    pub size: Option<(u16, u16)>,
+    pub cwd: Option<String>,
+    /// `--env` 指定的环境变量，设置在配置文件中的环境变量之后
+    pub env: Vec<(String, String)>,
+    pub output_mode: Option<OutputMode>,
+    pub log_level: Option<LogLevel>,
+    /// 目标程序和它的参数，为空时使用配置文件中的目标程序
+    pub target: Vec<String>,
+}
+
+impl Options {
+    /// 用命令行上的选项覆盖配置文件 `file` 中的配置，没有配置文件时从默认配置开始
+    pub fn into_config(self, file: Option<Config>) -> Result<Config, String> {
+        let mut config = match (file, self.target.split_first()) {
+            (Some(mut config), Some((program, args))) => {
+                config.target_program = program.clone();
+                config.args = args.to_vec();
+                config
+            }
+            (Some(config), None) => config,
+            (None, Some((program, args))) => Config::new(program.clone(), args.to_vec()),
+            (None, None) => return Err(String::from("缺少目标程序，要在命令行上给出或者写在配置文件中")),
+        };
+
+        if let Some(transport) = self.transport {
+            config.transport = transport;
+        }
+        if let Some(backend) = self.backend {
+            config.backend = backend;
+        }
+        if self.child_program.is_some() {
+            config.child_program = self.child_program;
+        }
+        config.kill_tree |= self.kill_tree;
+        if self.conerr {
+            config.agent_config |= AgentConfig::WINPTY_FLAG_CONERR;
+        }
+        if self.size.is_some() {
+            config.size = self.size;
+        }
+        if self.cwd.is_some() {
+            config.cwd = self.cwd;
+        }
+        config.env.extend(self.env);
+        if let Some(output_mode) = self.output_mode {
+            config.output_mode = output_mode;
+        }
+        if let Some(log_level) = self.log_level {
+            config.log_level = log_level;
+        }
+        Ok(config)
+    }
+}
+
+/// 解析命令行参数（不包括程序名）
+pub fn parse(args: &[String]) -> Result<Command, String> {
+    let mut options = Options::default();
+    let mut cols = None;
+    let mut rows = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let invalid = |error: String| format!("命令行中的 {} 无效：{}", arg, error);
+        let mut value = || args.next().ok_or_else(|| format!("命令行中的 {} 缺少值", arg));
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-V" | "--version" => return Ok(Command::Version),
+            "--config" => options.config_file = Some(value()?.clone()),
+            "--transport" => options.transport = Some(value()?.parse().map_err(invalid)?),
+            "--backend" => options.backend = Some(config::parse_backend(value()?).map_err(invalid)?),
+            "--child" => options.child_program = Some(value()?.clone()),
+            "--kill-tree" => options.kill_tree = true,
+            "--conerr" => options.conerr = true,
+            "--cols" => cols = Some(parse_dimension(value()?).map_err(invalid)?),
+            "--rows" => rows = Some(parse_dimension(value()?).map_err(invalid)?),
+            "--cwd" => options.cwd = Some(value()?.clone()),
+            "--env" => options.env.push(config::parse_env(value()?).map_err(invalid)?),
+            "--output-mode" => options.output_mode = Some(value()?.parse().map_err(invalid)?),
+            "--log-level" => options.log_level = Some(value()?.parse().map_err(invalid)?),
+            "--" => {
+                options.target = args.cloned().collect();
+                break;
+            }
+            option if option.starts_with('-') && option.len() > 1 => {
+                return Err(format!("未知的选项 {}，目标程序的参数要放在 -- 之后", option));
+            }
+            _ => {
+                options.target = std::iter::once(arg).chain(args).cloned().collect();
+                break;
+            }
+        }
+    }
+
+    options.size = match (cols, rows) {
+        (Some(cols), Some(rows)) => Some((cols, rows)),
+        (None, None) => None,
+        _ => return Err(String::from("命令行中的 --cols 和 --rows 要同时设置")),
+    };
+    Ok(Command::Run(options))
+}
+
+fn parse_dimension(value: &str) -> Result<u16, String> {
+    value
+        .parse()
+        .ok()
+        .filter(|&value| value > 0)
+        .ok_or_else(|| format!("应该是 1 到 65535 之间的整数，实际是 {:?}", value))
+}
+
+/// `--version` 显示的版本，两个程序的协议版本相同才能一起使用
+pub fn version() -> String {
+    format!("pty-proxy {}（协议版本 {}）", env!("CARGO_PKG_VERSION"), PROTOCOL_VERSION)
+}
+
+/// `--help` 显示的用法，`exe_name` 是不带扩展名的可执行文件名
+pub fn usage(exe_name: &str) -> String {
+    format!(
+        concat!(
+            "用法: {0} [选项] [--] <target_program> [args...]\n",
+            "      {0} --config <配置文件> [选项] [-- <target_program> [args...]]\n",
+            "\n",
+            "目标程序之前的参数是本程序的选项，目标程序的参数原样传给目标程序。\n",
+            "\n",
+            "选项：\n",
+            "  --config <配置文件>      读取配置文件，命令行上的选项和目标程序优先\n",
+            "  --transport <通信方式>   named_pipe、unix_socket、tcp 或 anonymous_pipe\n",
+            "  --backend <后端>         auto（默认）、conpty、winpty、unix 或 pipes\n",
+            "  --child <子程序>         使用独立的子程序，相对路径基于可执行文件目录\n",
+            "  --kill-tree              本进程退出（包括被强制结束）时结束目标程序及其启动的所有进程\n",
+            "  --conerr                 伪终端后端也把目标程序的标准错误单独输出到 stderr\n",
+            "  --cols <列数>            和 --rows 一起设置固定的终端大小，不设置时跟随所在的终端\n",
+            "  --rows <行数>\n",
+            "  --cwd <目录>             目标程序的工作目录\n",
+            "  --env <名称>=<值>        给目标程序设置环境变量，可以重复使用\n",
+            "  --output-mode <方式>     raw（默认，原样输出）或 plain（去掉转义序列）\n",
+            "  --log-level <级别>       在 stderr 上显示的日志：error（默认）、info 或 debug\n",
+            "  -h, --help               显示用法\n",
+            "  -V, --version            显示版本\n",
+            "\n",
+            "可执行文件旁边有 {0}.toml 时不解析命令行，直接使用其中的配置，示例：\n",
+            "target_program = \"cmd.exe\"\n",
+            "args = [\"/C\", \"echo helloworld\"]\n",
+            "cwd = \"C:\\\\Users\" # 可选，目标程序的工作目录\n",
+            "env = {{ PYTHONIOENCODING = \"utf-8\" }} # 可选，给目标程序设置的环境变量\n",
+            "transport = \"named_pipe\" # 可选\n",
+            "backend = \"auto\" # 可选，auto（默认）、conpty、winpty、unix（Linux）或 pipes（不需要控制台的程序直接用管道转发）\n",
+            "child_program = \"pty-proxy-child.exe\" # 可选，使用独立的子程序，相对路径基于可执行文件目录\n",
+            "kill_tree = true # 可选，本进程退出（包括被强制结束）时结束目标程序及其启动的所有进程\n",
+            "conerr = true # 可选，伪终端后端也把目标程序的标准错误单独输出到 stderr\n",
+            "cols = 120 # 可选，和 rows 一起设置固定的终端大小，不设置时跟随所在的终端\n",
+            "rows = 40\n",
+            "mouse_mode = \"none\" # 可选，winpty 的鼠标模式：none、auto 或 force\n",
+            "timeout = 10000 # 可选，等待 winpty 代理程序的毫秒数\n",
+            "agent_flags = [\"color_escapes\"] # 可选，虚拟终端的标志：plain_output、color_escapes 和 conerr\n",
+            "output_mode = \"raw\" # 可选，raw 或 plain\n",
+            "log_level = \"error\" # 可选，error、info 或 debug"
+        ),
+        exe_name
+    )
+}
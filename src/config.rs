@@ -0,0 +1,369 @@
+//! pty-proxy 的配置
+//!
+//! 配置写在可执行文件旁边的 `<可执行文件名>.toml` 中，没有这个配置文件时由命令行参数给出，
+//! 命令行上也可以用 `--config` 指定配置文件，见 [`crate::cli`]。
+//! [`Config::from_toml`] 逐项检查配置文件，出错时返回说明原因的错误信息，而不是 panic。
+//! 不认识的配置项只记为警告，旧版本留下的或者给新版本准备的配置文件仍然可以使用。
+//!
+//! 虚拟终端的设置（大小、鼠标模式、等待时间和标志）通过命令行传给 pty-proxy-child，
+//! 两边用同样的名称，见 [`parse_mouse_mode`] 和 [`parse_agent_flags`]。
+
+use std::fmt;
+use std::str::FromStr;
+
+use toml::{ Table, Value };
+use winptyrs::{ AgentConfig, MouseMode, PTYBackend };
+
+use crate::output::OutputMode;
+use crate::transport::TransportKind;
+
+/// 没有配置 timeout 时等待 winpty 代理程序的时间，单位为毫秒
+pub const DEFAULT_TIMEOUT: u32 = 10000;
+
+/// 鼠标模式在配置文件和命令行中的名称
+const MOUSE_MODES: [(&str, MouseMode); 3] = [
+    ("none", MouseMode::WINPTY_MOUSE_MODE_NONE),
+    ("auto", MouseMode::WINPTY_MOUSE_MODE_AUTO),
+    ("force", MouseMode::WINPTY_MOUSE_MODE_FORCE),
+];
+
+/// 虚拟终端的标志在配置文件和命令行中的名称
+const AGENT_FLAGS: [(&str, AgentConfig); 3] = [
+    ("plain_output", AgentConfig::WINPTY_FLAG_PLAIN_OUTPUT),
+    ("color_escapes", AgentConfig::WINPTY_FLAG_COLOR_ESCAPES),
+    ("conerr", AgentConfig::WINPTY_FLAG_CONERR),
+];
+
+/// 当前平台可以选择的后端在配置文件和命令行中的名称
+#[cfg(windows)]
+const BACKENDS: [(&str, PTYBackend); 4] = [
+    ("auto", PTYBackend::Auto),
+    ("conpty", PTYBackend::ConPTY),
+    ("winpty", PTYBackend::WinPTY),
+    ("pipes", PTYBackend::Pipes),
+];
+#[cfg(unix)]
+const BACKENDS: [(&str, PTYBackend); 3] = [
+    ("auto", PTYBackend::Auto),
+    ("unix", PTYBackend::Unix),
+    ("pipes", PTYBackend::Pipes),
+];
+
+/// 配置文件中可以使用的配置项
+const KEYS: [&str; 16] = [
+    "target_program",
+    "args",
+    "cwd",
+    "env",
+    "transport",
+    "backend",
+    "child_program",
+    "kill_tree",
+    "conerr",
+    "cols",
+    "rows",
+    "mouse_mode",
+    "timeout",
+    "agent_flags",
+    "output_mode",
+    "log_level",
+];
+
+/// 在 stderr 上显示的日志，级别越高显示得越多
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    /// 只显示错误，默认值
+    #[default]
+    Error,
+    /// 还显示使用的配置、后端和目标程序的退出代码
+    Info,
+    /// 还显示两个程序之间交互的细节
+    Debug,
+}
+
+impl LogLevel {
+    /// 所有日志级别
+    pub const ALL: [LogLevel; 3] = [LogLevel::Error, LogLevel::Info, LogLevel::Debug];
+
+    /// 配置文件和命令行中使用的名称
+    pub fn name(self) -> &'static str {
+        match self {
+            LogLevel::Error => "error",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+        }
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        LogLevel::ALL
+            .into_iter()
+            .find(|level| level.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<&str> = LogLevel::ALL.iter().map(|level| level.name()).collect();
+                format!("未知的日志级别 {:?}，可选值：{}", s, names.join(", "))
+            })
+    }
+}
+
+/// pty-proxy 的全部配置
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// 要在虚拟终端中运行的程序
+    pub target_program: String,
+    /// 传给目标程序的参数，逐个传递，不会被拆开
+    pub args: Vec<String>,
+    /// 目标程序的工作目录，`None` 表示和本进程相同
+    pub cwd: Option<String>,
+    /// 在继承的环境变量之外给目标程序设置的环境变量，按顺序设置
+    pub env: Vec<(String, String)>,
+    /// pty-proxy 和 pty-proxy-child 之间的通信方式
+    pub transport: TransportKind,
+    /// 后端，[`PTYBackend::Auto`] 表示依次尝试各个虚拟终端后端，使用第一个可用的
+    pub backend: PTYBackend,
+    /// 独立的子程序，相对路径基于可执行文件目录，`None` 表示由自身充当子程序
+    pub child_program: Option<String>,
+    /// 本进程退出（包括被强制结束）时是否结束目标程序及其启动的所有进程
+    pub kill_tree: bool,
+    /// 固定的终端大小（列数, 行数），`None` 表示跟随本进程所在的终端
+    pub size: Option<(u16, u16)>,
+    /// winpty 后端的鼠标模式
+    pub mouse_mode: MouseMode,
+    /// 等待 winpty 代理程序的时间，单位为毫秒
+    pub timeout: u32,
+    /// 虚拟终端的标志
+    pub agent_config: AgentConfig,
+    /// 目标程序的输出是否去掉转义序列
+    pub output_mode: OutputMode,
+    /// 在 stderr 上显示哪些日志
+    pub log_level: LogLevel,
+    /// 读取配置文件时发现的、不影响运行的问题，例如不认识的配置项
+    pub warnings: Vec<String>,
+}
+
+impl Config {
+    /// 运行 `target_program` 的默认配置
+    pub fn new(target_program: String, args: Vec<String>) -> Config {
+        Config {
+            target_program,
+            args,
+            cwd: None,
+            env: Vec::new(),
+            transport: TransportKind::default(),
+            backend: PTYBackend::Auto,
+            child_program: None,
+            kill_tree: false,
+            size: None,
+            mouse_mode: MouseMode::WINPTY_MOUSE_MODE_NONE,
+            timeout: DEFAULT_TIMEOUT,
+            agent_config: AgentConfig::WINPTY_FLAG_COLOR_ESCAPES,
+            output_mode: OutputMode::default(),
+            log_level: LogLevel::default(),
+            warnings: Vec::new(),
+        }
+    }
+
+    /// 解析并检查配置文件的内容
+    pub fn from_toml(content: &str) -> Result<Config, String> {
+        let table: Table = content.parse().map_err(|error| format!("无法解析配置文件：{}", error))?;
+
+        let target_program = get_str(&table, "target_program")?.ok_or("缺少配置项 target_program")?;
+        let args = match table.get("args") {
+            Some(value) => get_str_array("args", value)?,
+            None => Vec::new(),
+        };
+        let mut config = Config::new(target_program, args);
+        config.cwd = get_str(&table, "cwd")?;
+        if let Some(value) = table.get("env") {
+            config.env = get_str_table("env", value)?;
+            for (name, _) in &config.env {
+                check_env_name(name).map_err(|error| format!("配置项 env 无效：{}", error))?;
+            }
+        }
+
+        if let Some(transport) = get_str(&table, "transport")? {
+            config.transport = transport.parse().map_err(|error| format!("配置项 transport 无效：{}", error))?;
+        }
+        if let Some(backend) = get_str(&table, "backend")? {
+            config.backend = parse_backend(&backend).map_err(|error| format!("配置项 backend 无效：{}", error))?;
+        }
+        config.child_program = get_str(&table, "child_program")?;
+        config.kill_tree = get_bool(&table, "kill_tree")?.unwrap_or(false);
+
+        config.size = match (get_integer(&table, "cols", 1, u16::MAX as i64)?, get_integer(&table, "rows", 1, u16::MAX as i64)?) {
+            (Some(cols), Some(rows)) => Some((cols as u16, rows as u16)),
+            (None, None) => None,
+            _ => return Err(String::from("配置项 cols 和 rows 要同时设置")),
+        };
+        if let Some(mouse_mode) = get_str(&table, "mouse_mode")? {
+            config.mouse_mode = parse_mouse_mode(&mouse_mode).map_err(|error| format!("配置项 mouse_mode 无效：{}", error))?;
+        }
+        if let Some(timeout) = get_integer(&table, "timeout", 1, u32::MAX as i64)? {
+            config.timeout = timeout as u32;
+        }
+        if let Some(value) = table.get("agent_flags") {
+            let names = get_str_array("agent_flags", value)?;
+            config.agent_config = parse_agent_flags(names.iter().map(String::as_str))
+                .map_err(|error| format!("配置项 agent_flags 无效：{}", error))?;
+        }
+        if get_bool(&table, "conerr")?.unwrap_or(false) {
+            config.agent_config |= AgentConfig::WINPTY_FLAG_CONERR;
+        }
+        if let Some(output_mode) = get_str(&table, "output_mode")? {
+            config.output_mode = output_mode.parse().map_err(|error| format!("配置项 output_mode 无效：{}", error))?;
+        }
+        if let Some(log_level) = get_str(&table, "log_level")? {
+            config.log_level = log_level.parse().map_err(|error| format!("配置项 log_level 无效：{}", error))?;
+        }
+        config.warnings = table
+            .keys()
+            .filter(|key| !KEYS.contains(&key.as_str()))
+            .map(|key| format!("忽略未知的配置项 {}，可用的配置项：{}", key, KEYS.join(", ")))
+            .collect();
+
+        Ok(config)
+    }
+}
+
+/// 按名称解析后端，Windows 上可以选择 auto、conpty、winpty 或 pipes，Unix 上可以选择 auto、unix 或 pipes
+pub fn parse_backend(name: &str) -> Result<PTYBackend, String> {
+    BACKENDS
+        .iter()
+        .find(|(backend_name, _)| *backend_name == name)
+        .map(|(_, backend)| *backend)
+        .ok_or_else(|| format!("当前平台没有后端 {:?}，可选值：{}", name, known_names(&BACKENDS)))
+}
+
+/// 后端的名称，[`parse_backend`] 的反向操作
+///
+/// 当前平台不能选择的后端也有名称，自动选择时要说明它们为什么不可用。
+pub fn backend_name(backend: PTYBackend) -> &'static str {
+    match backend {
+        PTYBackend::Auto => "auto",
+        PTYBackend::ConPTY => "conpty",
+        PTYBackend::WinPTY => "winpty",
+        PTYBackend::Unix => "unix",
+        PTYBackend::Pipes => "pipes",
+        PTYBackend::Mock => "mock",
+        PTYBackend::NoBackend => "none",
+    }
+}
+
+/// 按名称（none、auto 或 force）解析鼠标模式
+pub fn parse_mouse_mode(name: &str) -> Result<MouseMode, String> {
+    MOUSE_MODES
+        .iter()
+        .find(|(mode_name, _)| *mode_name == name)
+        .map(|(_, mode)| *mode)
+        .ok_or_else(|| format!("未知的鼠标模式 {:?}，可选值：{}", name, known_names(&MOUSE_MODES)))
+}
+
+/// 鼠标模式的名称，[`parse_mouse_mode`] 的反向操作
+pub fn mouse_mode_name(mode: MouseMode) -> &'static str {
+    match mode {
+        MouseMode::WINPTY_MOUSE_MODE_NONE => "none",
+        MouseMode::WINPTY_MOUSE_MODE_AUTO => "auto",
+        MouseMode::WINPTY_MOUSE_MODE_FORCE => "force",
+    }
+}
+
+/// 按名称（plain_output、color_escapes 或 conerr）解析虚拟终端的标志
+///
+/// plain_output 和 color_escapes 的作用相反，不能同时使用。
+pub fn parse_agent_flags<'a>(names: impl IntoIterator<Item = &'a str>) -> Result<AgentConfig, String> {
+    let mut flags = AgentConfig::empty();
+    for name in names {
+        flags |= AGENT_FLAGS
+            .iter()
+            .find(|(flag_name, _)| *flag_name == name)
+            .map(|(_, flag)| *flag)
+            .ok_or_else(|| format!("未知的标志 {:?}，可选值：{}", name, known_names(&AGENT_FLAGS)))?;
+    }
+    if flags.contains(AgentConfig::WINPTY_FLAG_PLAIN_OUTPUT | AgentConfig::WINPTY_FLAG_COLOR_ESCAPES) {
+        return Err(String::from("plain_output 和 color_escapes 不能同时使用"));
+    }
+    Ok(flags)
+}
+
+/// 标志的名称，用逗号分隔，[`parse_agent_flags`] 的反向操作
+pub fn agent_flag_names(flags: AgentConfig) -> String {
+    AGENT_FLAGS
+        .iter()
+        .filter(|(_, flag)| flags.contains(*flag))
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// 解析 `<名称>=<值>` 形式的环境变量，值可以为空或含有 `=`
+pub fn parse_env(item: &str) -> Result<(String, String), String> {
+    let (name, value) = item
+        .split_once('=')
+        .ok_or_else(|| format!("环境变量 {:?} 应该写成 <名称>=<值>", item))?;
+    check_env_name(name)?;
+    Ok((name.to_string(), value.to_string()))
+}
+
+fn check_env_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.contains(['=', '\0']) {
+        return Err(format!("无效的环境变量名 {:?}", name));
+    }
+    Ok(())
+}
+
+fn known_names<T>(table: &[(&str, T)]) -> String {
+    table.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(", ")
+}
+
+fn get_str(table: &Table, key: &str) -> Result<Option<String>, String> {
+    match table.get(key) {
+        Some(Value::String(value)) => Ok(Some(value.clone())),
+        Some(value) => Err(format!("配置项 {} 应该是字符串，实际是 {}", key, value)),
+        None => Ok(None),
+    }
+}
+
+fn get_bool(table: &Table, key: &str) -> Result<Option<bool>, String> {
+    match table.get(key) {
+        Some(Value::Boolean(value)) => Ok(Some(*value)),
+        Some(value) => Err(format!("配置项 {} 应该是 true 或 false，实际是 {}", key, value)),
+        None => Ok(None),
+    }
+}
+
+fn get_integer(table: &Table, key: &str, min: i64, max: i64) -> Result<Option<i64>, String> {
+    match table.get(key) {
+        Some(Value::Integer(value)) if (min..=max).contains(value) => Ok(Some(*value)),
+        Some(value) => Err(format!("配置项 {} 应该是 {} 到 {} 之间的整数，实际是 {}", key, min, max, value)),
+        None => Ok(None),
+    }
+}
+
+fn get_str_table(key: &str, value: &Value) -> Result<Vec<(String, String)>, String> {
+    let error = || format!("配置项 {} 应该是值为字符串的表，实际是 {}", key, value);
+    value
+        .as_table()
+        .ok_or_else(error)?
+        .iter()
+        .map(|(name, item)| item.as_str().map(|item| (name.clone(), item.to_string())).ok_or_else(error))
+        .collect()
+}
+
+fn get_str_array(key: &str, value: &Value) -> Result<Vec<String>, String> {
+    let error = || format!("配置项 {} 应该是字符串数组，实际是 {}", key, value);
+    value
+        .as_array()
+        .ok_or_else(error)?
+        .iter()
+        .map(|item| item.as_str().map(String::from).ok_or_else(error))
+        .collect()
+}
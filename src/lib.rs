@@ -0,0 +1,8 @@
+//! pty-proxy 和 pty-proxy-child 共用的代码
+
+pub mod child;
+pub mod cli;
+pub mod config;
+pub mod output;
+pub mod protocol;
+pub mod transport;
@@ -0,0 +1,110 @@
+//! 目标程序的输出写到 stdout 之前的处理
+//!
+//! 虚拟终端的输出含有 VT-100（ANSI）转义序列，[`OutputMode::Plain`] 用 [`EscapeStripper`]
+//! 把它们去掉，只留下文本。输出是分段到达的，一个转义序列可能被拆在两段中，
+//! 所以 [`EscapeStripper`] 会记住上一段结束时的状态。
+
+use std::fmt;
+use std::str::FromStr;
+
+/// 输出方式
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum OutputMode {
+    /// 原样输出，包括转义序列，默认值
+    #[default]
+    Raw,
+    /// 去掉转义序列后输出
+    Plain,
+}
+
+impl OutputMode {
+    /// 所有输出方式
+    pub const ALL: [OutputMode; 2] = [OutputMode::Raw, OutputMode::Plain];
+
+    /// 配置文件和命令行中使用的名称
+    pub fn name(self) -> &'static str {
+        match self {
+            OutputMode::Raw => "raw",
+            OutputMode::Plain => "plain",
+        }
+    }
+}
+
+impl fmt::Display for OutputMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for OutputMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        OutputMode::ALL
+            .into_iter()
+            .find(|mode| mode.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<&str> = OutputMode::ALL.iter().map(|mode| mode.name()).collect();
+                format!("未知的输出方式 {:?}，可选值：{}", s, names.join(", "))
+            })
+    }
+}
+
+/// [`EscapeStripper`] 在两段输出之间记住的位置
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+enum State {
+    /// 普通文本
+    #[default]
+    Text,
+    /// 刚读到 ESC
+    Escape,
+    /// ESC 后面的中间字节，例如选择字符集的 `ESC ( B`
+    Intermediate,
+    /// 控制序列 `ESC [`，到 0x40 到 0x7E 之间的结束字节为止
+    Csi,
+    /// 操作系统命令 `ESC ]` 等字符串，到 BEL 或 `ESC \` 为止
+    String,
+    /// 字符串中读到了 ESC
+    StringEscape,
+}
+
+/// 从分段到达的输出中去掉转义序列
+#[derive(Default)]
+pub struct EscapeStripper {
+    state: State,
+}
+
+impl EscapeStripper {
+    pub fn new() -> EscapeStripper {
+        EscapeStripper::default()
+    }
+
+    /// 去掉这一段输出中的转义序列，没有结束的转义序列留到下一段继续处理
+    pub fn strip(&mut self, input: &[u8]) -> Vec<u8> {
+        const ESC: u8 = 0x1b;
+        const BEL: u8 = 0x07;
+
+        let mut output = Vec::with_capacity(input.len());
+        for &byte in input {
+            self.state = match (self.state, byte) {
+                (State::Text, ESC) => State::Escape,
+                (State::Text, _) => {
+                    output.push(byte);
+                    State::Text
+                }
+                (State::Escape | State::StringEscape, b'[') => State::Csi,
+                (State::Escape | State::StringEscape, b']' | b'P' | b'X' | b'^' | b'_') => State::String,
+                (State::StringEscape, b'\\') => State::Text,
+                (State::String | State::StringEscape, ESC) => State::StringEscape,
+                (_, ESC) => State::Escape,
+                (State::Escape | State::StringEscape | State::Intermediate, 0x20..=0x2f) => State::Intermediate,
+                (State::Escape | State::StringEscape | State::Intermediate, _) => State::Text,
+                (State::Csi, 0x40..=0x7e) => State::Text,
+                (State::Csi, _) => State::Csi,
+                (State::String, BEL) => State::Text,
+                (State::String, _) => State::String,
+            };
+        }
+        output
+    }
+}
@@ -0,0 +1,352 @@
+//! pty-proxy 和 pty-proxy-child 之间的消息协议
+//!
+//! 每条字节流上传输的都是一帧帧的消息：4 字节大端序的长度，后面是这么长的帧内容，
+//! 由 1 字节的消息类型和消息的数据组成。每条流的第一帧都是 [`Message::Hello`]，
+//! 带着发送方的协议版本，版本不同时接收方拒绝继续通信。
+//!
+//! [`Message::encode`] 和 [`Decoder`] 只负责编码和解码，不涉及具体的通信方式；
+//! [`MessageWriter`] 和 [`MessageReader`] 在 [`Transport`] 上收发消息。
+
+use std::fmt;
+use std::io;
+use std::sync::{ Arc, Mutex };
+
+use winptyrs::ExitStatus;
+
+use crate::transport::Transport;
+
+/// 当前的协议版本，消息格式不兼容时加一
+pub const PROTOCOL_VERSION: u16 = 2;
+
+/// 一帧内容的最大长度，超过时视为数据损坏
+pub const MAX_FRAME_LEN: usize = 16 << 20;
+
+/// 长度字段的字节数
+const LEN_SIZE: usize = 4;
+
+const TYPE_HELLO: u8 = 1;
+const TYPE_DATA: u8 = 2;
+const TYPE_RESIZE: u8 = 3;
+const TYPE_SIGNAL: u8 = 4;
+const TYPE_EXIT: u8 = 5;
+const TYPE_ERROR: u8 = 6;
+const TYPE_PING: u8 = 7;
+const TYPE_PONG: u8 = 8;
+const TYPE_LOG: u8 = 9;
+
+/// 两个进程之间传递的消息
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Message {
+    /// 每条流的第一条消息，附带发送方的协议版本
+    Hello { version: u16 },
+    /// 原样转发的字节：输入流上是目标程序的输入，输出流上是它的输出
+    Data(Vec<u8>),
+    /// 调整终端的大小
+    Resize { cols: u16, rows: u16 },
+    /// 向目标程序发送信号
+    Signal(Signal),
+    /// 目标程序的退出状态，是 pty-proxy-child 发送的最后一条消息
+    Exit(ExitStatus),
+    /// 对端发生的错误，只用于显示
+    Error(String),
+    /// 检查对端是否还在响应，对端回复附带相同数值的 [`Message::Pong`]
+    Ping(u32),
+    /// 对 [`Message::Ping`] 的回复
+    Pong(u32),
+    /// 对端的日志，发送方已经按日志级别筛选过，只用于显示
+    Log(String),
+}
+
+/// [`Message::Signal`] 可以发送的信号
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Signal {
+    /// 相当于按下 Ctrl+C
+    Interrupt,
+    /// 请求目标程序退出，一段时间后仍未退出则强制结束
+    Terminate,
+    /// 立即强制结束目标程序
+    Kill,
+}
+
+impl Signal {
+    fn to_byte(self) -> u8 {
+        match self {
+            Signal::Interrupt => 1,
+            Signal::Terminate => 2,
+            Signal::Kill => 3,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Option<Signal> {
+        match byte {
+            1 => Some(Signal::Interrupt),
+            2 => Some(Signal::Terminate),
+            3 => Some(Signal::Kill),
+            _ => None,
+        }
+    }
+}
+
+/// 解码消息时发现的错误
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProtocolError {
+    /// 帧的长度超过了 [`MAX_FRAME_LEN`]
+    FrameTooLarge(usize),
+    /// 长度为 0 的帧，连消息类型都没有
+    EmptyFrame,
+    /// 未知的消息类型
+    UnknownType(u8),
+    /// 消息的数据和它的类型不符
+    Malformed(u8),
+    /// 流的第一条消息不是 [`Message::Hello`]
+    MissingHello,
+    /// 对端的协议版本和本方不同
+    VersionMismatch(u16),
+    /// 流在一帧的中间结束了
+    Truncated,
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtocolError::FrameTooLarge(len) => write!(f, "消息长度 {} 超过了上限 {}", len, MAX_FRAME_LEN),
+            ProtocolError::EmptyFrame => f.write_str("收到了空的消息"),
+            ProtocolError::UnknownType(kind) => write!(f, "未知的消息类型 {}", kind),
+            ProtocolError::Malformed(kind) => write!(f, "类型为 {} 的消息内容无效", kind),
+            ProtocolError::MissingHello => f.write_str("对端没有先发送握手消息，可能是不兼容的旧版本"),
+            ProtocolError::VersionMismatch(version) => {
+                write!(f, "对端的协议版本 {} 和本方的 {} 不同", version, PROTOCOL_VERSION)
+            }
+            ProtocolError::Truncated => f.write_str("通信在一条消息的中间中断了"),
+        }
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+impl From<ProtocolError> for io::Error {
+    fn from(error: ProtocolError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, error)
+    }
+}
+
+impl Message {
+    /// 编码为一帧，包括开头的长度
+    ///
+    /// # Panics
+    /// 帧的内容超过 [`MAX_FRAME_LEN`] 时 panic，较长的数据用 [`MessageWriter::send_data`] 分段发送。
+    pub fn encode(&self) -> Vec<u8> {
+        let mut frame = vec![0; LEN_SIZE];
+        match self {
+            Message::Hello { version } => {
+                frame.push(TYPE_HELLO);
+                frame.extend_from_slice(&version.to_be_bytes());
+            }
+            Message::Data(bytes) => {
+                frame.push(TYPE_DATA);
+                frame.extend_from_slice(bytes);
+            }
+            Message::Resize { cols, rows } => {
+                frame.push(TYPE_RESIZE);
+                frame.extend_from_slice(&cols.to_be_bytes());
+                frame.extend_from_slice(&rows.to_be_bytes());
+            }
+            Message::Signal(signal) => {
+                frame.push(TYPE_SIGNAL);
+                frame.push(signal.to_byte());
+            }
+            Message::Exit(status) => {
+                let (kind, value) = match *status {
+                    ExitStatus::Exited(code) => (0, code),
+                    ExitStatus::Signaled(signal) => (1, signal as u32),
+                    ExitStatus::NtStatus(code) => (2, code),
+                    ExitStatus::Killed => (3, 0),
+                };
+                frame.push(TYPE_EXIT);
+                frame.push(kind);
+                frame.extend_from_slice(&value.to_be_bytes());
+            }
+            Message::Error(text) => {
+                frame.push(TYPE_ERROR);
+                frame.extend_from_slice(text.as_bytes());
+            }
+            Message::Ping(value) => {
+                frame.push(TYPE_PING);
+                frame.extend_from_slice(&value.to_be_bytes());
+            }
+            Message::Pong(value) => {
+                frame.push(TYPE_PONG);
+                frame.extend_from_slice(&value.to_be_bytes());
+            }
+            Message::Log(text) => {
+                frame.push(TYPE_LOG);
+                frame.extend_from_slice(text.as_bytes());
+            }
+        }
+        let len = frame.len() - LEN_SIZE;
+        assert!(len <= MAX_FRAME_LEN, "消息长度 {} 超过了上限 {}", len, MAX_FRAME_LEN);
+        frame[..LEN_SIZE].copy_from_slice(&(len as u32).to_be_bytes());
+        frame
+    }
+
+    /// 解码一帧的内容（不包括开头的长度）
+    pub fn decode(frame: &[u8]) -> Result<Message, ProtocolError> {
+        let (&kind, data) = frame.split_first().ok_or(ProtocolError::EmptyFrame)?;
+        let malformed = ProtocolError::Malformed(kind);
+        let message = match kind {
+            TYPE_HELLO => Message::Hello { version: u16::from_be_bytes(data.try_into().map_err(|_| malformed)?) },
+            TYPE_DATA => Message::Data(data.to_vec()),
+            TYPE_RESIZE => match data {
+                [c0, c1, r0, r1] => Message::Resize {
+                    cols: u16::from_be_bytes([*c0, *c1]),
+                    rows: u16::from_be_bytes([*r0, *r1]),
+                },
+                _ => return Err(malformed),
+            },
+            TYPE_SIGNAL => match data {
+                [signal] => Message::Signal(Signal::from_byte(*signal).ok_or(malformed)?),
+                _ => return Err(malformed),
+            },
+            TYPE_EXIT => match data {
+                [kind, value @ ..] if value.len() == 4 => {
+                    let value = u32::from_be_bytes(value.try_into().unwrap());
+                    Message::Exit(match kind {
+                        0 => ExitStatus::Exited(value),
+                        1 => ExitStatus::Signaled(value as i32),
+                        2 => ExitStatus::NtStatus(value),
+                        3 => ExitStatus::Killed,
+                        _ => return Err(malformed),
+                    })
+                }
+                _ => return Err(malformed),
+            },
+            TYPE_ERROR => Message::Error(String::from_utf8(data.to_vec()).map_err(|_| malformed)?),
+            TYPE_PING => Message::Ping(u32::from_be_bytes(data.try_into().map_err(|_| malformed)?)),
+            TYPE_PONG => Message::Pong(u32::from_be_bytes(data.try_into().map_err(|_| malformed)?)),
+            TYPE_LOG => Message::Log(String::from_utf8(data.to_vec()).map_err(|_| malformed)?),
+            _ => return Err(ProtocolError::UnknownType(kind)),
+        };
+        Ok(message)
+    }
+}
+
+/// 从任意分段的字节流中还原出消息
+#[derive(Default)]
+pub struct Decoder {
+    /// 收到但还没有解码的字节
+    buffer: Vec<u8>,
+    /// 已经解码到 `buffer` 中的位置
+    start: usize,
+}
+
+impl Decoder {
+    pub fn new() -> Decoder {
+        Decoder::default()
+    }
+
+    /// 加入收到的字节
+    pub fn push(&mut self, bytes: &[u8]) {
+        // 丢掉已经解码的部分，避免缓冲区无限增长
+        if self.start > 0 {
+            self.buffer.drain(..self.start);
+            self.start = 0;
+        }
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// 取出下一条完整的消息，还没有收全时返回 `Ok(None)`
+    ///
+    /// 返回错误后字节流已无法同步，不应继续使用。
+    pub fn next_message(&mut self) -> Result<Option<Message>, ProtocolError> {
+        let pending = &self.buffer[self.start..];
+        let Some(len) = pending.get(..LEN_SIZE) else {
+            return Ok(None);
+        };
+        let len = u32::from_be_bytes(len.try_into().unwrap()) as usize;
+        if len > MAX_FRAME_LEN {
+            return Err(ProtocolError::FrameTooLarge(len));
+        }
+        let Some(frame) = pending.get(LEN_SIZE..LEN_SIZE + len) else {
+            return Ok(None);
+        };
+        let message = Message::decode(frame)?;
+        self.start += LEN_SIZE + len;
+        Ok(Some(message))
+    }
+
+    /// 是否没有剩下未解码的字节，流结束时还有剩余说明最后一帧不完整
+    pub fn is_empty(&self) -> bool {
+        self.start == self.buffer.len()
+    }
+}
+
+/// 在 [`Transport`] 上发送消息，可以由多个线程共用
+pub struct MessageWriter {
+    transport: Mutex<Arc<dyn Transport>>,
+}
+
+impl MessageWriter {
+    /// 发送握手消息，之后就可以发送其他消息
+    pub fn new(transport: Arc<dyn Transport>) -> io::Result<MessageWriter> {
+        let writer = MessageWriter { transport: Mutex::new(transport) };
+        writer.send(&Message::Hello { version: PROTOCOL_VERSION })?;
+        Ok(writer)
+    }
+
+    /// 发送一条消息，多个线程同时发送时消息不会交错
+    pub fn send(&self, message: &Message) -> io::Result<()> {
+        let frame = message.encode();
+        self.transport.lock().unwrap().write_all(&frame)
+    }
+
+    /// 把字节作为 [`Message::Data`] 发送，太长时分成多条
+    pub fn send_data(&self, bytes: &[u8]) -> io::Result<()> {
+        for chunk in bytes.chunks(MAX_FRAME_LEN - 1) {
+            self.send(&Message::Data(chunk.to_vec()))?;
+        }
+        Ok(())
+    }
+}
+
+/// 从 [`Transport`] 接收消息
+pub struct MessageReader {
+    transport: Arc<dyn Transport>,
+    decoder: Decoder,
+    /// 是否已经收到握手消息
+    greeted: bool,
+}
+
+impl MessageReader {
+    pub fn new(transport: Arc<dyn Transport>) -> MessageReader {
+        MessageReader { transport, decoder: Decoder::new(), greeted: false }
+    }
+
+    /// 读取下一条消息，对端正常关闭时返回 `Ok(None)`
+    ///
+    /// 第一条消息必须是版本相同的 [`Message::Hello`]，它不会返回给调用者。
+    pub fn read_message(&mut self) -> io::Result<Option<Message>> {
+        let mut buffer = [0u8; 4096];
+        loop {
+            match self.decoder.next_message()? {
+                Some(Message::Hello { version }) if !self.greeted => {
+                    if version != PROTOCOL_VERSION {
+                        return Err(ProtocolError::VersionMismatch(version).into());
+                    }
+                    self.greeted = true;
+                }
+                Some(_) if !self.greeted => return Err(ProtocolError::MissingHello.into()),
+                Some(message) => return Ok(Some(message)),
+                None => {
+                    let n = self.transport.read(&mut buffer)?;
+                    if n == 0 {
+                        return match self.decoder.is_empty() {
+                            true => Ok(None),
+                            false => Err(ProtocolError::Truncated.into()),
+                        };
+                    }
+                    self.decoder.push(&buffer[..n]);
+                }
+            }
+        }
+    }
+}
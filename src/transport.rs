@@ -0,0 +1,200 @@
+//! pty-proxy 和 pty-proxy-child 之间的通信方式
+//!
+//! 两个进程之间有三条单向的字节流：pty-proxy 写、pty-proxy-child 读的输入流
+//! （[`Direction::Outbound`]），以及 pty-proxy-child 写、pty-proxy 读的输出流和标准错误流
+//! （[`Direction::Inbound`]）。
+//!
+//! pty-proxy 用 [`listen`] 为每条流创建一个 [`TransportListener`]，
+//! 把它的端点描述（[`TransportListener::endpoint`]）通过命令行传给 pty-proxy-child，
+//! 后者用 [`connect`] 连接过来，双方各自得到一个 [`Transport`]。
+//!
+//! 可用的通信方式见 [`TransportKind`]，有些主机的策略禁止使用命名管道，可以换成其他方式。
+
+use std::fmt;
+use std::io::{ self, Read, Write };
+use std::str::FromStr;
+
+mod tcp;
+mod anonymous_pipe;
+#[cfg(windows)]
+mod named_pipe;
+#[cfg(unix)]
+mod unix_socket;
+
+/// 通信方式
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TransportKind {
+    /// Windows 命名管道 `\\.\pipe\ptyproxy<uuid>`，仅 Windows 可用
+    NamedPipe,
+    /// 临时目录下的 Unix 域套接字 `ptyproxy<uuid>.sock`，仅 Unix 可用
+    UnixSocket,
+    /// 监听 127.0.0.1 上的随机端口，连接后需要先发送一次性的令牌
+    Tcp,
+    /// 由 pty-proxy-child 继承的匿名管道
+    AnonymousPipe,
+}
+
+impl TransportKind {
+    /// 所有通信方式
+    pub const ALL: [TransportKind; 4] = [
+        TransportKind::NamedPipe,
+        TransportKind::UnixSocket,
+        TransportKind::Tcp,
+        TransportKind::AnonymousPipe,
+    ];
+
+    /// 配置文件和命令行中使用的名称
+    pub fn name(&self) -> &'static str {
+        match self {
+            TransportKind::NamedPipe => "named_pipe",
+            TransportKind::UnixSocket => "unix_socket",
+            TransportKind::Tcp => "tcp",
+            TransportKind::AnonymousPipe => "anonymous_pipe",
+        }
+    }
+
+    /// 当前平台是否支持这种通信方式
+    pub fn is_supported(&self) -> bool {
+        match self {
+            TransportKind::NamedPipe => cfg!(windows),
+            TransportKind::UnixSocket => cfg!(unix),
+            TransportKind::Tcp | TransportKind::AnonymousPipe => true,
+        }
+    }
+}
+
+impl Default for TransportKind {
+    /// Windows 上默认使用命名管道，Unix 上默认使用 Unix 域套接字
+    fn default() -> Self {
+        if cfg!(windows) {
+            TransportKind::NamedPipe
+        } else {
+            TransportKind::UnixSocket
+        }
+    }
+}
+
+impl fmt::Display for TransportKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for TransportKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        TransportKind::ALL
+            .into_iter()
+            .find(|kind| kind.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<&str> = TransportKind::ALL.iter().map(|kind| kind.name()).collect();
+                format!("未知的通信方式 {:?}，可选值：{}", s, names.join(", "))
+            })
+    }
+}
+
+/// 字节流的方向，以 pty-proxy 的视角命名
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Direction {
+    /// pty-proxy-child 写，pty-proxy 读（终端输出）
+    Inbound,
+    /// pty-proxy 写，pty-proxy-child 读（终端输入）
+    Outbound,
+}
+
+/// 已连接的单向字节流
+pub trait Transport: Send + Sync {
+    /// 读取数据，对端关闭时返回 0
+    fn read(&self, buffer: &mut [u8]) -> io::Result<usize>;
+
+    /// 写入全部数据
+    fn write_all(&self, data: &[u8]) -> io::Result<()>;
+}
+
+/// 等待 pty-proxy-child 连接的服务端
+pub trait TransportListener: Send {
+    /// 传给 pty-proxy-child 的端点描述，格式为 `<通信方式>:<地址>`
+    fn endpoint(&self) -> String;
+
+    /// 等待对端连接
+    ///
+    /// 对于匿名管道，必须在启动 pty-proxy-child 之后再调用，
+    /// 因为这会关闭留给子进程继承的那一端。
+    fn accept(self: Box<Self>) -> io::Result<Box<dyn Transport>>;
+}
+
+/// 生成一个新的 uuid（去掉了连字符），用于命名管道和套接字
+pub fn new_pipe_uuid() -> String {
+    format!("{}", uuid::Uuid::new_v4()).replace("-", "")
+}
+
+/// 创建一个等待连接的服务端
+pub fn listen(kind: TransportKind, direction: Direction) -> io::Result<Box<dyn TransportListener>> {
+    match kind {
+        #[cfg(windows)]
+        TransportKind::NamedPipe => named_pipe::listen(direction),
+        #[cfg(unix)]
+        TransportKind::UnixSocket => unix_socket::listen(direction),
+        TransportKind::Tcp => tcp::listen(direction),
+        TransportKind::AnonymousPipe => anonymous_pipe::listen(direction),
+        #[allow(unreachable_patterns)]
+        _ => Err(unsupported(kind)),
+    }
+}
+
+/// 连接到 [`TransportListener::endpoint`] 给出的端点
+///
+/// 没有 `<通信方式>:` 前缀的端点按旧版本的规则视为默认通信方式下的 uuid。
+pub fn connect(endpoint: &str, direction: Direction) -> io::Result<Box<dyn Transport>> {
+    let (kind, address) = match endpoint.split_once(':') {
+        Some((kind, address)) => match kind.parse() {
+            Ok(kind) => (kind, address),
+            Err(_) => (TransportKind::default(), endpoint),
+        },
+        None => (TransportKind::default(), endpoint),
+    };
+
+    match kind {
+        #[cfg(windows)]
+        TransportKind::NamedPipe => named_pipe::connect(address, direction),
+        #[cfg(unix)]
+        TransportKind::UnixSocket => unix_socket::connect(address, direction),
+        TransportKind::Tcp => tcp::connect(address, direction),
+        TransportKind::AnonymousPipe => anonymous_pipe::connect(address, direction),
+        #[allow(unreachable_patterns)]
+        _ => Err(unsupported(kind)),
+    }
+}
+
+fn endpoint(kind: TransportKind, address: &str) -> String {
+    format!("{}:{}", kind.name(), address)
+}
+
+#[allow(dead_code)]
+fn unsupported(kind: TransportKind) -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, format!("当前平台不支持通信方式 {}", kind))
+}
+
+/// 基于标准库流类型的 [`Transport`]
+struct Stream<T>(T);
+
+impl<T> Transport for Stream<T>
+where
+    T: Send + Sync,
+    for<'a> &'a T: Read + Write,
+{
+    fn read(&self, buffer: &mut [u8]) -> io::Result<usize> {
+        match (&self.0).read(buffer) {
+            Err(error) if matches!(
+                error.kind(),
+                io::ErrorKind::BrokenPipe | io::ErrorKind::ConnectionReset | io::ErrorKind::ConnectionAborted
+            ) => Ok(0),
+            result => result,
+        }
+    }
+
+    fn write_all(&self, data: &[u8]) -> io::Result<()> {
+        (&self.0).write_all(data)
+    }
+}
@@ -0,0 +1,110 @@
+//! 由 pty-proxy-child 继承的匿名管道
+//!
+//! pty-proxy 创建管道后把其中一端标记为可继承，端点中记录的是这一端的
+//! 文件描述符（Windows 上是句柄值），子进程继承后数值不变，直接使用即可。
+
+use std::fs::File;
+use std::io;
+
+use super::{ Direction, Stream, Transport, TransportKind, TransportListener };
+
+#[cfg(unix)]
+use std::os::fd::{ AsRawFd, FromRawFd, OwnedFd, RawFd };
+#[cfg(windows)]
+use std::os::windows::io::{ AsRawHandle, FromRawHandle, OwnedHandle, RawHandle };
+
+#[cfg(unix)]
+type OwnedPipe = OwnedFd;
+#[cfg(windows)]
+type OwnedPipe = OwnedHandle;
+
+/// 等待子进程继承的匿名管道
+struct AnonymousPipeListener {
+    /// pty-proxy 自己使用的一端
+    local: OwnedPipe,
+    /// 留给 pty-proxy-child 继承的一端
+    remote: OwnedPipe,
+}
+
+/// 设置管道的一端能否被子进程继承
+#[cfg(unix)]
+fn set_inheritable(pipe: &OwnedPipe, inheritable: bool) -> io::Result<()> {
+    let fd = pipe.as_raw_fd();
+    unsafe {
+        let flags = libc::fcntl(fd, libc::F_GETFD);
+        let flags = if inheritable { flags & !libc::FD_CLOEXEC } else { flags | libc::FD_CLOEXEC };
+        if flags < 0 || libc::fcntl(fd, libc::F_SETFD, flags) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+#[cfg(windows)]
+fn set_inheritable(pipe: &OwnedPipe, inheritable: bool) -> io::Result<()> {
+    use windows_sys::Win32::Foundation::{ SetHandleInformation, HANDLE_FLAG_INHERIT };
+
+    let flags = if inheritable { HANDLE_FLAG_INHERIT } else { 0 };
+    let result = unsafe {
+        SetHandleInformation(pipe.as_raw_handle() as _, HANDLE_FLAG_INHERIT, flags)
+    };
+    if result == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn raw_value(pipe: &OwnedPipe) -> String {
+    pipe.as_raw_fd().to_string()
+}
+#[cfg(windows)]
+fn raw_value(pipe: &OwnedPipe) -> String {
+    (pipe.as_raw_handle() as usize).to_string()
+}
+
+pub fn listen(direction: Direction) -> io::Result<Box<dyn TransportListener>> {
+    let (reader, writer) = io::pipe()?;
+    let (local, remote): (OwnedPipe, OwnedPipe) = match direction {
+        Direction::Inbound => (reader.into(), writer.into()),
+        Direction::Outbound => (writer.into(), reader.into()),
+    };
+    set_inheritable(&remote, true)?;
+    Ok(Box::new(AnonymousPipeListener { local, remote }))
+}
+
+impl TransportListener for AnonymousPipeListener {
+    fn endpoint(&self) -> String {
+        super::endpoint(TransportKind::AnonymousPipe, &raw_value(&self.remote))
+    }
+
+    fn accept(self: Box<Self>) -> io::Result<Box<dyn Transport>> {
+        // 子进程已经继承了另一端，这里必须关掉它，否则永远读不到 EOF
+        drop(self.remote);
+        Ok(Box::new(Stream(File::from(self.local))))
+    }
+}
+
+pub fn connect(address: &str, _direction: Direction) -> io::Result<Box<dyn Transport>> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidInput, format!("无效的匿名管道端点：{}", address));
+
+    #[cfg(unix)]
+    let pipe = {
+        let fd: RawFd = address.parse().map_err(|_| invalid())?;
+        if fd < 0 || unsafe { libc::fcntl(fd, libc::F_GETFD) } < 0 {
+            return Err(invalid());
+        }
+        unsafe { OwnedPipe::from_raw_fd(fd) }
+    };
+    #[cfg(windows)]
+    let pipe = {
+        let handle: usize = address.parse().map_err(|_| invalid())?;
+        if handle == 0 {
+            return Err(invalid());
+        }
+        unsafe { OwnedPipe::from_raw_handle(handle as RawHandle) }
+    };
+    // 继承来的一端仍然是可继承的，不改掉的话目标程序也会继承它
+    set_inheritable(&pipe, false)?;
+
+    Ok(Box::new(Stream(File::from(pipe))))
+}
@@ -0,0 +1,159 @@
+//! Windows 命名管道
+
+use std::ffi::OsStr;
+use std::io;
+use std::os::windows::ffi::OsStrExt;
+use std::os::windows::io::{ AsRawHandle, OwnedHandle, FromRawHandle };
+use std::ptr::null_mut;
+
+use windows_sys::{
+    Win32::Foundation::*,
+    Win32::Storage::FileSystem::*,
+    Win32::System::Pipes::*,
+};
+
+use super::{ Direction, Transport, TransportKind, TransportListener, new_pipe_uuid };
+
+fn to_wstr(s: &str) -> Vec<u16> {
+    // 将 Rust 字符串转换为 OsStr
+    let os_str = OsStr::new(s);
+    // 将 OsStr 转换为 UTF-16 编码的宽字符数组
+    let wide_chars: Vec<u16> = os_str.encode_wide().chain(Some(0)).collect();
+    // 返回宽字符数组
+    wide_chars
+}
+
+/// 根据 uuid 构造命名管道名称
+pub fn pipe_name(pipe_uuid: &str) -> String {
+    format!(r"\\.\pipe\ptyproxy{}", pipe_uuid)
+}
+
+/// 等待连接的命名管道服务端
+struct NamedPipeListener {
+    pipe_uuid: String,
+    handle: OwnedHandle,
+}
+
+/// 已连接的命名管道
+struct NamedPipe {
+    handle: OwnedHandle,
+}
+
+// 创建命名管道
+pub fn listen(direction: Direction) -> io::Result<Box<dyn TransportListener>> {
+    let pipe_uuid = new_pipe_uuid();
+    let (open_mode, pipe_mode) = match direction {
+        Direction::Inbound => (PIPE_ACCESS_INBOUND, PIPE_READMODE_BYTE | PIPE_WAIT),
+        Direction::Outbound => (PIPE_ACCESS_OUTBOUND, PIPE_TYPE_BYTE | PIPE_WAIT),
+    };
+    let pipe_name = to_wstr(&pipe_name(&pipe_uuid));
+    let pipe_handle: HANDLE = unsafe {
+        CreateNamedPipeW(
+            pipe_name.as_ptr(),
+            open_mode,
+            pipe_mode,
+            1,
+            4096,
+            4096,
+            0,
+            null_mut()
+        )
+    };
+    if pipe_handle == INVALID_HANDLE_VALUE {
+        return Err(io::Error::last_os_error());
+    }
+    let handle = unsafe { OwnedHandle::from_raw_handle(pipe_handle) };
+    Ok(Box::new(NamedPipeListener { pipe_uuid, handle }))
+}
+
+impl TransportListener for NamedPipeListener {
+    fn endpoint(&self) -> String {
+        super::endpoint(TransportKind::NamedPipe, &self.pipe_uuid)
+    }
+
+    // 连接命名管道（等待对端连接）
+    fn accept(self: Box<Self>) -> io::Result<Box<dyn Transport>> {
+        let result = unsafe { ConnectNamedPipe(self.handle.as_raw_handle() as HANDLE, null_mut()) };
+        if result == 0 {
+            let error = io::Error::last_os_error();
+            // 对端在调用 ConnectNamedPipe 之前就已经连上了
+            if error.raw_os_error() != Some(ERROR_PIPE_CONNECTED as i32) {
+                return Err(error);
+            }
+        }
+        Ok(Box::new(NamedPipe { handle: self.handle }))
+    }
+}
+
+// 连接到命名管道
+pub fn connect(pipe_uuid: &str, direction: Direction) -> io::Result<Box<dyn Transport>> {
+    let pipe_name = format!("{}\0", pipe_name(pipe_uuid));
+    // Inbound 由 pty-proxy-child 写入，Outbound 由它读取
+    let (access, flags) = match direction {
+        Direction::Inbound => (GENERIC_WRITE, FILE_FLAG_OVERLAPPED),
+        Direction::Outbound => (GENERIC_READ, 0),
+    };
+    let pipe_handle = unsafe {
+        CreateFileA(
+            pipe_name.as_ptr(),
+            access,
+            0,
+            null_mut(),
+            OPEN_EXISTING,
+            flags,
+            null_mut()
+        )
+    };
+    if pipe_handle == INVALID_HANDLE_VALUE {
+        return Err(io::Error::last_os_error());
+    }
+    let handle = unsafe { OwnedHandle::from_raw_handle(pipe_handle) };
+    Ok(Box::new(NamedPipe { handle }))
+}
+
+impl Transport for NamedPipe {
+    // 从命名管道读取，对端关闭时返回 0
+    fn read(&self, buffer: &mut [u8]) -> io::Result<usize> {
+        let mut bytes_read: u32 = 0;
+        let result = unsafe {
+            ReadFile(
+                self.handle.as_raw_handle() as HANDLE,
+                buffer.as_mut_ptr() as *mut _,
+                buffer.len() as u32,
+                &mut bytes_read,
+                null_mut()
+            )
+        };
+
+        if result == 0 {
+            let error = io::Error::last_os_error();
+            if error.raw_os_error() == Some(ERROR_BROKEN_PIPE as i32) {
+                // 管道已断开连接，返回 0
+                return Ok(0);
+            } else {
+                // 其他错误，返回错误
+                return Err(error);
+            }
+        }
+
+        Ok(bytes_read as usize)
+    }
+
+    // 写入命名管道
+    fn write_all(&self, data: &[u8]) -> io::Result<()> {
+        let mut bytes_written: u32 = 0;
+        let result = unsafe {
+            WriteFile(
+                self.handle.as_raw_handle() as HANDLE,
+                data.as_ptr() as *const _,
+                data.len() as u32,
+                &mut bytes_written,
+                null_mut()
+            )
+        };
+        if result == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,77 @@
+//! 本机 TCP 连接
+//!
+//! 本机上的任何进程都能连接监听的端口，所以 pty-proxy-child 连上之后
+//! 必须先发送端点中附带的一次性令牌，令牌不对的连接会被直接关闭。
+
+use std::io::{ self, Read, Write };
+use std::net::{ Ipv4Addr, Shutdown, TcpListener, TcpStream };
+use std::time::Duration;
+
+use super::{ Direction, Stream, Transport, TransportKind, TransportListener, new_pipe_uuid };
+
+/// 等待对端发送令牌的最长时间
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 等待连接的 TCP 服务端
+struct TcpTransportListener {
+    listener: TcpListener,
+    token: String,
+    direction: Direction,
+}
+
+pub fn listen(direction: Direction) -> io::Result<Box<dyn TransportListener>> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+    Ok(Box::new(TcpTransportListener { listener, token: new_pipe_uuid(), direction }))
+}
+
+impl TcpTransportListener {
+    /// 校验对端发来的令牌
+    fn handshake(&self, mut stream: &TcpStream) -> io::Result<bool> {
+        stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+        let mut token = vec![0u8; self.token.len()];
+        stream.read_exact(&mut token)?;
+        stream.set_read_timeout(None)?;
+        Ok(token == self.token.as_bytes())
+    }
+}
+
+impl TransportListener for TcpTransportListener {
+    /// 端点格式为 `tcp:<地址>/<令牌>`
+    fn endpoint(&self) -> String {
+        let address = match self.listener.local_addr() {
+            Ok(address) => address.to_string(),
+            Err(_) => String::new(),
+        };
+        super::endpoint(TransportKind::Tcp, &format!("{}/{}", address, self.token))
+    }
+
+    fn accept(self: Box<Self>) -> io::Result<Box<dyn Transport>> {
+        loop {
+            let (stream, _) = self.listener.accept()?;
+            if !matches!(self.handshake(&stream), Ok(true)) {
+                continue;
+            }
+            stream.set_nodelay(true)?;
+            match self.direction {
+                Direction::Inbound => stream.shutdown(Shutdown::Write)?,
+                Direction::Outbound => stream.shutdown(Shutdown::Read)?,
+            }
+            return Ok(Box::new(Stream(stream)));
+        }
+    }
+}
+
+pub fn connect(address: &str, direction: Direction) -> io::Result<Box<dyn Transport>> {
+    let (address, token) = address.rsplit_once('/').ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidInput, format!("无效的 TCP 端点：{}", address))
+    })?;
+    let mut stream = TcpStream::connect(address)?;
+    stream.set_nodelay(true)?;
+    stream.write_all(token.as_bytes())?;
+    // Inbound 由 pty-proxy-child 写入，Outbound 由它读取
+    match direction {
+        Direction::Inbound => stream.shutdown(Shutdown::Read)?,
+        Direction::Outbound => stream.shutdown(Shutdown::Write)?,
+    }
+    Ok(Box::new(Stream(stream)))
+}
@@ -0,0 +1,54 @@
+//! Unix 域套接字
+
+use std::fs;
+use std::io;
+use std::net::Shutdown;
+use std::os::unix::net::{ UnixListener, UnixStream };
+use std::path::PathBuf;
+
+use super::{ Direction, Stream, Transport, TransportKind, TransportListener, new_pipe_uuid };
+
+/// 根据 uuid 构造 Unix 域套接字的路径
+pub fn socket_path(pipe_uuid: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("ptyproxy{}.sock", pipe_uuid))
+}
+
+/// 等待连接的套接字服务端
+struct UnixSocketListener {
+    listener: UnixListener,
+    path: PathBuf,
+}
+
+impl Drop for UnixSocketListener {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+pub fn listen(_direction: Direction) -> io::Result<Box<dyn TransportListener>> {
+    let path = socket_path(&new_pipe_uuid());
+    let listener = UnixListener::bind(&path)?;
+    Ok(Box::new(UnixSocketListener { listener, path }))
+}
+
+impl TransportListener for UnixSocketListener {
+    fn endpoint(&self) -> String {
+        super::endpoint(TransportKind::UnixSocket, &self.path.to_string_lossy())
+    }
+
+    fn accept(self: Box<Self>) -> io::Result<Box<dyn Transport>> {
+        let (stream, _) = self.listener.accept()?;
+        // 连接建立后套接字文件就没用了，listener 析构时会删除它
+        Ok(Box::new(Stream(stream)))
+    }
+}
+
+pub fn connect(path: &str, direction: Direction) -> io::Result<Box<dyn Transport>> {
+    let stream = UnixStream::connect(path)?;
+    // Inbound 由 pty-proxy-child 写入，Outbound 由它读取
+    match direction {
+        Direction::Inbound => stream.shutdown(Shutdown::Read)?,
+        Direction::Outbound => stream.shutdown(Shutdown::Write)?,
+    }
+    Ok(Box::new(Stream(stream)))
+}
@@ -0,0 +1,101 @@
+use pty_proxy::cli::{ self, Command, Options };
+use pty_proxy::config::{ Config, LogLevel };
+use pty_proxy::output::OutputMode;
+use pty_proxy::transport::TransportKind;
+use winptyrs::{ AgentConfig, PTYBackend };
+
+fn parse(args: &[&str]) -> Result<Command, String> {
+    cli::parse(&args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>())
+}
+
+fn run_options(args: &[&str]) -> Options {
+    match parse(args) {
+        Ok(Command::Run(options)) => options,
+        other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn cli_separates_options_from_target() {
+    let options = run_options(&[
+        "--cols", "100", "--rows", "30",
+        "--backend", "pipes",
+        "--cwd", "/tmp",
+        "--env", "A=1", "--env", "B=x=y", "--env", "C=",
+        "--output-mode", "plain",
+        "--log-level", "debug",
+        "--kill-tree",
+        "--", "/bin/sh", "-c", "echo --help",
+    ]);
+
+    assert_eq!(options.size, Some((100, 30)));
+    assert_eq!(options.backend, Some(PTYBackend::Pipes));
+    assert_eq!(options.cwd.as_deref(), Some("/tmp"));
+    let env = [("A", "1"), ("B", "x=y"), ("C", "")].map(|(name, value)| (name.to_string(), value.to_string()));
+    assert_eq!(options.env, env);
+    assert_eq!(options.output_mode, Some(OutputMode::Plain));
+    assert_eq!(options.log_level, Some(LogLevel::Debug));
+    assert!(options.kill_tree);
+    assert_eq!(options.target, ["/bin/sh", "-c", "echo --help"]);
+}
+
+#[test]
+fn cli_target_without_separator() {
+    // 以前的版本没有 --，第一个不是选项的参数就是目标程序，之后的参数都属于它
+    let options = run_options(&["--transport", "tcp", "--conerr", "/bin/sh", "-c", "exit 1", "--", "--kill-tree"]);
+    assert_eq!(options.transport, Some(TransportKind::Tcp));
+    assert!(options.conerr);
+    assert!(!options.kill_tree);
+    assert_eq!(options.target, ["/bin/sh", "-c", "exit 1", "--", "--kill-tree"]);
+
+    assert_eq!(run_options(&["--", "--version"]).target, ["--version"]);
+    assert!(run_options(&[]).target.is_empty());
+}
+
+#[test]
+fn cli_help_and_version() {
+    assert_eq!(parse(&["--help"]), Ok(Command::Help));
+    assert_eq!(parse(&["--backend", "pipes", "-h", "/bin/true"]), Ok(Command::Help));
+    assert_eq!(parse(&["-V"]), Ok(Command::Version));
+    assert!(cli::version().contains(env!("CARGO_PKG_VERSION")));
+    assert!(cli::usage("renamed-tool").contains("renamed-tool.toml"));
+}
+
+#[test]
+fn cli_errors_name_the_problem() {
+    let error = |args: &[&str]| parse(args).unwrap_err();
+
+    assert!(error(&["--bogus", "/bin/true"]).contains("未知的选项 --bogus"));
+    assert!(error(&["--cols"]).contains("--cols 缺少值"));
+    assert!(error(&["--cols", "0", "--rows", "30", "/bin/true"]).contains("--cols"));
+    assert!(error(&["--cols", "100", "/bin/true"]).contains("要同时设置"));
+    assert!(error(&["--env", "NOVALUE", "/bin/true"]).contains("<名称>=<值>"));
+    assert!(error(&["--env", "=1", "/bin/true"]).contains("环境变量名"));
+    assert!(error(&["--output-mode", "fancy", "/bin/true"]).contains("fancy"));
+    assert!(error(&["--log-level", "trace", "/bin/true"]).contains("trace"));
+    assert!(error(&["--transport", "carrier_pigeon", "/bin/true"]).contains("carrier_pigeon"));
+}
+
+#[test]
+fn cli_options_override_config_file() {
+    let mut file = Config::new(String::from("/bin/cat"), vec![String::from("-u")]);
+    file.env.push((String::from("A"), String::from("file")));
+    file.size = Some((80, 24));
+    file.log_level = LogLevel::Info;
+
+    // 命令行上没有目标程序时使用配置文件中的
+    let config = run_options(&["--env", "A=cli", "--conerr"]).into_config(Some(file.clone())).unwrap();
+    assert_eq!(config.target_program, "/bin/cat");
+    assert_eq!(config.args, ["-u"]);
+    assert_eq!(config.env.last(), Some(&(String::from("A"), String::from("cli"))));
+    assert_eq!(config.size, Some((80, 24)));
+    assert_eq!(config.log_level, LogLevel::Info);
+    assert!(config.agent_config.contains(AgentConfig::WINPTY_FLAG_CONERR));
+
+    let config = run_options(&["--cols", "100", "--rows", "30", "--", "/bin/echo"]).into_config(Some(file)).unwrap();
+    assert_eq!(config.target_program, "/bin/echo");
+    assert!(config.args.is_empty());
+    assert_eq!(config.size, Some((100, 30)));
+
+    assert!(run_options(&[]).into_config(None).unwrap_err().contains("缺少目标程序"));
+}
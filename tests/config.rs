@@ -0,0 +1,120 @@
+use pty_proxy::config::{ self, Config, DEFAULT_TIMEOUT, LogLevel };
+use pty_proxy::output::OutputMode;
+use pty_proxy::transport::TransportKind;
+use winptyrs::{ AgentConfig, MouseMode, PTYBackend };
+
+#[test]
+fn config_defaults() {
+    let config = Config::from_toml("target_program = \"cmd.exe\"").unwrap();
+    assert_eq!(config.target_program, "cmd.exe");
+    assert!(config.args.is_empty());
+    assert_eq!(config.cwd, None);
+    assert!(config.env.is_empty());
+    assert_eq!(config.transport, TransportKind::default());
+    assert_eq!(config.backend, PTYBackend::Auto);
+    assert_eq!(config.size, None);
+    assert_eq!(config.mouse_mode, MouseMode::WINPTY_MOUSE_MODE_NONE);
+    assert_eq!(config.timeout, DEFAULT_TIMEOUT);
+    assert_eq!(config.agent_config, AgentConfig::WINPTY_FLAG_COLOR_ESCAPES);
+    assert_eq!(config.output_mode, OutputMode::Raw);
+    assert_eq!(config.log_level, LogLevel::Error);
+}
+
+#[test]
+fn config_reads_every_key() {
+    let config = Config::from_toml(concat!(
+        "target_program = \"cmd.exe\"\n",
+        "args = [\"/C\", \"echo hello world\"]\n",
+        "transport = \"tcp\"\n",
+        "backend = \"pipes\"\n",
+        "child_program = \"pty-proxy-child.exe\"\n",
+        "kill_tree = true\n",
+        "cols = 120\n",
+        "rows = 40\n",
+        "mouse_mode = \"force\"\n",
+        "timeout = 30000\n",
+        "agent_flags = [\"plain_output\"]\n",
+        "conerr = true\n",
+        "cwd = \"C:\\\\Users\"\n",
+        "env = { PYTHONIOENCODING = \"utf-8\", EMPTY = \"\" }\n",
+        "output_mode = \"plain\"\n",
+        "log_level = \"info\"\n",
+    )).unwrap();
+
+    assert_eq!(config.args, ["/C", "echo hello world"]);
+    assert_eq!(config.transport, TransportKind::Tcp);
+    assert_eq!(config.backend, PTYBackend::Pipes);
+    assert_eq!(config.child_program.as_deref(), Some("pty-proxy-child.exe"));
+    assert!(config.kill_tree);
+    assert_eq!(config.size, Some((120, 40)));
+    assert_eq!(config.mouse_mode, MouseMode::WINPTY_MOUSE_MODE_FORCE);
+    assert_eq!(config.timeout, 30000);
+    assert_eq!(config.agent_config, AgentConfig::WINPTY_FLAG_PLAIN_OUTPUT | AgentConfig::WINPTY_FLAG_CONERR);
+    assert_eq!(config.cwd.as_deref(), Some("C:\\Users"));
+    assert!(config.env.contains(&(String::from("PYTHONIOENCODING"), String::from("utf-8"))));
+    assert!(config.env.contains(&(String::from("EMPTY"), String::new())));
+    assert_eq!(config.output_mode, OutputMode::Plain);
+    assert_eq!(config.log_level, LogLevel::Info);
+}
+
+#[test]
+fn config_ignores_unknown_keys_with_warning() {
+    let config = Config::from_toml("target_program = \"a\"\ntarget = \"b\"\nargs = [\"x\"]").unwrap();
+    assert_eq!(config.target_program, "a");
+    assert_eq!(config.args, ["x"]);
+    assert_eq!(config.warnings.len(), 1);
+    assert!(config.warnings[0].contains("未知的配置项 target"), "{:?}", config.warnings);
+
+    assert!(Config::from_toml("target_program = \"a\"").unwrap().warnings.is_empty());
+}
+
+#[test]
+fn config_errors_name_the_problem() {
+    let error = |content: &str| Config::from_toml(content).unwrap_err();
+
+    assert!(error("target_program = ").contains("无法解析配置文件"));
+    assert!(error("args = []").contains("target_program"));
+    assert!(error("target_program = 1").contains("target_program 应该是字符串"));
+    assert!(error("target_program = \"a\"\nargs = [1]").contains("args 应该是字符串数组"));
+    assert!(error("target_program = \"a\"\ntransport = \"carrier_pigeon\"").contains("carrier_pigeon"));
+    assert!(error("target_program = \"a\"\nbackend = \"telnet\"").contains("telnet"));
+    #[cfg(unix)]
+    assert!(error("target_program = \"a\"\nbackend = \"conpty\"").contains("当前平台没有后端 \"conpty\""));
+    assert!(error("target_program = \"a\"\nkill_tree = \"yes\"").contains("kill_tree 应该是 true 或 false"));
+    assert!(error("target_program = \"a\"\ncols = 0\nrows = 40").contains("cols 应该是 1 到 65535 之间的整数"));
+    assert!(error("target_program = \"a\"\ncols = 120\nrows = 70000").contains("rows"));
+    assert!(error("target_program = \"a\"\ncols = 120").contains("cols 和 rows 要同时设置"));
+    assert!(error("target_program = \"a\"\nmouse_mode = \"always\"").contains("always"));
+    assert!(error("target_program = \"a\"\ntimeout = -1").contains("timeout"));
+    assert!(error("target_program = \"a\"\nagent_flags = [\"bold\"]").contains("bold"));
+    assert!(
+        error("target_program = \"a\"\nagent_flags = [\"plain_output\", \"color_escapes\"]")
+            .contains("不能同时使用")
+    );
+    assert!(error("target_program = \"a\"\nenv = [\"A=1\"]").contains("env 应该是值为字符串的表"));
+    assert!(error("target_program = \"a\"\nenv = { A = 1 }").contains("env 应该是值为字符串的表"));
+    assert!(error("target_program = \"a\"\nenv = { \"A=B\" = \"1\" }").contains("无效的环境变量名"));
+    assert!(error("target_program = \"a\"\noutput_mode = \"fancy\"").contains("fancy"));
+    assert!(error("target_program = \"a\"\nlog_level = \"trace\"").contains("trace"));
+}
+
+#[test]
+fn child_option_names_round_trip() {
+    for name in ["auto", "pipes"] {
+        assert_eq!(config::backend_name(config::parse_backend(name).unwrap()), name);
+    }
+    // 自动选择时要说明其他平台的后端为什么不可用
+    assert_eq!(config::backend_name(PTYBackend::ConPTY), "conpty");
+    assert_eq!(config::backend_name(PTYBackend::WinPTY), "winpty");
+    for mode in [MouseMode::WINPTY_MOUSE_MODE_NONE, MouseMode::WINPTY_MOUSE_MODE_AUTO, MouseMode::WINPTY_MOUSE_MODE_FORCE] {
+        assert_eq!(config::parse_mouse_mode(config::mouse_mode_name(mode)).unwrap(), mode);
+    }
+    for flags in [
+        AgentConfig::empty(),
+        AgentConfig::WINPTY_FLAG_COLOR_ESCAPES,
+        AgentConfig::WINPTY_FLAG_PLAIN_OUTPUT | AgentConfig::WINPTY_FLAG_CONERR,
+    ] {
+        let names = config::agent_flag_names(flags);
+        assert_eq!(config::parse_agent_flags(names.split(',').filter(|name| !name.is_empty())).unwrap(), flags);
+    }
+}
@@ -0,0 +1,33 @@
+use pty_proxy::output::{ EscapeStripper, OutputMode };
+
+const OUTPUT: &[u8] = concat!(
+    "\x1b[?25l\x1b[2J\x1b[H",         // 控制序列
+    "\x1b]0;C:\\Windows\\cmd.exe\x07", // 以 BEL 结束的窗口标题
+    "plain \x1b[1;31mred\x1b[0m",
+    "\x1b(B\x1b=",                     // 选择字符集和其他两字节的序列
+    "\x1bP1$r0m\x1b\\",                // 以 ESC \ 结束的设备控制字符串
+    " 中文\r\n"
+).as_bytes();
+
+#[test]
+fn stripper_removes_escape_sequences() {
+    assert_eq!(EscapeStripper::new().strip(OUTPUT), "plain red 中文\r\n".as_bytes());
+}
+
+#[test]
+fn stripper_handles_split_sequences() {
+    for chunk in [1, 2, 3, 5, 7] {
+        let mut stripper = EscapeStripper::new();
+        let stripped: Vec<u8> = OUTPUT.chunks(chunk).flat_map(|piece| stripper.strip(piece)).collect();
+        assert_eq!(stripped, "plain red 中文\r\n".as_bytes(), "{}", chunk);
+    }
+}
+
+#[test]
+fn output_mode_names() {
+    for mode in OutputMode::ALL {
+        assert_eq!(mode.name().parse(), Ok(mode));
+    }
+    assert_eq!(OutputMode::default(), OutputMode::Raw);
+    assert!("fancy".parse::<OutputMode>().unwrap_err().contains("raw, plain"));
+}
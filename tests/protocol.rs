@@ -0,0 +1,119 @@
+use std::sync::Arc;
+
+use pty_proxy::protocol::{ Decoder, MAX_FRAME_LEN, Message, MessageReader, MessageWriter, PROTOCOL_VERSION, ProtocolError, Signal };
+use pty_proxy::transport::{ self, Direction, TransportKind };
+use winptyrs::ExitStatus;
+
+fn messages() -> Vec<Message> {
+    vec![
+        Message::Hello { version: PROTOCOL_VERSION },
+        Message::Data(b"plain \x1b[1mbold\x1b[0m \0\xff".to_vec()),
+        Message::Data(Vec::new()),
+        Message::Resize { cols: 1024, rows: 2 },
+        Message::Signal(Signal::Interrupt),
+        Message::Signal(Signal::Terminate),
+        Message::Signal(Signal::Kill),
+        Message::Exit(ExitStatus::Exited(7)),
+        Message::Exit(ExitStatus::Signaled(15)),
+        Message::Exit(ExitStatus::NtStatus(0xC0000005)),
+        Message::Exit(ExitStatus::Killed),
+        Message::Error(String::from("无法写入 PTY")),
+        Message::Ping(u32::MAX),
+        Message::Pong(42),
+        Message::Log(String::from("使用后端 unix")),
+    ]
+}
+
+#[test]
+fn messages_round_trip() {
+    for message in messages() {
+        let frame = message.encode();
+        let len = u32::from_be_bytes(frame[..4].try_into().unwrap()) as usize;
+        assert_eq!(len, frame.len() - 4);
+        assert_eq!(Message::decode(&frame[4..]), Ok(message));
+    }
+}
+
+#[test]
+fn decoder_reassembles_split_frames() {
+    let stream: Vec<u8> = messages().iter().flat_map(Message::encode).collect();
+    // 按每种分段大小投喂，包括一次一个字节
+    for chunk in [1, 2, 3, 5, 7, 64, stream.len()] {
+        let mut decoder = Decoder::new();
+        let mut decoded = Vec::new();
+        for piece in stream.chunks(chunk) {
+            decoder.push(piece);
+            while let Some(message) = decoder.next_message().unwrap() {
+                decoded.push(message);
+            }
+        }
+        assert_eq!(decoded, messages(), "{}", chunk);
+        assert!(decoder.is_empty());
+    }
+}
+
+#[test]
+fn decoder_waits_for_whole_frame() {
+    let frame = Message::Data(b"hello".to_vec()).encode();
+    let mut decoder = Decoder::new();
+    decoder.push(&frame[..frame.len() - 1]);
+    assert_eq!(decoder.next_message(), Ok(None));
+    assert!(!decoder.is_empty());
+    decoder.push(&frame[frame.len() - 1..]);
+    assert_eq!(decoder.next_message(), Ok(Some(Message::Data(b"hello".to_vec()))));
+}
+
+#[test]
+fn decoder_rejects_invalid_frames() {
+    let decode = |bytes: &[u8]| {
+        let mut decoder = Decoder::new();
+        decoder.push(bytes);
+        decoder.next_message()
+    };
+    assert_eq!(decode(&[0, 0, 0, 0]), Err(ProtocolError::EmptyFrame));
+    assert_eq!(decode(&[0, 0, 0, 1, 99]), Err(ProtocolError::UnknownType(99)));
+    assert_eq!(decode(&[0, 0, 0, 2, 4, 9]), Err(ProtocolError::Malformed(4)));
+    assert_eq!(decode(&[0, 0, 0, 3, 3, 0, 80]), Err(ProtocolError::Malformed(3)));
+    assert_eq!(decode(&[0, 0, 0, 3, 6, 0xff, 0xfe]), Err(ProtocolError::Malformed(6)));
+    let too_large = (MAX_FRAME_LEN as u32 + 1).to_be_bytes();
+    assert_eq!(decode(&too_large), Err(ProtocolError::FrameTooLarge(MAX_FRAME_LEN + 1)));
+}
+
+#[test]
+fn messages_over_transport() {
+    let listener = transport::listen(TransportKind::Tcp, Direction::Inbound).unwrap();
+    let endpoint = listener.endpoint();
+    let child = std::thread::spawn(move || {
+        let writer = MessageWriter::new(Arc::from(transport::connect(&endpoint, Direction::Inbound).unwrap())).unwrap();
+        writer.send_data(b"output").unwrap();
+        writer.send(&Message::Exit(ExitStatus::Exited(3))).unwrap();
+    });
+    let mut reader = MessageReader::new(Arc::from(listener.accept().unwrap()));
+    child.join().unwrap();
+
+    // 握手消息不会返回给调用者
+    assert_eq!(reader.read_message().unwrap(), Some(Message::Data(b"output".to_vec())));
+    assert_eq!(reader.read_message().unwrap(), Some(Message::Exit(ExitStatus::Exited(3))));
+    assert_eq!(reader.read_message().unwrap(), None);
+}
+
+#[test]
+fn reader_checks_hello() {
+    for (first, expected) in [
+        (Message::Hello { version: PROTOCOL_VERSION + 1 }, ProtocolError::VersionMismatch(PROTOCOL_VERSION + 1)),
+        (Message::Data(b"raw".to_vec()), ProtocolError::MissingHello),
+    ] {
+        let listener = transport::listen(TransportKind::Tcp, Direction::Inbound).unwrap();
+        let endpoint = listener.endpoint();
+        let child = std::thread::spawn(move || {
+            let transport = transport::connect(&endpoint, Direction::Inbound).unwrap();
+            transport.write_all(&first.encode()).unwrap();
+        });
+        let mut reader = MessageReader::new(Arc::from(listener.accept().unwrap()));
+        child.join().unwrap();
+
+        let error = reader.read_message().unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), expected.to_string());
+    }
+}
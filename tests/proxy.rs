@@ -0,0 +1,579 @@
+#![cfg(unix)]
+
+use std::fs;
+use std::io::{ BufRead, BufReader, Read, Write };
+use std::process::{ Command, Stdio };
+use std::sync::Arc;
+use std::time::{ Duration, Instant };
+
+use pty_proxy::child;
+use pty_proxy::protocol::{ Message, MessageReader };
+use pty_proxy::transport::{ self, Direction, TransportKind };
+use winptyrs::{ PTYBackend, PtyCommand };
+
+#[test]
+fn proxy_forwards_io_and_exit_code() {
+    let mut proxy = Command::new(env!("CARGO_BIN_EXE_pty-proxy"))
+        .arg("/bin/sh")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    proxy.stdin.take().unwrap().write_all(b"echo hello $((40+2)); exit 7\n").unwrap();
+    let output = proxy.wait_with_output().unwrap();
+
+    assert!(String::from_utf8_lossy(&output.stdout).contains("hello 42"));
+    assert_eq!(output.status.code(), Some(7));
+}
+
+#[test]
+fn proxy_passes_target_args() {
+    let output = Command::new(env!("CARGO_BIN_EXE_pty-proxy"))
+        .args(["/bin/echo", "first", "second"])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+
+    assert!(String::from_utf8_lossy(&output.stdout).contains("first second"));
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn proxy_selects_transport_by_flag() {
+    for transport in ["unix_socket", "tcp", "anonymous_pipe"] {
+        let mut proxy = Command::new(env!("CARGO_BIN_EXE_pty-proxy"))
+            .args(["--transport", transport, "/bin/sh"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        proxy.stdin.take().unwrap().write_all(b"echo via $((2+3)); exit 5\n").unwrap();
+        let output = proxy.wait_with_output().unwrap();
+
+        assert!(String::from_utf8_lossy(&output.stdout).contains("via 5"), "{}", transport);
+        assert_eq!(output.status.code(), Some(5), "{}", transport);
+    }
+}
+
+#[test]
+fn proxy_does_not_leak_anonymous_pipes_to_target() {
+    // 末尾的 true 让 sh 不会直接 exec 成 ls，列出的是目标程序自己打开的文件
+    let output = Command::new(env!("CARGO_BIN_EXE_pty-proxy"))
+        .args(["--transport", "anonymous_pipe", "--backend", "unix", "/bin/sh", "-c", "ls /proc/$$/fd; true"])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.split_whitespace().collect::<Vec<_>>(), ["0", "1", "2"], "{}", stdout);
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn proxy_reports_target_that_cannot_start() {
+    for backend in ["unix", "pipes"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_pty-proxy"))
+            .args(["--backend", backend, "/nonexistent/program"])
+            .stdin(Stdio::null())
+            .output()
+            .unwrap();
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.starts_with("pty-proxy-child: "), "{}", stderr);
+        assert!(stderr.contains("/nonexistent/program"), "{}", stderr);
+        assert_eq!(output.status.code(), Some(child::SPAWN_FAILED_EXIT_CODE), "{}", backend);
+    }
+}
+
+#[test]
+fn proxy_reports_child_that_cannot_start() {
+    let output = Command::new(env!("CARGO_BIN_EXE_pty-proxy"))
+        .args(["--child", "no-such-pty-proxy-child", "/bin/true"])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+
+    // 说明原因后以非零代码退出，而不是 panic
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.starts_with("pty-proxy: "), "{}", stderr);
+    assert!(stderr.contains("no-such-pty-proxy-child"), "{}", stderr);
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn proxy_rejects_unknown_transport() {
+    let output = Command::new(env!("CARGO_BIN_EXE_pty-proxy"))
+        .args(["--transport", "carrier_pigeon", "/bin/true"])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("carrier_pigeon"));
+}
+
+#[test]
+fn proxy_selects_backend_at_runtime() {
+    for backend in ["auto", "unix"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_pty-proxy"))
+            .args(["--backend", backend, "/bin/sh", "-c", "exit 6"])
+            .stdin(Stdio::null())
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(6), "{}", backend);
+    }
+
+    // Linux 上没有 ConPTY，启动前就报错
+    let output = Command::new(env!("CARGO_BIN_EXE_pty-proxy"))
+        .args(["--backend", "conpty", "/bin/true"])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("conpty"));
+}
+
+#[test]
+fn proxy_separates_options_from_target() {
+    let output = Command::new(env!("CARGO_BIN_EXE_pty-proxy"))
+        .args(["--backend", "pipes", "--cwd", "/", "--env", "GREETING=hello world", "--"])
+        .args(["/bin/sh", "-c", "echo \"$GREETING from $(pwd)\"; echo \"$0\"", "--help"])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "hello world from /\n--help\n");
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn proxy_help_version_and_unknown_options() {
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_pty-proxy")).args(args).stdin(Stdio::null()).output().unwrap()
+    };
+
+    let output = run(&["--help"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("--output-mode"));
+    assert_eq!(output.status.code(), Some(0));
+
+    let output = run(&["--version"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains(env!("CARGO_PKG_VERSION")));
+    assert_eq!(output.status.code(), Some(0));
+
+    let output = run(&["--bogus", "--", "/bin/true"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--bogus"));
+    assert_eq!(output.status.code(), Some(2));
+
+    let output = run(&["--kill-tree"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("缺少目标程序"));
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn proxy_reads_config_file_by_flag() {
+    let dir = std::env::temp_dir().join(format!("pty-proxy-config-flag-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let config = dir.join("elsewhere.toml");
+    fs::write(&config, "target_program = \"/bin/sh\"\nargs = [\"-c\", \"stty size\"]\ncols = 80\nrows = 24\n").unwrap();
+
+    // 命令行上的选项覆盖配置文件中的
+    let output = Command::new(env!("CARGO_BIN_EXE_pty-proxy"))
+        .args(["--config", config.to_str().unwrap(), "--cols", "100", "--rows", "30"])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert!(String::from_utf8_lossy(&output.stdout).contains("30 100"));
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn proxy_plain_output_mode_strips_escapes() {
+    let output = Command::new(env!("CARGO_BIN_EXE_pty-proxy"))
+        .args(["--output-mode", "plain", "--", "/usr/bin/printf", "\\033]0;title\\007\\033[1mbold\\033[0m"])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "bold");
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn proxy_logs_backend_at_info_level() {
+    let output = Command::new(env!("CARGO_BIN_EXE_pty-proxy"))
+        .args(["--log-level", "info", "--", "/bin/true"])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("pty-proxy-child: 后端 conpty 不可用"), "{}", stderr);
+    assert!(stderr.contains("pty-proxy-child: 使用后端 unix\n"), "{}", stderr);
+    assert_eq!(output.status.code(), Some(0));
+
+    // 默认只显示错误
+    let output = Command::new(env!("CARGO_BIN_EXE_pty-proxy"))
+        .args(["--", "/bin/true"])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+}
+
+#[test]
+fn proxy_pipes_backend_forwards_plain_output() {
+    let mut proxy = Command::new(env!("CARGO_BIN_EXE_pty-proxy"))
+        .args(["--backend", "pipes", "/bin/sh"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    proxy.stdin.take().unwrap().write_all(b"echo plain $((1+1)); echo oops 1>&2; exit 4\n").unwrap();
+    let output = proxy.wait_with_output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    // 没有伪终端：不回显输入，没有 \r 和转义序列，标准错误单独转发
+    assert!(stdout.contains("plain 2\n"));
+    assert!(!stdout.contains("oops"));
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "oops\n");
+    assert!(!stdout.contains("$((1+1))"));
+    assert!(!stdout.contains('\r'));
+    assert_eq!(output.status.code(), Some(4));
+}
+
+#[test]
+fn proxy_keeps_stderr_separate_with_conerr() {
+    let output = Command::new(env!("CARGO_BIN_EXE_pty-proxy"))
+        .args(["--backend", "unix", "--conerr", "/bin/sh", "-c", "printf 'oops\\351\\n' 1>&2; echo fine; test -t 1"])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    // 标准输出仍然是伪终端，标准错误按原始字节转发，不是 UTF-8 也不会被替换
+    assert!(stdout.contains("fine\r\n"));
+    assert!(!stdout.contains("oops"));
+    assert_eq!(output.stderr, b"oops\xe9\n");
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn child_reports_invalid_options_to_proxy() {
+    let listener_read = transport::listen(TransportKind::UnixSocket, Direction::Inbound).unwrap();
+    let listener_write = transport::listen(TransportKind::UnixSocket, Direction::Outbound).unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_pty-proxy"))
+        .args([child::SUBCOMMAND, "--size", "0x0", "--timeout", "soon"])
+        .args([listener_read.endpoint(), listener_write.endpoint()])
+        .arg("/bin/true")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let mut reader = MessageReader::new(Arc::from(listener_read.accept().unwrap()));
+    let _writer = listener_write.accept().unwrap();
+
+    // 不会 panic，只报告第一个错误，不启动目标程序
+    match reader.read_message().unwrap() {
+        Some(Message::Error(text)) => assert!(text.contains("0x0"), "{}", text),
+        message => panic!("{:?}", message),
+    }
+    assert!(reader.read_message().unwrap().is_none());
+    assert_eq!(child.wait().unwrap().code(), Some(2));
+}
+
+#[test]
+fn proxy_runs_external_child_by_flag() {
+    let output = Command::new(env!("CARGO_BIN_EXE_pty-proxy"))
+        .args(["--child", env!("CARGO_BIN_EXE_pty-proxy-child"), "/bin/echo", "external"])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+
+    assert!(String::from_utf8_lossy(&output.stdout).contains("external"));
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn proxy_works_alone_under_another_name() {
+    // 只复制 pty-proxy 本身，旁边没有 pty-proxy-child
+    let dir = std::env::temp_dir().join(format!("pty-proxy-renamed-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let renamed = dir.join("renamed-tool");
+    fs::copy(env!("CARGO_BIN_EXE_pty-proxy"), &renamed).unwrap();
+
+    let output = Command::new(&renamed)
+        .args(["/bin/echo", "renamed"])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert!(String::from_utf8_lossy(&output.stdout).contains("renamed"));
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn proxy_keeps_args_with_spaces_quotes_and_backslashes() {
+    let output = Command::new(env!("CARGO_BIN_EXE_pty-proxy"))
+        .args(["--backend", "pipes", "/usr/bin/printf", "[%s]", "two words", "a \"quote\"", "back\\slash\\", ""])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "[two words][a \"quote\"][back\\slash\\][]");
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn proxy_keeps_config_args_intact() {
+    let dir = std::env::temp_dir().join(format!("pty-proxy-config-args-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let renamed = dir.join("configured-tool");
+    fs::copy(env!("CARGO_BIN_EXE_pty-proxy"), &renamed).unwrap();
+    fs::write(
+        dir.join("configured-tool.toml"),
+        concat!(
+            "target_program = \"/usr/bin/printf\"\n",
+            "args = [\"<%s>\", \"two  spaces\", 'C:\\Program Files\\', '\\\"']\n",
+            "backend = \"pipes\"\n"
+        )
+    ).unwrap();
+
+    let output = Command::new(&renamed).stdin(Stdio::null()).output().unwrap();
+    // 有配置文件时命令行参数不当作选项，--help 也一样
+    let ignored = Command::new(&renamed).args(["--help", "--bogus"]).stdin(Stdio::null()).output().unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "<two  spaces><C:\\Program Files\\><\\\">");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(ignored.stdout, output.stdout);
+    assert_eq!(ignored.status.code(), Some(0));
+}
+
+#[test]
+fn proxy_passes_config_to_child() {
+    let dir = std::env::temp_dir().join(format!("pty-proxy-config-pty-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let renamed = dir.join("sized-tool");
+    fs::copy(env!("CARGO_BIN_EXE_pty-proxy"), &renamed).unwrap();
+    fs::write(
+        dir.join("sized-tool.toml"),
+        concat!(
+            "target_program = \"/bin/sh\"\n",
+            "args = [\"-c\", \"stty size; echo oops 1>&2\"]\n",
+            "backend = \"unix\"\n",
+            "cols = 120\n",
+            "rows = 40\n",
+            "mouse_mode = \"auto\"\n",
+            "timeout = 5000\n",
+            "agent_flags = [\"color_escapes\", \"conerr\"]\n"
+        )
+    ).unwrap();
+
+    let output = Command::new(&renamed).stdin(Stdio::null()).output().unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert!(String::from_utf8_lossy(&output.stdout).contains("40 120"));
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "oops\n");
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn proxy_warns_about_unknown_config_keys() {
+    let dir = std::env::temp_dir().join(format!("pty-proxy-config-unknown-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let renamed = dir.join("old-tool");
+    fs::copy(env!("CARGO_BIN_EXE_pty-proxy"), &renamed).unwrap();
+    fs::write(
+        dir.join("old-tool.toml"),
+        "target_program = \"/bin/echo\"\nargs = [\"still works\"]\nbackend = \"pipes\"\nretired_option = 1\n"
+    ).unwrap();
+
+    let output = Command::new(&renamed).stdin(Stdio::null()).output().unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    // 旧配置文件中不认识的配置项只给出警告，目标程序照常运行
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.starts_with("pty-proxy: "), "{}", stderr);
+    assert!(stderr.contains("retired_option"), "{}", stderr);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "still works\n");
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn proxy_reports_invalid_config() {
+    let dir = std::env::temp_dir().join(format!("pty-proxy-config-invalid-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let renamed = dir.join("broken-tool");
+    fs::copy(env!("CARGO_BIN_EXE_pty-proxy"), &renamed).unwrap();
+    fs::write(dir.join("broken-tool.toml"), "target_program = \"/bin/true\"\ncols = 0\nrows = 40\n").unwrap();
+
+    let output = Command::new(&renamed).stdin(Stdio::null()).output().unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    // 说明哪一项有误，而不是 panic
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("broken-tool.toml"), "{}", stderr);
+    assert!(stderr.contains("cols"), "{}", stderr);
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn proxy_stops_target_on_sigterm() {
+    let mut proxy = Command::new(env!("CARGO_BIN_EXE_pty-proxy"))
+        .args(["--backend", "pipes", "/bin/sh", "-c", "trap 'echo bye; exit 9' TERM; echo ready; while :; do sleep 0.1; done"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let mut stdout = BufReader::new(proxy.stdout.take().unwrap());
+    let mut line = String::new();
+    stdout.read_line(&mut line).unwrap();
+    assert_eq!(line, "ready\n");
+
+    // stdin 保持打开，只靠信号让目标程序退出
+    unsafe {
+        libc::kill(proxy.id() as libc::pid_t, libc::SIGTERM);
+    }
+    let mut rest = String::new();
+    stdout.read_to_string(&mut rest).unwrap();
+    assert_eq!(rest, "bye\n");
+    assert_eq!(proxy.wait().unwrap().code(), Some(9));
+}
+
+#[test]
+fn proxy_heartbeat_is_quiet_while_child_responds() {
+    let mut proxy = Command::new(env!("CARGO_BIN_EXE_pty-proxy"))
+        .args(["/bin/sh", "-c", "sleep 6; exit 3"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // stdin 保持打开，目标程序运行期间至少发送过一次心跳
+    let stdin = proxy.stdin.take();
+    let status = proxy.wait().unwrap();
+    drop(stdin);
+    let mut stderr = String::new();
+    proxy.stderr.take().unwrap().read_to_string(&mut stderr).unwrap();
+
+    assert_eq!(status.code(), Some(3));
+    assert_eq!(stderr, "");
+}
+
+#[test]
+fn proxy_stops_target_when_stdin_closes() {
+    let start = Instant::now();
+    let output = Command::new(env!("CARGO_BIN_EXE_pty-proxy"))
+        .args(["/bin/sleep", "100"])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+
+    // 等待 3 秒后 SIGTERM 结束了 sleep
+    assert_eq!(output.status.code(), Some(128 + 15));
+    assert!(start.elapsed() >= Duration::from_secs(3));
+    assert!(start.elapsed() < Duration::from_secs(20));
+}
+
+#[test]
+fn proxy_exit_code_of_signaled_target() {
+    let output = Command::new(env!("CARGO_BIN_EXE_pty-proxy"))
+        .args(["/bin/sh", "-c", "kill -KILL $$"])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(128 + 9));
+}
+
+/// 等待进程结束，几秒后仍在运行则返回 false，容器中的孤儿进程可能不会被回收
+fn wait_gone(pid: libc::pid_t) -> bool {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while Instant::now() < deadline {
+        let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).unwrap_or_default();
+        let zombie = stat.rsplit_once(')').is_some_and(|(_, rest)| rest.trim_start().starts_with('Z'));
+        if zombie || unsafe { libc::kill(pid, 0) } < 0 {
+            return true;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    false
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn proxy_kill_tree_when_killed() {
+    for backend in [None, Some("pipes")] {
+        let mut proxy = Command::new(env!("CARGO_BIN_EXE_pty-proxy"));
+        if let Some(backend) = backend {
+            proxy.args(["--backend", backend]);
+        }
+        // 后台任务忽略 SIGHUP，也不占用输出
+        let mut proxy = proxy
+            .args(["--kill-tree", "/bin/sh", "-c", "trap '' HUP; sleep 100 >/dev/null & echo pid=$!; wait"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        let mut stdout = BufReader::new(proxy.stdout.take().unwrap());
+        let mut line = String::new();
+        while !line.contains("pid=") {
+            line.clear();
+            stdout.read_line(&mut line).unwrap();
+        }
+        let pid: libc::pid_t = line.trim().trim_start_matches("pid=").parse().unwrap();
+
+        // 强制结束 pty-proxy，没有机会做任何清理
+        proxy.kill().unwrap();
+        proxy.wait().unwrap();
+        assert!(wait_gone(pid), "{:?}", backend);
+    }
+}
+
+#[test]
+fn proxy_keeps_fixed_size_without_terminal() {
+    let output = Command::new(env!("CARGO_BIN_EXE_pty-proxy"))
+        .args(["/bin/sh", "-c", "stty size"])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+
+    assert!(String::from_utf8_lossy(&output.stdout).contains("2 1024"));
+}
+
+#[test]
+fn proxy_follows_terminal_size() {
+    // 在伪终端中运行 pty-proxy，目标程序每读到一行就报告一次自己的终端大小
+    let pty = PtyCommand::new(env!("CARGO_BIN_EXE_pty-proxy"))
+        .args(["/bin/sh", "-c", "stty size; while read line; do stty size; done"])
+        .size(100, 30)
+        .backend(PTYBackend::Unix)
+        .spawn()
+        .unwrap();
+
+    let read_until = |expected: &str| {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let mut output = String::new();
+        while !output.contains(expected) {
+            assert!(Instant::now() < deadline, "{:?}", output);
+            pty.write_bytes(b"\n").unwrap();
+            output.push_str(&pty.read(4096, false).unwrap().to_string_lossy());
+        }
+    };
+    read_until("30 100");
+
+    // 改变外层终端的大小，pty-proxy 收到 SIGWINCH 后转发给目标程序
+    pty.set_size(120, 40).unwrap();
+    read_until("40 120");
+}
@@ -0,0 +1,123 @@
+use std::thread;
+
+use pty_proxy::transport::{ self, Direction, TransportKind, new_pipe_uuid };
+
+/// 可以在同一个进程里测试的通信方式，匿名管道需要子进程继承，由 tests/proxy.rs 覆盖
+fn kinds() -> impl Iterator<Item = TransportKind> {
+    TransportKind::ALL
+        .into_iter()
+        .filter(|kind| kind.is_supported() && *kind != TransportKind::AnonymousPipe)
+}
+
+#[test]
+fn new_pipe_uuid_has_no_dashes() {
+    let pipe_uuid = new_pipe_uuid();
+    assert_eq!(pipe_uuid.len(), 32);
+    assert!(!pipe_uuid.contains('-'));
+}
+
+#[test]
+fn transport_kind_names_round_trip() {
+    for kind in TransportKind::ALL {
+        assert_eq!(kind.name().parse::<TransportKind>().unwrap(), kind);
+        assert_eq!(kind.to_string(), kind.name());
+    }
+    assert!(TransportKind::default().is_supported());
+    assert!("carrier_pigeon".parse::<TransportKind>().is_err());
+}
+
+#[test]
+fn endpoint_names_transport_kind() {
+    for kind in kinds() {
+        let listener = transport::listen(kind, Direction::Inbound).unwrap();
+        assert!(listener.endpoint().starts_with(&format!("{}:", kind.name())));
+    }
+}
+
+#[test]
+fn child_writes_proxy_reads() {
+    for kind in kinds() {
+        let listener = transport::listen(kind, Direction::Inbound).unwrap();
+        let endpoint = listener.endpoint();
+
+        let client = thread::spawn(move || {
+            let pipe = transport::connect(&endpoint, Direction::Inbound).unwrap();
+            pipe.write_all("你好, pipe\0\u{1b}[0m".as_bytes()).unwrap();
+        });
+
+        let pipe = listener.accept().unwrap();
+        client.join().unwrap();
+
+        let mut received = Vec::new();
+        let mut buffer = [0u8; 4];
+        loop {
+            let n = pipe.read(&mut buffer).unwrap();
+            if n == 0 {
+                break;
+            }
+            received.extend_from_slice(&buffer[..n]);
+        }
+        assert_eq!(received, "你好, pipe\0\u{1b}[0m".as_bytes(), "{}", kind);
+    }
+}
+
+#[test]
+fn proxy_writes_child_reads() {
+    for kind in kinds() {
+        let listener = transport::listen(kind, Direction::Outbound).unwrap();
+        let endpoint = listener.endpoint();
+
+        let client = thread::spawn(move || {
+            let pipe = transport::connect(&endpoint, Direction::Outbound).unwrap();
+            let mut buffer = [0u8; 16];
+            let n = pipe.read(&mut buffer).unwrap();
+            buffer[..n].to_vec()
+        });
+
+        let pipe = listener.accept().unwrap();
+        pipe.write_all(b"input\r\n").unwrap();
+        assert_eq!(client.join().unwrap(), b"input\r\n", "{}", kind);
+
+        // 对端关闭之后写入应当失败
+        assert!(pipe.write_all(&[0u8; 65536]).and_then(|_| pipe.write_all(&[0u8; 65536])).is_err(), "{}", kind);
+    }
+}
+
+#[test]
+fn connect_to_missing_endpoint_fails() {
+    for kind in kinds() {
+        let endpoint = transport::listen(kind, Direction::Inbound).unwrap().endpoint();
+        // listener 已经被丢弃
+        assert!(transport::connect(&endpoint, Direction::Inbound).is_err(), "{}", kind);
+        assert!(transport::connect(&endpoint, Direction::Outbound).is_err(), "{}", kind);
+    }
+    assert!(transport::connect("anonymous_pipe:not-a-handle", Direction::Inbound).is_err());
+    assert!(transport::connect("tcp:127.0.0.1:1", Direction::Inbound).is_err());
+}
+
+#[test]
+fn tcp_rejects_wrong_token() {
+    let listener = transport::listen(TransportKind::Tcp, Direction::Inbound).unwrap();
+    let endpoint = listener.endpoint();
+    let (address, token) = endpoint.rsplit_once('/').unwrap();
+
+    let client = thread::spawn({
+        let forged = format!("{}/{}", address, "0".repeat(token.len()));
+        let endpoint = endpoint.clone();
+        move || {
+            // 令牌不对的连接会被服务端关闭，读不到任何数据
+            let forged = transport::connect(&forged, Direction::Outbound).unwrap();
+            let mut buffer = [0u8; 16];
+            assert_eq!(forged.read(&mut buffer).unwrap(), 0);
+
+            let pipe = transport::connect(&endpoint, Direction::Inbound).unwrap();
+            pipe.write_all(b"ok").unwrap();
+        }
+    });
+
+    let pipe = listener.accept().unwrap();
+    let mut buffer = [0u8; 16];
+    let n = pipe.read(&mut buffer).unwrap();
+    assert_eq!(&buffer[..n], b"ok");
+    client.join().unwrap();
+}
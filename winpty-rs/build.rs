@@ -6,16 +6,21 @@ use windows::Win32::System::LibraryLoader::{ GetProcAddress, GetModuleHandleW };
 #[cfg(feature = "conpty")]
 use windows::core::{ PCSTR };
 
+#[cfg(windows)]
 #[cfg(feature = "conpty")]
 use windows::core::{ HSTRING };
+#[cfg(windows)]
 #[cfg(feature = "conpty")]
 use std::i64;
 
+#[cfg(windows)]
 #[cfg(feature = "winpty")]
 use which::which;
 
+#[cfg(windows)]
 #[cfg(any(feature = "conpty", feature = "winpty"))]
 use std::process::Command;
+#[cfg(windows)]
 #[cfg(any(feature = "conpty", feature = "winpty"))]
 use std::str;
 
@@ -49,6 +54,7 @@ fn command_output(cmd: &mut Command) -> String {
     str::from_utf8(&cmd.output().unwrap().stdout).unwrap().trim().to_string()
 }
 
+#[cfg_attr(not(windows), allow(clippy::needless_return))]
 fn main() {
     if std::env::var("DOCS_RS").is_ok() {
         return;
@@ -1,7 +1,8 @@
-//! Create and spawn processes inside a pseudoterminal in Windows.
+//! Create and spawn processes inside a pseudoterminal in Windows and Unix.
 //!
 //! This crate provides an abstraction over different backend implementations to spawn PTY processes in Windows.
-//! Right now this library supports using [`WinPTY`] and [`ConPTY`].
+//! Right now this library supports using [`WinPTY`] and [`ConPTY`], as well as the native `openpty` interface
+//! on Unix systems. A mock backend that simulates the process in memory is also available for testing.
 //!
 //! The abstraction is represented through the [`PTY`] struct, which declares methods to initialize, spawn, read,
 //! write and get diverse information about the state of a process that is running inside a pseudoterminal.
@@ -16,4 +17,8 @@ extern crate num_traits;
 
 pub mod pty;
 // mod pty_spawn;
-pub use pty::{PTY, PTYArgs, PTYBackend, MouseMode, AgentConfig};
+pub use pty::{PTY, PTYArgs, PTYBackend, PTYEvent, PtyCommand, PtyError, PtySignal, ExitStatus, MouseMode, AgentConfig, MockProgram, MockScript};
+pub use pty::{OverflowPolicy, OutputStats, ReadOutcome};
+pub use pty::{PtyReader, PtyWriter, PtyControl};
+#[cfg(feature = "tokio")]
+pub use pty::{AsyncPtyReader, AsyncPtyWriter, AsyncPtyControl};
@@ -1,6 +1,6 @@
 
 //! This module declares the [`PTY`] struct, which enables a Rust
-//! program to create a pseudoterminal (PTY) in Windows.
+//! program to create a pseudoterminal (PTY) in Windows and Unix.
 //!
 //! Additionally, this module also contains several generic structs used to
 //! perform I/O operations with a process, [`PTYProcess`]. Also it defines
@@ -12,19 +12,46 @@
 // Local modules
 mod winpty;
 mod conpty;
+mod unix;
+mod mock;
+mod pipes;
 mod base;
+mod buffer;
+pub mod cmdline;
+mod command;
+mod decoder;
+mod error;
+mod split;
+mod status;
+#[cfg(feature = "tokio")]
+mod asynchronous;
 
 use std::ffi::OsString;
+use std::sync::mpsc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
 // Local imports
 use self::winpty::WinPTY;
 pub use self::winpty::{MouseMode, AgentConfig};
 use self::conpty::ConPTY;
-pub use base::{PTYImpl, PTYProcess};
+use self::unix::UnixPTY;
+use self::mock::MockPTY;
+use self::pipes::PipesPTY;
+pub use self::mock::{MockIO, MockProgram, MockScript, MockStep};
+pub use base::{PTYImpl, PTYChild, PTYProcess, PTYEvent, PtySignal, ReadOutcome};
+pub use buffer::{OverflowPolicy, OutputStats, DEFAULT_OUTPUT_CAPACITY};
+pub use command::PtyCommand;
+pub use decoder::Utf8Decoder;
+pub use error::PtyError;
+pub use split::{PtyReader, PtyWriter, PtyControl};
+pub use status::ExitStatus;
+#[cfg(feature = "tokio")]
+pub use asynchronous::{AsyncPtyReader, AsyncPtyWriter, AsyncPtyControl};
 
 /// Available backends to create pseudoterminals.
 #[derive(Primitive)]
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum PTYBackend {
 	/// Use the native Windows API, available from Windows 10 (Build version 1809).
 	ConPTY = 0,
@@ -34,6 +61,14 @@ pub enum PTYBackend {
 	Auto = 2,
 	/// Placeholder value used to declare that a PTY was created with no backend.
 	NoBackend = 3,
+	/// Use the native Unix pseudoterminal interface, available on Linux and other Unix systems.
+	Unix = 4,
+	/// Simulate the process in memory, see [`MockProgram`]. Available on every platform,
+	/// it is never selected automatically.
+	Mock = 5,
+	/// Spawn the process with plain pipes as its standard streams, without any pseudoconsole.
+	/// Available on every platform, it is never selected automatically.
+	Pipes = 6,
 }
 
 /// Data struct that represents the possible arguments used to create a pseudoterminal
@@ -49,7 +84,8 @@ pub struct PTYArgs {
 	/// Amount of time to wait for the agent (in ms) to startup and to wait for any given
     /// agent RPC request.
 	pub timeout: u32,
-	/// General configuration settings for the winpty backend.
+	/// General configuration settings for the winpty backend. The Unix backend
+	/// also honours [`AgentConfig::WINPTY_FLAG_CONERR`], see [`PTY::read_stderr`].
 	pub agent_config: AgentConfig
 }
 
@@ -67,7 +103,11 @@ pub struct PTYArgs {
 /// use std::ffi::OsString;
 /// use winptyrs::{PTY, PTYArgs, MouseMode, AgentConfig};
 ///
-/// let cmd = OsString::from("c:\\windows\\system32\\cmd.exe");
+/// let cmd = if cfg!(windows) {
+///     OsString::from("c:\\windows\\system32\\cmd.exe")
+/// } else {
+///     OsString::from("/bin/sh")
+/// };
 /// let pty_args = PTYArgs {
 ///     cols: 80,
 ///     rows: 25,
@@ -115,113 +155,160 @@ pub struct PTYArgs {
 /// };
 ///
 /// // Initialize a winpty and a conpty pseudoterminal.
+/// # #[cfg(windows)] {
 /// let conpty = PTY::new_with_backend(&pty_args, PTYBackend::ConPTY).unwrap();
 /// let winpty = PTY::new_with_backend(&pty_args, PTYBackend::WinPTY).unwrap();
+/// # }
+///
+/// // Initialize a pseudoterminal using the native Unix interface.
+/// # #[cfg(unix)]
+/// let unix = PTY::new_with_backend(&pty_args, PTYBackend::Unix).unwrap();
 /// ```
 pub struct PTY {
 	 /// Backend used by the current pseudoterminal, must be one of [`self::PTYBackend`].
 	 /// If the value is [`self::PTYBackend::NoBackend`], then no operations will be available.
 	 backend: PTYBackend,
 	 /// Reference to the PTY handler which depends on the value of `backend`.
-	 pty: Box<dyn PTYImpl>
+	 pty: Box<dyn PTYImpl>,
+	 /// `true` once the process was killed through [`PTY::kill`] while it was alive.
+	 killed: AtomicBool,
+	 /// Backends that [`PTY::new`] tried before `backend`, with the reason each one failed.
+	 fallbacks: Vec<(PTYBackend, PtyError)>
 }
 
 impl PTY {
 	/// Create a new pseudoterminal setting the backend automatically.
-	pub fn new(args: &PTYArgs) -> Result<PTY, OsString> {
-		let mut errors: OsString = OsString::from("There were some errors trying to instantiate a PTY:");
-		// Try to create a PTY using the ConPTY backend
-		let conpty_instance: Result<Box<dyn PTYImpl>, OsString> = ConPTY::new(args);
-	 	let pty: Option<PTY> =
-			match conpty_instance {
-				Ok(conpty) => {
-					let pty_instance = PTY {
-						backend: PTYBackend::ConPTY,
-						pty: conpty
-					};
-					Some(pty_instance)
-				},
-				Err(err) => {
-					errors = OsString::from(format!("{:?} (ConPTY) -> {:?};", errors, err));
-					None
-				}
-			};
-
-		// Try to create a PTY instance using the WinPTY backend
-		match pty {
-			Some(pty) => Ok(pty),
-			None => {
-				let winpty_instance: Result<Box<dyn PTYImpl>, OsString> = WinPTY::new(args);
-				match winpty_instance {
-					Ok(winpty) => {
-						let pty_instance = PTY {
-							backend: PTYBackend::WinPTY,
-							pty: winpty
-						};
-						Ok(pty_instance)
-					},
-					Err(err) => {
-						errors = OsString::from(format!("{:?} (WinPTY) -> {:?}", errors, err));
-						Err(errors)
-					}
+	///
+	/// The backends are tried in order: ConPTY, WinPTY and then Unix, the first
+	/// one that can be instantiated is used.
+	///
+	/// The backends skipped on the way are reported by [`PTY::backend_fallbacks`].
+	/// If every backend fails, a [`PtyError::BackendUnavailable`] error for
+	/// [`PTYBackend::Auto`] lists the reason of each failure.
+	pub fn new(args: &PTYArgs) -> Result<PTY, PtyError> {
+		let mut fallbacks: Vec<(PTYBackend, PtyError)> = Vec::new();
+		for backend in [PTYBackend::ConPTY, PTYBackend::WinPTY, PTYBackend::Unix] {
+			match PTY::new_with_backend(args, backend) {
+				Ok(mut pty) => {
+					pty.fallbacks = fallbacks;
+					return Ok(pty);
 				}
+				// An invalid size is not the fault of the backend, no other one would accept it.
+				Err(err @ PtyError::InvalidSize { .. }) => return Err(err),
+				Err(err) => fallbacks.push((backend, err))
 			}
 		}
+		let reasons: Vec<String> = fallbacks.iter()
+			.map(|(backend, err)| format!("{:?}: {}", backend, err))
+			.collect();
+		Err(PtyError::BackendUnavailable { backend: PTYBackend::Auto, reason: reasons.join("; ") })
 	}
 
 	/// Create a new pseudoterminal using a given backend
-	pub fn new_with_backend(args: &PTYArgs, backend: PTYBackend) -> Result<PTY, OsString> {
-		match backend {
-			PTYBackend::ConPTY => {
-				match ConPTY::new(args) {
-					Ok(conpty) => {
-						let pty = PTY {
-							backend,
-							pty: conpty
-						};
-						Ok(pty)
-					},
-					Err(err) => Err(err)
-				}
-			},
-			PTYBackend::WinPTY => {
-				match WinPTY::new(args) {
-					Ok(winpty) => {
-						let pty = PTY {
-							backend,
-							pty: winpty
-						};
-						Ok(pty)
-					},
-					Err(err) => Err(err)
-				}
-			},
-			PTYBackend::Auto => PTY::new(args),
-			PTYBackend::NoBackend => Err(OsString::from("NoBackend is not a valid option"))
-		}
+	pub fn new_with_backend(args: &PTYArgs, backend: PTYBackend) -> Result<PTY, PtyError> {
+		let pty: Box<dyn PTYImpl> = match backend {
+			PTYBackend::ConPTY => ConPTY::new(args)?,
+			PTYBackend::WinPTY => WinPTY::new(args)?,
+			PTYBackend::Unix => UnixPTY::new(args)?,
+			PTYBackend::Mock => MockPTY::new(args)?,
+			PTYBackend::Pipes => PipesPTY::new(args)?,
+			PTYBackend::Auto => return PTY::new(args),
+			PTYBackend::NoBackend => return Err(PtyError::unavailable(backend, "NoBackend is not a valid option"))
+		};
+		Ok(PTY { backend, pty, killed: AtomicBool::new(false), fallbacks: Vec::new() })
+	}
+
+	/// Create a new pseudoterminal using the [`PTYBackend::Mock`] backend, which
+	/// runs `program` when a process is spawned.
+	///
+	/// A mock pseudoterminal created with [`PTY::new_with_backend`] instead
+	/// replays the session file given as `appname` to [`PTY::spawn`], see
+	/// [`MockScript`] for its format.
+	///
+	/// # Examples
+	/// ```
+	/// use std::ffi::OsString;
+	/// use winptyrs::{PTY, PTYArgs, MouseMode, AgentConfig, MockProgram};
+	///
+	/// let pty_args = PTYArgs {
+	///     cols: 80,
+	///     rows: 25,
+	///     mouse_mode: MouseMode::WINPTY_MOUSE_MODE_NONE,
+	///     timeout: 10000,
+	///     agent_config: AgentConfig::WINPTY_FLAG_COLOR_ESCAPES
+	/// };
+	///
+	/// // Echo a single line back and exit.
+	/// let program = MockProgram::from_fn(|io| {
+	///     let input = io.read().unwrap();
+	///     io.write(&String::from_utf8_lossy(&input)).unwrap();
+	///     7
+	/// });
+	///
+	/// let mut pty = PTY::new_mock(&pty_args, program).unwrap();
+	/// pty.spawn(OsString::from("mock"), None, None, None).unwrap();
+	/// pty.write(OsString::from("hello\r\n")).unwrap();
+	/// assert_eq!(pty.read(7, true).unwrap(), OsString::from("hello\r\n"));
+	///
+	/// pty.wait_for_exit().unwrap();
+	/// assert_eq!(pty.get_exitstatus().unwrap(), Some(7));
+	/// ```
+	pub fn new_mock(args: &PTYArgs, program: MockProgram) -> Result<PTY, PtyError> {
+		let pty = MockPTY::new_with_program(args, program)?;
+		Ok(PTY { backend: PTYBackend::Mock, pty, killed: AtomicBool::new(false), fallbacks: Vec::new() })
 	}
 
 	/// Spawn a process inside the PTY.
 	///
 	/// # Arguments
 	/// * `appname` - Full path to the executable binary to spawn.
-	/// * `cmdline` - Optional space-delimited arguments to provide to the executable.
+	/// * `cmdline` - Optional arguments to provide to the executable, as a single command line.
+	///   Arguments that contain spaces or quotes must be quoted following the rules of the
+	///   Microsoft C runtime, which are also used to split the command line on Unix,
+	///   see [`cmdline`]. [`PtyCommand`] takes care of the quoting.
 	/// * `cwd` - Optional path from where the executable should be spawned.
 	/// * `env` - Optional environment variables to provide to the process. Each
-	/// variable should be declared as `VAR=VALUE` and be separated by a NUL (0) character.
+	///   variable should be declared as `VAR=VALUE` and be separated by a NUL (0) character.
 	///
 	/// # Returns
 	/// `true` if the call was successful, else an error will be returned.
-	pub fn spawn(&mut self, appname: OsString, cmdline: Option<OsString>, cwd: Option<OsString>, env: Option<OsString>) -> Result<bool, OsString> {
+	pub fn spawn(&mut self, appname: OsString, cmdline: Option<OsString>, cwd: Option<OsString>, env: Option<OsString>) -> Result<bool, PtyError> {
 		self.pty.spawn(appname, cmdline, cwd, env)
 	}
 
+	/// Spawn a process inside the PTY, with its arguments given one by one.
+	///
+	/// On Unix the arguments reach the process unchanged, even when they are
+	/// not valid Unicode. Elsewhere they are quoted into a command line, as
+	/// [`PTY::spawn`] expects. The other arguments are the same as in [`PTY::spawn`].
+	pub fn spawn_args(&mut self, appname: OsString, args: Vec<OsString>, cwd: Option<OsString>, env: Option<OsString>) -> Result<bool, PtyError> {
+		self.pty.spawn_args(appname, args, cwd, env)
+	}
+
+	/// Kill the whole process tree when the PTY is dropped: the process and
+	/// every process it spawned. Disabled by default, it must be enabled
+	/// before [`PTY::spawn`].
+	///
+	/// Windows puts the processes in a Job Object that kills them when it is
+	/// closed, which happens when the PTY is dropped or the current process
+	/// exits in any way. Processes spawned before they join the Job Object,
+	/// which the WinPTY and Pipes backends can only do after spawning, escape it.
+	///
+	/// Unix kills the process group the process leads, processes that moved
+	/// to another group (e.g., background jobs of an interactive shell) escape
+	/// it. On Linux the process is also killed when the current process dies
+	/// without dropping the PTY, but as soon as the *thread* that spawned it
+	/// exits as well: spawn it from a thread that lives as long as the PTY.
+	pub fn set_kill_on_drop(&mut self, enabled: bool) -> Result<(), PtyError> {
+		self.pty.set_kill_on_drop(enabled)
+	}
+
 	/// Change the PTY size.
     ///
     /// # Arguments
     /// * `cols` - Number of character columns to display.
     /// * `rows` - Number of line rows to display.
-	pub fn set_size(&self, cols: i32, rows: i32) -> Result<(), OsString> {
+	pub fn set_size(&self, cols: i32, rows: i32) -> Result<(), PtyError> {
 		self.pty.set_size(cols, rows)
 	}
 
@@ -230,23 +317,213 @@ impl PTY {
 		self.backend
 	}
 
+	/// Backends that were tried and could not be used before the one returned
+	/// by [`PTY::get_backend`], in order, with the reason each one failed.
+	///
+	/// Only a PTY created by [`PTY::new`] (or with [`PTYBackend::Auto`]) falls
+	/// back to another backend, the list is empty otherwise.
+	pub fn backend_fallbacks(&self) -> &[(PTYBackend, PtyError)] {
+		&self.fallbacks
+	}
+
 	/// Read at most `length` characters from a process standard output.
     ///
     /// # Arguments
     /// * `length` - Upper limit on the number of characters to read.
-    /// * `blocking` - Block the reading thread if no bytes are available.
+    /// * `blocking` - Block the reading thread until some output is available
+    ///   or the output reaches EOF.
     ///
     /// # Notes
-    /// * If `blocking = false`, then the function will check how much characters are available on
-    /// the stream and will read the minimum between the input argument and the total number of
-    /// characters available.
+    /// * If `blocking = false`, then the function waits a short while for output, and
+    ///   returns an empty string if there is still none.
+    ///
+    /// * Once the process has exited and all its output was read, [`PtyError::Eof`] is
+    ///   returned. Use [`PTY::try_read`] to handle both cases without matching on errors.
     ///
     /// * The bytes returned are represented using a [`OsString`] since Windows operates over
-    /// `u16` strings.
-	pub fn read(&self, length: u32, blocking: bool) -> Result<OsString, OsString> {
-        self.pty.read(length, blocking)
+    ///   `u16` strings.
+	pub fn read(&self, length: u32, blocking: bool) -> Result<OsString, PtyError> {
+        self.try_read(length, blocking)?.into_result()
     }
 
+	/// Read at most `length` characters from a process standard output, telling
+	/// apart the lack of output from its end.
+	///
+	/// The arguments have the same meaning as in [`PTY::read`]. Every backend returns
+	/// all the output of the process before [`ReadOutcome::Eof`], and a blocking read
+	/// never returns [`ReadOutcome::WouldBlock`].
+	///
+	/// # Examples
+	/// ```
+	/// use std::ffi::OsString;
+	/// use winptyrs::{PTY, PTYArgs, MouseMode, AgentConfig, MockScript, ReadOutcome};
+	///
+	/// let pty_args = PTYArgs {
+	///     cols: 80,
+	///     rows: 25,
+	///     mouse_mode: MouseMode::WINPTY_MOUSE_MODE_NONE,
+	///     timeout: 10000,
+	///     agent_config: AgentConfig::WINPTY_FLAG_COLOR_ESCAPES
+	/// };
+	///
+	/// let mut pty = PTY::new_mock(&pty_args, MockScript::new().output("hello\r\n").into()).unwrap();
+	/// pty.spawn(OsString::from("mock"), None, None, None).unwrap();
+	///
+	/// let mut output = OsString::new();
+	/// loop {
+	///     match pty.try_read(1000, true).unwrap() {
+	///         ReadOutcome::Data(text) => output.push(text),
+	///         ReadOutcome::WouldBlock => unreachable!(),
+	///         ReadOutcome::Eof => break,
+	///     }
+	/// }
+	/// assert_eq!(output, "hello\r\n");
+	/// ```
+	pub fn try_read(&self, length: u32, blocking: bool) -> Result<ReadOutcome<OsString>, PtyError> {
+		self.pty.read(length, blocking)
+	}
+
+	/// Read at most `length` characters from a process standard error.
+	///
+	/// Only available for backends that keep the standard error separate from the
+	/// standard output: [`PTYBackend::Pipes`], as well as [`PTYBackend::WinPTY`] and
+	/// [`PTYBackend::Unix`] when [`AgentConfig::WINPTY_FLAG_CONERR`] is set in
+	/// [`PTYArgs::agent_config`]. The others return an error. The arguments and the
+	/// result have the same meaning as in [`PTY::read`].
+	pub fn read_stderr(&self, length: u32, blocking: bool) -> Result<OsString, PtyError> {
+		self.try_read_stderr(length, blocking)?.into_result()
+	}
+
+	/// Read at most `length` characters from a process standard error, as
+	/// [`PTY::try_read`] does for the standard output.
+	pub fn try_read_stderr(&self, length: u32, blocking: bool) -> Result<ReadOutcome<OsString>, PtyError> {
+		self.pty.read_stderr(length, blocking)
+	}
+
+	/// Read raw bytes from a process standard error, without any decoding, as
+	/// [`PTY::read_bytes`] does for the standard output. Only available for
+	/// the same backends as [`PTY::read_stderr`].
+	pub fn read_stderr_bytes(&self, buf: &mut [u8], blocking: bool) -> Result<usize, PtyError> {
+		self.try_read_stderr_bytes(buf, blocking)?.into_result()
+	}
+
+	/// Read raw bytes from a process standard error, telling apart the lack
+	/// of output from its end, see [`PTY::try_read`].
+	pub fn try_read_stderr_bytes(&self, buf: &mut [u8], blocking: bool) -> Result<ReadOutcome<usize>, PtyError> {
+		self.pty.read_stderr_bytes(buf, blocking)
+	}
+
+	/// Read raw bytes from a process standard output, without any decoding.
+	///
+	/// Unlike [`PTY::read`], the output is returned exactly as the process wrote it,
+	/// including NUL characters and invalid UTF-8.
+	///
+	/// # Arguments
+	/// * `buf` - Buffer to fill, at most `buf.len()` bytes are read.
+	/// * `blocking` - Block the reading thread until some output is available
+	///   or the output reaches EOF.
+	///
+	/// # Returns
+	/// The number of bytes placed at the start of `buf`, which is zero if no output
+	/// was available. Once every byte has been read and the process has exited,
+	/// [`PtyError::Eof`] is returned, as [`PTY::read`] does.
+	pub fn read_bytes(&self, buf: &mut [u8], blocking: bool) -> Result<usize, PtyError> {
+		self.try_read_bytes(buf, blocking)?.into_result()
+	}
+
+	/// Read raw bytes from a process standard output, telling apart the lack
+	/// of output from its end, see [`PTY::try_read`].
+	pub fn try_read_bytes(&self, buf: &mut [u8], blocking: bool) -> Result<ReadOutcome<usize>, PtyError> {
+		self.pty.read_bytes(buf, blocking)
+	}
+
+	/// Receive the output of the process as soon as it is read, instead of polling
+	/// [`PTY::read`] or [`PTY::read_bytes`].
+	///
+	/// Every chunk of output arrives as a [`PTYEvent::Output`] and a single
+	/// [`PTYEvent::Exit`] follows the last one once the process has exited.
+	/// The output can be subscribed to only once, and it cannot be read through
+	/// the other reading methods afterwards. The standard error of the
+	/// [`PTYBackend::Pipes`] backend is not included, see [`PTY::read_stderr`].
+	///
+	/// # Examples
+	/// ```
+	/// use std::ffi::OsString;
+	/// use winptyrs::{PTY, PTYArgs, PTYEvent, MouseMode, AgentConfig, MockProgram};
+	///
+	/// let pty_args = PTYArgs {
+	///     cols: 80,
+	///     rows: 25,
+	///     mouse_mode: MouseMode::WINPTY_MOUSE_MODE_NONE,
+	///     timeout: 10000,
+	///     agent_config: AgentConfig::WINPTY_FLAG_COLOR_ESCAPES
+	/// };
+	///
+	/// let mut pty = PTY::new_mock(&pty_args, MockProgram::from_fn(|io| {
+	///     io.write("hello\r\n").unwrap();
+	///     3
+	/// })).unwrap();
+	/// pty.spawn(OsString::from("mock"), None, None, None).unwrap();
+	///
+	/// let mut output = Vec::new();
+	/// for event in pty.subscribe().unwrap() {
+	///     match event {
+	///         PTYEvent::Output(bytes) => output.extend(bytes),
+	///         PTYEvent::Error(err) => panic!("{:?}", err),
+	///         PTYEvent::Exit(status) => assert_eq!(status, Some(3)),
+	///     }
+	/// }
+	/// assert_eq!(output, b"hello\r\n");
+	/// ```
+	pub fn subscribe(&self) -> Result<mpsc::Receiver<PTYEvent>, PtyError> {
+		self.pty.subscribe()
+	}
+
+	/// Change how much output is kept until it is read, [`DEFAULT_OUTPUT_CAPACITY`]
+	/// bytes by default, and what happens once more output does not fit in it.
+	///
+	/// By default the output stops being read until there is room for it, the
+	/// process then blocks on its writes as it would with a pipe. Output forwarded
+	/// to a subscriber is never buffered.
+	///
+	/// # Examples
+	/// ```
+	/// use std::ffi::OsString;
+	/// use winptyrs::{PTY, PTYArgs, PtyError, MouseMode, AgentConfig, MockProgram, OverflowPolicy};
+	///
+	/// let pty_args = PTYArgs {
+	///     cols: 80,
+	///     rows: 25,
+	///     mouse_mode: MouseMode::WINPTY_MOUSE_MODE_NONE,
+	///     timeout: 10000,
+	///     agent_config: AgentConfig::WINPTY_FLAG_COLOR_ESCAPES
+	/// };
+	///
+	/// let mut pty = PTY::new_mock(&pty_args, MockProgram::from_fn(|io| {
+	///     io.write("0123456789").unwrap();
+	///     0
+	/// })).unwrap();
+	/// pty.set_output_buffer(4, OverflowPolicy::DropOldest).unwrap();
+	/// pty.spawn(OsString::from("mock"), None, None, None).unwrap();
+	/// pty.wait_for_exit().unwrap();
+	///
+	/// let mut output = String::new();
+	/// while let Ok(text) = pty.read(1000, true) {
+	///     output.push_str(&text.to_string_lossy());
+	/// }
+	/// assert_eq!(output, "6789");
+	/// assert_eq!(pty.output_stats().unwrap().dropped_bytes, 6);
+	/// ```
+	pub fn set_output_buffer(&self, capacity: usize, policy: OverflowPolicy) -> Result<(), PtyError> {
+		self.pty.set_output_buffer(capacity, policy)
+	}
+
+	/// Retrieve the counters of the output buffer, such as the amount of output
+	/// dropped because it did not fit, see [`PTY::set_output_buffer`].
+	pub fn output_stats(&self) -> Result<OutputStats, PtyError> {
+		self.pty.output_stats()
+	}
+
 	/// Write a (possibly) UTF-16 string into the standard input of a process.
     ///
     /// # Arguments
@@ -254,17 +531,29 @@ impl PTY {
     ///
     /// # Returns
     /// The total number of characters written if the call was successful, else
-    /// an [`OsString`] containing an human-readable error.
-    pub fn write(&self, buf: OsString) -> Result<u32, OsString> {
+    /// a [`PtyError`] describing the failure.
+    pub fn write(&self, buf: OsString) -> Result<u32, PtyError> {
         self.pty.write(buf)
     }
 
+	/// Write raw bytes into the standard input of a process, without any encoding.
+	///
+	/// # Arguments
+	/// * `buf` - Bytes to write.
+	///
+	/// # Returns
+	/// The total number of bytes written if the call was successful, else
+	/// a [`PtyError`] describing the failure.
+	pub fn write_bytes(&self, buf: &[u8]) -> Result<u32, PtyError> {
+		self.pty.write_bytes(buf)
+	}
+
 	/// Check if a process reached End-of-File (EOF).
     ///
     /// # Returns
-    /// `true` if the process reached EOL, false otherwise. If an error occurs, then a [`OsString`]
-    /// containing a human-readable error is raised.
-    pub fn is_eof(&self) -> Result<bool, OsString> {
+    /// `true` if the process reached EOL, false otherwise. If an error occurs, then a [`PtyError`]
+    /// describing it is raised.
+    pub fn is_eof(&self) -> Result<bool, PtyError> {
 		self.pty.is_eof()
     }
 
@@ -272,12 +561,12 @@ impl PTY {
     ///
     /// # Returns
     /// `None` if the process has not exited, else the exit code of the process.
-    pub fn get_exitstatus(&self) -> Result<Option<u32>, OsString> {
+    pub fn get_exitstatus(&self) -> Result<Option<u32>, PtyError> {
         self.pty.get_exitstatus()
     }
 
 	/// Determine if the process is still alive.
-    pub fn is_alive(&self) -> Result<bool, OsString> {
+    pub fn is_alive(&self) -> Result<bool, PtyError> {
         self.pty.is_alive()
     }
 
@@ -292,7 +581,130 @@ impl PTY {
 	}
 
 	/// Wait for the process to exit/finish.
-    pub fn wait_for_exit(&self) -> Result<bool, OsString> {
+    pub fn wait_for_exit(&self) -> Result<bool, PtyError> {
 		self.pty.wait_for_exit()
 	}
+
+	/// Wait at most `timeout` for the process to exit.
+	///
+	/// # Returns
+	/// How the process ended, see [`PTY::exit_status`], or `None` if it is
+	/// still running once `timeout` is over.
+	///
+	/// # Examples
+	/// ```
+	/// use std::ffi::OsString;
+	/// use std::time::Duration;
+	/// use winptyrs::{PTY, PTYArgs, MouseMode, AgentConfig, MockProgram};
+	/// use winptyrs::ExitStatus;
+	///
+	/// let pty_args = PTYArgs {
+	///     cols: 80,
+	///     rows: 25,
+	///     mouse_mode: MouseMode::WINPTY_MOUSE_MODE_NONE,
+	///     timeout: 10000,
+	///     agent_config: AgentConfig::WINPTY_FLAG_COLOR_ESCAPES
+	/// };
+	///
+	/// let mut pty = PTY::new_mock(&pty_args, MockProgram::from_fn(|io| {
+	///     io.expect("exit\r");
+	///     2
+	/// })).unwrap();
+	/// pty.spawn(OsString::from("mock"), None, None, None).unwrap();
+	///
+	/// assert_eq!(pty.wait_timeout(Duration::from_millis(50)).unwrap(), None);
+	/// pty.write(OsString::from("exit\r")).unwrap();
+	/// assert_eq!(pty.wait_timeout(Duration::from_secs(5)).unwrap(), Some(ExitStatus::Exited(2)));
+	/// ```
+	pub fn wait_timeout(&self, timeout: Duration) -> Result<Option<ExitStatus>, PtyError> {
+		if !self.pty.wait_timeout(timeout)? {
+			return Ok(None);
+		}
+		self.exit_status()
+	}
+
+	/// Retrieve how the process ended, unlike [`PTY::get_exitstatus`] it tells
+	/// apart an exit code, a signal, a crash and a process killed through this PTY.
+	///
+	/// # Returns
+	/// `None` if the process has not exited.
+	pub fn exit_status(&self) -> Result<Option<ExitStatus>, PtyError> {
+		let status = self.pty.exit_status()?;
+		if !self.killed.load(Ordering::SeqCst) {
+			return Ok(status);
+		}
+		Ok(status.map(|status| match status {
+			// Otherwise, the process ended by itself before it could be killed.
+			ExitStatus::Signaled(status::SIGKILL) => ExitStatus::Killed,
+			ExitStatus::Exited(status::KILLED_EXIT_CODE) if cfg!(windows) => ExitStatus::Killed,
+			status => status
+		}))
+	}
+
+	/// Interrupt the process, as Ctrl+C (Ctrl+Break for some Windows programs) does.
+	///
+	/// On Unix, `SIGINT` is sent to the foreground process group of the terminal, see
+	/// [`PtySignal::Interrupt`].
+	pub fn send_interrupt(&self) -> Result<(), PtyError> {
+		self.pty.signal(PtySignal::Interrupt)
+	}
+
+	/// Stop the process right away, `SIGKILL` on Unix and `TerminateProcess` on Windows.
+	///
+	/// Killing a process that already exited does nothing. Otherwise, its
+	/// [`PTY::exit_status`] is [`ExitStatus::Killed`].
+	pub fn kill(&self) -> Result<(), PtyError> {
+		let alive = self.is_alive()?;
+		self.pty.signal(PtySignal::Kill)?;
+		if alive {
+			self.killed.store(true, Ordering::SeqCst);
+		}
+		Ok(())
+	}
+
+	/// Ask the process to exit, and kill it if it is still running after `grace`.
+	///
+	/// The request is `SIGTERM` on Unix and Ctrl+C on Windows consoles. Backends that
+	/// cannot ask politely kill the process right away.
+	///
+	/// # Returns
+	/// `true` if the process exited on its own, `false` if it had to be killed.
+	///
+	/// # Examples
+	/// ```
+	/// use std::ffi::OsString;
+	/// use std::time::Duration;
+	/// use winptyrs::{PTY, PTYArgs, MouseMode, AgentConfig, MockProgram};
+	///
+	/// let pty_args = PTYArgs {
+	///     cols: 80,
+	///     rows: 25,
+	///     mouse_mode: MouseMode::WINPTY_MOUSE_MODE_NONE,
+	///     timeout: 10000,
+	///     agent_config: AgentConfig::WINPTY_FLAG_COLOR_ESCAPES
+	/// };
+	///
+	/// // Exit on Ctrl+C, which is how a mock process is asked to terminate.
+	/// let mut pty = PTY::new_mock(&pty_args, MockProgram::from_fn(|io| {
+	///     io.expect("\x03");
+	///     130
+	/// })).unwrap();
+	/// pty.spawn(OsString::from("mock"), None, None, None).unwrap();
+	///
+	/// assert!(pty.terminate(Duration::from_secs(5)).unwrap());
+	/// assert_eq!(pty.get_exitstatus().unwrap(), Some(130));
+	/// ```
+	pub fn terminate(&self, grace: Duration) -> Result<bool, PtyError> {
+		if !self.is_alive()? {
+			return Ok(true);
+		}
+
+		if self.pty.signal(PtySignal::Terminate).is_ok() && self.wait_timeout(grace)?.is_some() {
+			return Ok(true);
+		}
+
+		self.kill()?;
+		self.wait_for_exit()?;
+		Ok(false)
+	}
 }
@@ -0,0 +1,443 @@
+//! Asynchronous halves of a [`PTY`] for the [tokio](https://tokio.rs) runtime,
+//! available with the `tokio` feature.
+//!
+//! On Unix, [`PTY::spawn_async`] registers the master side of the pseudoterminal
+//! with the reactor and waits for the process through `tokio::process`, so a
+//! session does not need any thread of its own. Other backends, and every PTY
+//! passed to [`PTY::split_async`], run their blocking operations on the tokio
+//! blocking thread pool instead.
+
+use std::ffi::OsString;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::task::{self, JoinHandle};
+
+#[cfg(unix)]
+use std::os::fd::{AsRawFd, OwnedFd};
+#[cfg(unix)]
+use std::os::unix::process::ExitStatusExt;
+#[cfg(unix)]
+use std::sync::Arc;
+#[cfg(unix)]
+use tokio::io::unix::AsyncFd;
+#[cfg(unix)]
+use tokio::sync::watch;
+
+use super::{PTY, PTYArgs, PTYBackend, PtyError, PtyReader, PtyWriter, PtyControl};
+#[cfg(unix)]
+use super::PtySignal;
+#[cfg(unix)]
+use super::unix::{open_pty, pty_command, resize_pty, signal_pty};
+#[cfg(unix)]
+use super::cmdline::split_cmdline;
+
+fn join_error(err: task::JoinError) -> io::Error {
+    io::Error::other(err.to_string())
+}
+
+fn join_error_pty(err: task::JoinError) -> PtyError {
+    PtyError::other(err.to_string())
+}
+
+/// Make a file descriptor non-blocking, as required by the reactor.
+#[cfg(unix)]
+fn set_nonblocking(fd: &OwnedFd) -> Result<(), PtyError> {
+    unsafe {
+        let flags = libc::fcntl(fd.as_raw_fd(), libc::F_GETFL);
+        if flags < 0 || libc::fcntl(fd.as_raw_fd(), libc::F_SETFL, flags | libc::O_NONBLOCK) < 0 {
+            return Err(PtyError::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Exit code of a process, following the shell convention for processes
+/// terminated by a signal as [`PTY::get_exitstatus`] does.
+#[cfg(unix)]
+fn exit_code(status: std::process::ExitStatus) -> u32 {
+    match status.code() {
+        Some(code) => code as u32,
+        None => 128 + status.signal().unwrap_or(0) as u32
+    }
+}
+
+/// Blocking half that is moved to the blocking thread pool while it is busy.
+enum Blocking<T, R> {
+    Idle(T),
+    Busy(JoinHandle<(T, io::Result<R>)>),
+    /// Only seen while moving from one state to the other.
+    Moving,
+}
+
+impl<T: Send + 'static, R: Send + 'static> Blocking<T, R> {
+    /// Wait for the pending operation, if any, and return its result.
+    fn poll_idle(&mut self, cx: &mut Context<'_>) -> Poll<Option<io::Result<R>>> {
+        match self {
+            Blocking::Idle(_) => Poll::Ready(None),
+            Blocking::Moving => unreachable!(),
+            Blocking::Busy(handle) => {
+                let (half, result) = ready!(Pin::new(handle).poll(cx)).map_err(join_error)?;
+                *self = Blocking::Idle(half);
+                Poll::Ready(Some(result))
+            }
+        }
+    }
+
+    /// Start an operation on the blocking thread pool, the half must be idle.
+    fn start<F>(&mut self, operation: F)
+    where F: FnOnce(&mut T) -> io::Result<R> + Send + 'static {
+        let Blocking::Idle(mut half) = std::mem::replace(self, Blocking::Moving) else {
+            panic!("A blocking operation is already running");
+        };
+        *self = Blocking::Busy(task::spawn_blocking(move || {
+            let result = operation(&mut half);
+            (half, result)
+        }));
+    }
+}
+
+enum ReaderInner {
+    #[cfg(unix)]
+    Fd(Arc<AsyncFd<OwnedFd>>),
+    Blocking {
+        half: Blocking<PtyReader, Vec<u8>>,
+        /// Output read by the blocking pool that did not fit in the caller buffer.
+        leftover: Vec<u8>,
+    },
+}
+
+enum WriterInner {
+    #[cfg(unix)]
+    Fd(Arc<AsyncFd<OwnedFd>>),
+    Blocking(Blocking<PtyWriter, ()>),
+}
+
+#[derive(Clone)]
+enum ControlInner {
+    #[cfg(unix)]
+    Fd {
+        master: Arc<AsyncFd<OwnedFd>>,
+        pid: u32,
+        /// Exit status of the process, published once it exits.
+        status: watch::Receiver<Option<Result<u32, PtyError>>>,
+    },
+    Blocking(PtyControl),
+}
+
+/// Reading half of an asynchronous PTY, it implements [`AsyncRead`] over
+/// the raw output of the process.
+///
+/// A read returns `Ok(())` without filling the buffer once the process has
+/// exited and all its output was read.
+pub struct AsyncPtyReader {
+    inner: ReaderInner,
+}
+
+/// Writing half of an asynchronous PTY, it implements [`AsyncWrite`] over
+/// the standard input of the process.
+pub struct AsyncPtyWriter {
+    inner: WriterInner,
+}
+
+/// Controlling half of an asynchronous PTY, used to resize it and to wait
+/// for the process.
+#[derive(Clone)]
+pub struct AsyncPtyControl {
+    inner: ControlInner,
+}
+
+impl PTY {
+    /// Spawn a process inside a new pseudoterminal and split it into
+    /// asynchronous halves. Must be called from within a tokio runtime.
+    ///
+    /// On Unix the native pseudoterminal is driven by the reactor, elsewhere
+    /// the PTY is created with [`PTY::new`] and split with [`PTY::split_async`].
+    /// The arguments have the same meaning as in [`PTY::new`] and [`PTY::spawn`].
+    pub fn spawn_async(args: &PTYArgs, appname: OsString, cmdline: Option<OsString>, cwd: Option<OsString>, env: Option<OsString>) -> Result<(AsyncPtyReader, AsyncPtyWriter, AsyncPtyControl), PtyError> {
+        #[cfg(unix)]
+        {
+            let (master, slave) = open_pty(args)?;
+            let command = pty_command(&slave, appname, split_cmdline(cmdline), cwd, env, false)?;
+            // The command keeps copies of the slave side, which must be closed
+            // for the end of the output to be noticed.
+            let mut child = tokio::process::Command::from(command).spawn()
+                .map_err(PtyError::spawn)?;
+            drop(slave);
+            let pid = child.id().unwrap_or(0);
+
+            set_nonblocking(&master)?;
+            // SAFETY: the descriptor is owned by the AsyncFd and stays open as long as it.
+            let master = unsafe { AsyncFd::register(master) }.map_err(io::Error::from)?;
+            let master = Arc::new(master);
+
+            let (status_tx, status_rx) = watch::channel(None);
+            tokio::spawn(async move {
+                let status = child.wait().await
+                    .map(exit_code)
+                    .map_err(PtyError::from);
+                let _ = status_tx.send(Some(status));
+            });
+
+            Ok((
+                AsyncPtyReader { inner: ReaderInner::Fd(master.clone()) },
+                AsyncPtyWriter { inner: WriterInner::Fd(master.clone()) },
+                AsyncPtyControl { inner: ControlInner::Fd { master, pid, status: status_rx } },
+            ))
+        }
+        #[cfg(not(unix))]
+        {
+            let mut pty = PTY::new(args)?;
+            pty.spawn(appname, cmdline, cwd, env)?;
+            Ok(pty.split_async())
+        }
+    }
+
+    /// Split a pseudoterminal, with its process already spawned, into
+    /// asynchronous halves. Works with every backend, the blocking operations
+    /// run on the tokio blocking thread pool.
+    pub fn split_async(self) -> (AsyncPtyReader, AsyncPtyWriter, AsyncPtyControl) {
+        let (reader, writer, control) = self.split();
+        (
+            AsyncPtyReader { inner: ReaderInner::Blocking { half: Blocking::Idle(reader), leftover: Vec::new() } },
+            AsyncPtyWriter { inner: WriterInner::Blocking(Blocking::Idle(writer)) },
+            AsyncPtyControl { inner: ControlInner::Blocking(control) },
+        )
+    }
+}
+
+impl AsyncRead for AsyncPtyReader {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        match &mut self.get_mut().inner {
+            #[cfg(unix)]
+            ReaderInner::Fd(master) => loop {
+                let mut guard = ready!(master.poll_read_ready(cx))?;
+                let unfilled = buf.initialize_unfilled();
+                let result = guard.try_io(|fd| {
+                    let n = unsafe { libc::read(fd.as_raw_fd(), unfilled.as_mut_ptr().cast(), unfilled.len()) };
+                    if n < 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                    Ok(n as usize)
+                });
+                match result {
+                    Ok(Ok(n)) => {
+                        buf.advance(n);
+                        return Poll::Ready(Ok(()));
+                    }
+                    // The master side fails with EIO once the process side was closed.
+                    Ok(Err(err)) if err.raw_os_error() == Some(libc::EIO) => return Poll::Ready(Ok(())),
+                    Ok(Err(err)) if err.kind() == io::ErrorKind::Interrupted => continue,
+                    Ok(Err(err)) => return Poll::Ready(Err(err)),
+                    Err(_would_block) => continue,
+                }
+            },
+            ReaderInner::Blocking { half, leftover } => loop {
+                if !leftover.is_empty() {
+                    let n = buf.remaining().min(leftover.len());
+                    buf.put_slice(&leftover[..n]);
+                    leftover.drain(..n);
+                    return Poll::Ready(Ok(()));
+                }
+                if let Some(result) = ready!(half.poll_idle(cx)) {
+                    let output = result?;
+                    if output.is_empty() {
+                        return Poll::Ready(Ok(()));
+                    }
+                    *leftover = output;
+                    continue;
+                }
+                if buf.remaining() == 0 {
+                    return Poll::Ready(Ok(()));
+                }
+                let length = buf.remaining();
+                half.start(move |reader| {
+                    let mut output = vec![0; length];
+                    let n = io::Read::read(reader, &mut output)?;
+                    output.truncate(n);
+                    Ok(output)
+                });
+            }
+        }
+    }
+}
+
+impl AsyncWrite for AsyncPtyWriter {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, data: &[u8]) -> Poll<io::Result<usize>> {
+        match &mut self.get_mut().inner {
+            #[cfg(unix)]
+            WriterInner::Fd(master) => loop {
+                let mut guard = ready!(master.poll_write_ready(cx))?;
+                let result = guard.try_io(|fd| {
+                    let n = unsafe { libc::write(fd.as_raw_fd(), data.as_ptr().cast(), data.len()) };
+                    if n < 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                    Ok(n as usize)
+                });
+                match result {
+                    Ok(Err(err)) if err.kind() == io::ErrorKind::Interrupted => continue,
+                    Ok(result) => return Poll::Ready(result),
+                    Err(_would_block) => continue,
+                }
+            },
+            WriterInner::Blocking(half) => {
+                // Report the previous write first, the data is then written in the background.
+                if let Some(result) = ready!(half.poll_idle(cx)) {
+                    result?;
+                }
+                let data = data.to_vec();
+                let length = data.len();
+                half.start(move |writer| io::Write::write_all(writer, &data));
+                Poll::Ready(Ok(length))
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match &mut self.get_mut().inner {
+            #[cfg(unix)]
+            WriterInner::Fd(_) => Poll::Ready(Ok(())),
+            WriterInner::Blocking(half) => {
+                match ready!(half.poll_idle(cx)) {
+                    Some(result) => Poll::Ready(result),
+                    None => Poll::Ready(Ok(()))
+                }
+            }
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+impl AsyncPtyControl {
+    /// Change the PTY size, see [`PTY::set_size`].
+    pub async fn set_size(&self, cols: i32, rows: i32) -> Result<(), PtyError> {
+        match &self.inner {
+            #[cfg(unix)]
+            ControlInner::Fd { master, .. } => resize_pty(master.get_ref().as_raw_fd(), cols, rows),
+            ControlInner::Blocking(control) => {
+                let control = control.clone();
+                task::spawn_blocking(move || control.set_size(cols, rows)).await.map_err(join_error_pty)?
+            }
+        }
+    }
+
+    /// Wait for the process to exit.
+    ///
+    /// # Returns
+    /// The exit code of the process.
+    pub async fn wait_for_exit(&self) -> Result<u32, PtyError> {
+        match &self.inner {
+            #[cfg(unix)]
+            ControlInner::Fd { status, .. } => {
+                let mut status = status.clone();
+                let exited = status.wait_for(Option::is_some).await
+                    .map_err(|err| PtyError::other(err.to_string()))?;
+                exited.clone().unwrap()
+            }
+            ControlInner::Blocking(control) => {
+                let control = control.clone();
+                task::spawn_blocking(move || {
+                    control.wait_for_exit()?;
+                    control.get_exitstatus()?
+                        .ok_or_else(|| PtyError::other("The process exit status is not available"))
+                }).await.map_err(join_error_pty)?
+            }
+        }
+    }
+
+    /// Retrieve the exit status of the process, `None` if it has not exited.
+    pub fn get_exitstatus(&self) -> Result<Option<u32>, PtyError> {
+        match &self.inner {
+            #[cfg(unix)]
+            ControlInner::Fd { status, .. } => status.borrow().clone().transpose(),
+            ControlInner::Blocking(control) => control.get_exitstatus()
+        }
+    }
+
+    /// Determine if the process is still alive.
+    pub fn is_alive(&self) -> Result<bool, PtyError> {
+        match &self.inner {
+            #[cfg(unix)]
+            ControlInner::Fd { status, .. } => Ok(status.borrow().is_none()),
+            ControlInner::Blocking(control) => control.is_alive()
+        }
+    }
+
+    /// Retrieve the Process ID associated to the current process.
+    pub fn get_pid(&self) -> u32 {
+        match &self.inner {
+            #[cfg(unix)]
+            ControlInner::Fd { pid, .. } => *pid,
+            ControlInner::Blocking(control) => control.get_pid()
+        }
+    }
+
+    /// Get the backend used by the PTY.
+    pub fn get_backend(&self) -> PTYBackend {
+        match &self.inner {
+            #[cfg(unix)]
+            ControlInner::Fd { .. } => PTYBackend::Unix,
+            ControlInner::Blocking(control) => control.get_backend()
+        }
+    }
+
+    /// Interrupt the process, see [`PTY::send_interrupt`].
+    pub fn send_interrupt(&self) -> Result<(), PtyError> {
+        match &self.inner {
+            #[cfg(unix)]
+            ControlInner::Fd { .. } => self.signal(PtySignal::Interrupt),
+            ControlInner::Blocking(control) => control.send_interrupt()
+        }
+    }
+
+    /// Stop the process right away, see [`PTY::kill`].
+    pub fn kill(&self) -> Result<(), PtyError> {
+        match &self.inner {
+            #[cfg(unix)]
+            ControlInner::Fd { .. } => self.signal(PtySignal::Kill),
+            ControlInner::Blocking(control) => control.kill()
+        }
+    }
+
+    /// Ask the process to exit and kill it after `grace`, see [`PTY::terminate`].
+    pub async fn terminate(&self, grace: Duration) -> Result<bool, PtyError> {
+        match &self.inner {
+            #[cfg(unix)]
+            ControlInner::Fd { .. } => {
+                if !self.is_alive()? {
+                    return Ok(true);
+                }
+                self.signal(PtySignal::Terminate)?;
+                if tokio::time::timeout(grace, self.wait_for_exit()).await.is_ok() {
+                    return Ok(true);
+                }
+                self.kill()?;
+                self.wait_for_exit().await?;
+                Ok(false)
+            }
+            ControlInner::Blocking(control) => {
+                let control = control.clone();
+                task::spawn_blocking(move || control.terminate(grace)).await.map_err(join_error_pty)?
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    fn signal(&self, signal: PtySignal) -> Result<(), PtyError> {
+        let ControlInner::Fd { master, pid, .. } = &self.inner else {
+            unreachable!("only the file descriptor variant is signalled directly");
+        };
+        if !self.is_alive()? {
+            return Ok(());
+        }
+        signal_pty(master.get_ref().as_raw_fd(), *pid, signal)
+    }
+}
@@ -1,57 +1,85 @@
-/// Base struct used to generalize some of the PTY I/O operations.
+//! Base struct used to generalize some of the PTY I/O operations.
 
-use windows::Win32::Foundation::{CloseHandle, HANDLE, STATUS_PENDING, S_OK, WAIT_FAILED, WAIT_OBJECT_0, WAIT_TIMEOUT};
+#[cfg(windows)]
+use windows::Win32::Foundation::{CloseHandle, HANDLE, INVALID_HANDLE_VALUE, STATUS_PENDING, S_OK, WAIT_FAILED, WAIT_OBJECT_0, WAIT_TIMEOUT};
+#[cfg(windows)]
 use windows::Win32::Storage::FileSystem::{GetFileSizeEx, ReadFile, WriteFile};
-use windows::Win32::System::Pipes::PeekNamedPipe;
+#[cfg(windows)]
+use windows::Win32::System::Pipes::{CreatePipe, PeekNamedPipe};
+#[cfg(windows)]
 use windows::Win32::System::IO::CancelIoEx;
-use windows::Win32::System::Threading::{GetExitCodeProcess, GetProcessId, WaitForSingleObject};
-use windows::Win32::Globalization::{MultiByteToWideChar, WideCharToMultiByte, CP_UTF8, MULTI_BYTE_TO_WIDE_CHAR_FLAGS};
-use windows::core::{HRESULT, Error, PCSTR};
+#[cfg(windows)]
+use windows::Win32::System::Threading::{GetExitCodeProcess, GetProcessId, TerminateProcess, WaitForSingleObject};
+#[cfg(windows)]
+use windows::Win32::Globalization::{WideCharToMultiByte, CP_UTF8};
+#[cfg(windows)]
+use windows::Win32::System::JobObjects::{
+    AssignProcessToJobObject, CreateJobObjectW, SetInformationJobObject,
+    JobObjectExtendedLimitInformation, JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+    JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE};
+#[cfg(windows)]
+use windows::core::{HRESULT, Error, PCSTR, PCWSTR};
+#[cfg(windows)]
 use windows::Win32::System::Threading::INFINITE;
 
 use std::ptr;
-use std::sync::mpsc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
 use std::thread;
-use std::time::Duration;
-use std::mem::MaybeUninit;
+use std::time::{Duration, Instant};
 use std::cmp::min;
 use std::ffi::OsString;
+#[cfg(windows)]
+use std::mem::{self, MaybeUninit};
+#[cfg(windows)]
 use core::ffi::c_void;
 
-#[cfg(windows)]
-use std::os::windows::prelude::*;
 #[cfg(windows)]
 use std::os::windows::ffi::OsStrExt;
 #[cfg(unix)]
-use std::vec::IntoIter;
+use std::os::unix::ffi::OsStrExt;
+#[cfg(unix)]
+use std::{io, mem};
+#[cfg(unix)]
+use libc::c_int;
 
-use super::PTYArgs;
+use super::{PTYArgs, PtyError, ExitStatus};
+use super::buffer::{OutputBuffer, OutputStats, OverflowPolicy};
+use super::cmdline::join_args;
+#[cfg(windows)]
+use super::status::KILLED_EXIT_CODE;
 
-#[cfg(unix)]
-trait OsStrExt {
-    fn from_wide(x: &[u16]) -> OsString;
-    fn encode_wide(&self) -> IntoIter<u16>;
 
-}
+#[cfg(windows)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LocalHandle(pub *mut c_void);
 
-#[cfg(unix)]
-impl OsStrExt for OsString {
-    fn from_wide(_: &[u16]) -> OsString {
-        return OsString::new();
+#[cfg(windows)]
+impl LocalHandle {
+    pub const fn null() -> Self {
+        Self(ptr::null_mut())
     }
 
-    fn encode_wide(&self) -> IntoIter<u16> {
-        return Vec::<u16>::new().into_iter();
+    pub fn is_invalid(&self) -> bool {
+        self.0 == -1 as _ || self.0 == 0 as _
     }
 }
 
-
+/// On Unix a handle is either a file descriptor or a process ID.
+///
+/// Zero is a valid file descriptor (standard input), so the null handle is -1.
+#[cfg(unix)]
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub struct LocalHandle(pub *mut c_void);
+pub struct LocalHandle(pub c_int);
 
+#[cfg(unix)]
 impl LocalHandle {
+    pub const fn null() -> Self {
+        Self(-1)
+    }
+
     pub fn is_invalid(&self) -> bool {
-        self.0 == -1 as _ || self.0 == 0 as _
+        self.0 < 0
     }
 }
 
@@ -59,18 +87,27 @@ unsafe impl Send for LocalHandle {}
 unsafe impl Sync for LocalHandle {}
 
 
+#[cfg(windows)]
 impl From<HANDLE> for LocalHandle {
     fn from(value: HANDLE) -> Self {
         Self(value.0)
     }
 }
 
+#[cfg(windows)]
 impl From<LocalHandle> for HANDLE {
     fn from(value: LocalHandle) -> Self {
         Self(value.0)
     }
 }
 
+#[cfg(unix)]
+impl From<c_int> for LocalHandle {
+    fn from(value: c_int) -> Self {
+        Self(value)
+    }
+}
+
 
 /// This trait should be implemented by any backend that wants to provide a PTY implementation.
 pub trait PTYImpl: Sync + Send {
@@ -82,7 +119,7 @@ pub trait PTYImpl: Sync + Send {
     /// # Returns
     /// * `pty`: The instantiated PTY struct.
     #[allow(clippy::new_ret_no_self)]
-    fn new(args: &PTYArgs) -> Result<Box<dyn PTYImpl>, OsString>
+    fn new(args: &PTYArgs) -> Result<Box<dyn PTYImpl>, PtyError>
         where Self: Sized;
 
     /// Spawn a process inside the PTY.
@@ -92,18 +129,40 @@ pub trait PTYImpl: Sync + Send {
 	/// * `cmdline` - Optional space-delimited arguments to provide to the executable.
 	/// * `cwd` - Optional path from where the executable should be spawned.
 	/// * `env` - Optional environment variables to provide to the process. Each
-	/// variable should be declared as `VAR=VALUE` and be separated by a NUL (0) character.
+	///   variable should be declared as `VAR=VALUE` and be separated by a NUL (0) character.
 	///
 	/// # Returns
 	/// `true` if the call was successful, else an error will be returned.
-    fn spawn(&mut self, appname: OsString, cmdline: Option<OsString>, cwd: Option<OsString>, env: Option<OsString>) -> Result<bool, OsString>;
+    fn spawn(&mut self, appname: OsString, cmdline: Option<OsString>, cwd: Option<OsString>, env: Option<OsString>) -> Result<bool, PtyError>;
+
+    /// Spawn a process inside the PTY, with its arguments given one by one.
+    ///
+    /// Backends that start the process with a single command line quote the
+    /// arguments following the rules of the Microsoft C runtime and call
+    /// [`PTYImpl::spawn`], which is what this default does. The Unix ones
+    /// pass the arguments to the process unchanged.
+    fn spawn_args(&mut self, appname: OsString, args: Vec<OsString>, cwd: Option<OsString>, env: Option<OsString>) -> Result<bool, PtyError> {
+        let args: Vec<_> = args.iter().map(|arg| arg.to_string_lossy()).collect();
+        let cmdline = Some(join_args(&args)).filter(|cmdline| !cmdline.is_empty()).map(OsString::from);
+        self.spawn(appname, cmdline, cwd, env)
+    }
+
+    /// Kill every process spawned by the process when the PTY is dropped,
+    /// instead of only the process itself. Must be called before spawning.
+    fn set_kill_on_drop(&mut self, enabled: bool) -> Result<(), PtyError>;
+
+    /// Change the capacity and the overflow policy of the output buffer.
+    fn set_output_buffer(&self, capacity: usize, policy: OverflowPolicy) -> Result<(), PtyError>;
+
+    /// Retrieve the counters of the output buffer.
+    fn output_stats(&self) -> Result<OutputStats, PtyError>;
 
     /// Change the PTY size.
     ///
     /// # Arguments
     /// * `cols` - Number of character columns to display.
     /// * `rows` - Number of line rows to display.
-    fn set_size(&self, cols: i32, rows: i32) -> Result<(), OsString>;
+    fn set_size(&self, cols: i32, rows: i32) -> Result<(), PtyError>;
 
     /// Read at most `length` characters from a process standard output.
     ///
@@ -113,12 +172,45 @@ pub trait PTYImpl: Sync + Send {
     ///
     /// # Notes
     /// * If `blocking = false`, then the function will check how much characters are available on
-    /// the stream and will read the minimum between the input argument and the total number of
-    /// characters available.
+    ///   the stream and will read the minimum between the input argument and the total number of
+    ///   characters available.
     ///
     /// * The bytes returned are represented using a [`OsString`] since Windows operates over
-    /// `u16` strings.
-    fn read(&self, length: u32, blocking: bool) -> Result<OsString, OsString>;
+    ///   `u16` strings.
+    ///
+    /// # Returns
+    /// See [`ReadOutcome`], a blocking read never returns [`ReadOutcome::WouldBlock`].
+    fn read(&self, length: u32, blocking: bool) -> Result<ReadOutcome<OsString>, PtyError>;
+
+    /// Read at most `length` characters from a process standard error, for
+    /// backends that keep it separate from the standard output.
+    ///
+    /// The arguments have the same meaning as in [`PTYImpl::read`]. Backends
+    /// that cannot provide a separate standard error return an error.
+    fn read_stderr(&self, _length: u32, _blocking: bool) -> Result<ReadOutcome<OsString>, PtyError> {
+        Err(PtyError::other("This PTY backend does not provide a separate standard error"))
+    }
+
+    /// Read raw bytes from a process standard error, without any decoding.
+    ///
+    /// The arguments have the same meaning as in [`PTYImpl::read_bytes`], the
+    /// backends are the same as for [`PTYImpl::read_stderr`].
+    fn read_stderr_bytes(&self, _buf: &mut [u8], _blocking: bool) -> Result<ReadOutcome<usize>, PtyError> {
+        Err(PtyError::other("This PTY backend does not provide a separate standard error"))
+    }
+
+    /// Read raw bytes from a process standard output, without any decoding.
+    ///
+    /// # Arguments
+    /// * `buf` - Buffer to fill, at most `buf.len()` bytes are read.
+    /// * `blocking` - Block the reading thread if no bytes are available.
+    ///
+    /// # Returns
+    /// The number of bytes placed at the start of `buf`, see [`ReadOutcome`].
+    fn read_bytes(&self, buf: &mut [u8], blocking: bool) -> Result<ReadOutcome<usize>, PtyError>;
+
+    /// Receive the output of the process as soon as it is read, see [`PTYProcess::subscribe`].
+    fn subscribe(&self) -> Result<mpsc::Receiver<PTYEvent>, PtyError>;
 
     /// Write a (possibly) UTF-16 string into the standard input of a process.
     ///
@@ -127,24 +219,34 @@ pub trait PTYImpl: Sync + Send {
     ///
     /// # Returns
     /// The total number of characters written if the call was successful, else
-    /// an [`OsString`] containing an human-readable error.
-    fn write(&self, buf: OsString) -> Result<u32, OsString>;
+    /// a [`PtyError`] describing the failure.
+    fn write(&self, buf: OsString) -> Result<u32, PtyError>;
+
+    /// Write raw bytes into the standard input of a process, without any encoding.
+    ///
+    /// # Arguments
+    /// * `buf` - Bytes to write.
+    ///
+    /// # Returns
+    /// The total number of bytes written if the call was successful, else
+    /// a [`PtyError`] describing the failure.
+    fn write_bytes(&self, buf: &[u8]) -> Result<u32, PtyError>;
 
     /// Check if a process reached End-of-File (EOF).
     ///
     /// # Returns
-    /// `true` if the process reached EOL, false otherwise. If an error occurs, then a [`OsString`]
-    /// containing a human-readable error is raised.
-    fn is_eof(&self) -> Result<bool, OsString>;
+    /// `true` if the process reached EOL, false otherwise. If an error occurs, then a [`PtyError`]
+    /// describing it is raised.
+    fn is_eof(&self) -> Result<bool, PtyError>;
 
     /// Retrieve the exit status of the process
     ///
     /// # Returns
     /// `None` if the process has not exited, else the exit code of the process.
-    fn get_exitstatus(&self) -> Result<Option<u32>, OsString>;
+    fn get_exitstatus(&self) -> Result<Option<u32>, PtyError>;
 
     /// Determine if the process is still alive.
-    fn is_alive(&self) -> Result<bool, OsString>;
+    fn is_alive(&self) -> Result<bool, PtyError>;
 
     /// Retrieve the Process ID associated to the current process.
     fn get_pid(&self) -> u32;
@@ -153,11 +255,26 @@ pub trait PTYImpl: Sync + Send {
 	fn get_fd(&self) -> isize;
 
     /// Wait for the process to exit/finish.
-    fn wait_for_exit(&self) -> Result<bool, OsString>;
+    fn wait_for_exit(&self) -> Result<bool, PtyError>;
+
+    /// Wait at most `timeout` for the process to exit.
+    ///
+    /// # Returns
+    /// `true` if the process has exited, `false` if it is still running.
+    fn wait_timeout(&self, timeout: Duration) -> Result<bool, PtyError>;
+
+    /// Retrieve how the process ended, `None` if it has not exited.
+    fn exit_status(&self) -> Result<Option<ExitStatus>, PtyError>;
+
+    /// Send a signal to the process, see [`PtySignal`] for what each one does
+    /// on every platform.
+    fn signal(&self, signal: PtySignal) -> Result<(), PtyError>;
 }
 
 
-fn read(mut length: u32, blocking: bool, stream: HANDLE, using_pipes: bool) -> Result<OsString, OsString> {
+#[cfg(windows)]
+fn read(mut length: u32, blocking: bool, stream: LocalHandle, using_pipes: bool) -> Result<Vec<u8>, PtyError> {
+    let stream: HANDLE = stream.into();
     let mut result: HRESULT;
     if !blocking {
         if using_pipes {
@@ -180,9 +297,7 @@ fn read(mut length: u32, blocking: bool, stream: HANDLE, using_pipes: bool) -> R
 
 
                 if result.is_err() {
-                    let result_msg = result.message();
-                    let string = OsString::from(result_msg);
-                    return Err(string);
+                    return Err(result.into());
                 }
                 let num_bytes = bytes_u.assume_init();
                 length = min(length, num_bytes);
@@ -199,9 +314,7 @@ fn read(mut length: u32, blocking: bool, stream: HANDLE, using_pipes: bool) -> R
                 result = if GetFileSizeEx(stream, size_ref).is_ok() { S_OK } else { Error::from_win32().into() };
 
                 if result.is_err() {
-                    let result_msg = result.message();
-                    let string = OsString::from(result_msg);
-                    return Err(string);
+                    return Err(result.into());
                 }
                 length = min(length, *size_ptr as u32);
                 size.assume_init();
@@ -209,69 +322,33 @@ fn read(mut length: u32, blocking: bool, stream: HANDLE, using_pipes: bool) -> R
         }
     }
 
-    //let mut buf: Vec<u16> = Vec::with_capacity((length + 1) as usize);
-    //buf.fill(1);
-    let os_str = "\0".repeat((length + 1) as usize);
-    let mut buf_vec: Vec<u8> = os_str.as_str().as_bytes().to_vec();
-    let mut chars_read = MaybeUninit::<u32>::uninit();
-    //let chars_read: *mut u32 = ptr::null_mut();
-    // println!("Length: {}, {}", length, length > 0);
-    // if length > 0 {
-        unsafe {
-            match length {
-                0 => {
-
-                }
-                _ => {
-                    // let chars_read_ptr = chars_read.as_mut_ptr();
-                    let chars_read_ptr = ptr::addr_of_mut!(*chars_read.as_mut_ptr());
-                    // let chars_read_mut = chars_read_ptr.as_mut();
-                    let chars_read_mut = Some(chars_read_ptr);
-                    // println!("Blocked here");
-                    result =
-                        if ReadFile(stream, Some(&mut buf_vec[..]),
-                                    chars_read_mut, None).is_ok() {
-                            S_OK
-                        } else {
-                            Error::from_win32().into()
-                        };
-                    // println!("Unblocked here");
-
-                    if result.is_err() {
-                        let result_msg = result.message();
-                        let string = OsString::from(result_msg);
-                        return Err(string);
-                    }
-                }
-            }
-        }
-    // }
-
-    // let os_str = OsString::with_capacity(buf_vec.len());
-    let mut vec_buf: Vec<u16> = std::iter::repeat(0).take(buf_vec.len()).collect();
+    if length == 0 {
+        return Ok(Vec::new());
+    }
 
+    let mut buf: Vec<u8> = vec![0; length as usize];
+    let mut bytes_read = MaybeUninit::<u32>::uninit();
     unsafe {
-        MultiByteToWideChar(
-            CP_UTF8, MULTI_BYTE_TO_WIDE_CHAR_FLAGS(0), &buf_vec[..],
-            Some(&mut vec_buf[..]));
-    }
-
-    // let non_zeros: Vec<u16> = vec_buf.split(|elem| *elem == 0 as u16).collect();
-    let non_zeros_init = Vec::new();
-    let non_zeros: Vec<u16> =
-        vec_buf
-        .split(|x| x == &0)
-        .map(|x| x.to_vec())
-        .fold(non_zeros_init, |mut acc, mut x| {
-            acc.append(&mut x);
-            acc
-        });
-    // let non_zeros: &[u16] = non_zeros_slices.into_iter().reduce(|acc, item| [acc, item].concat()).unwrap();
-    let os_str = OsString::from_wide(&non_zeros[..]);
-    Ok(os_str)
+        let bytes_read_ptr = ptr::addr_of_mut!(*bytes_read.as_mut_ptr());
+        result =
+            if ReadFile(stream, Some(&mut buf[..]),
+                        Some(bytes_read_ptr), None).is_ok() {
+                S_OK
+            } else {
+                Error::from_win32().into()
+            };
+
+        if result.is_err() {
+            return Err(result.into());
+        }
+        buf.truncate(bytes_read.assume_init() as usize);
+    }
+    Ok(buf)
 }
 
-fn is_alive(process: HANDLE) -> Result<bool, OsString> {
+#[cfg(windows)]
+fn is_alive(process: LocalHandle) -> Result<bool, PtyError> {
+    let process: HANDLE = process.into();
     unsafe {
         let is_timeout = WaitForSingleObject(process, 0);
         let succ = is_timeout != WAIT_FAILED;
@@ -281,31 +358,48 @@ fn is_alive(process: HANDLE) -> Result<bool, OsString> {
             Ok(alive)
         } else {
             let err: HRESULT = Error::from_win32().into();
-            let result_msg = err.message();
-            let string = OsString::from(result_msg);
-            Err(string)
+            Err(err.into())
         }
     }
 }
 
-fn wait_for_exit(process: HANDLE) -> Result<bool, OsString> {
+#[cfg(windows)]
+fn wait_for_exit(process: LocalHandle) -> Result<bool, PtyError> {
+    wait_process(process, INFINITE)
+}
+
+#[cfg(windows)]
+fn wait_timeout(process: LocalHandle, timeout: Duration) -> Result<bool, PtyError> {
+    // Stay below INFINITE, which would never return.
+    let millis = min(timeout.as_millis(), (INFINITE - 1) as u128) as u32;
+    wait_process(process, millis)
+}
+
+#[cfg(windows)]
+fn wait_process(process: LocalHandle, millis: u32) -> Result<bool, PtyError> {
+    let process: HANDLE = process.into();
     unsafe {
-        let wait_status = WaitForSingleObject(process, INFINITE);
+        let wait_status = WaitForSingleObject(process, millis);
         let succ = wait_status != WAIT_FAILED;
         if succ {
             let dead = wait_status == WAIT_OBJECT_0;
             Ok(dead)
         } else {
             let err: HRESULT = Error::from_win32().into();
-            let result_msg = err.message();
-            let string = OsString::from(result_msg);
-            Err(string)
+            Err(err.into())
         }
     }
 }
 
 
-fn get_exitstatus(process: HANDLE) -> Result<Option<u32>, OsString> {
+#[cfg(windows)]
+fn exit_status(process: LocalHandle) -> Result<Option<ExitStatus>, PtyError> {
+    get_exitstatus(process).map(|exitstatus| exitstatus.map(ExitStatus::from_code))
+}
+
+#[cfg(windows)]
+fn get_exitstatus(process: LocalHandle) -> Result<Option<u32>, PtyError> {
+    let process: HANDLE = process.into();
     let mut exit = MaybeUninit::<u32>::uninit();
     unsafe {
         let exit_ptr: *mut u32 = ptr::addr_of_mut!(*exit.as_mut_ptr());
@@ -323,14 +417,14 @@ fn get_exitstatus(process: HANDLE) -> Result<Option<u32>, OsString> {
             Ok(exitstatus)
         } else {
             let err: HRESULT = Error::from_win32().into();
-            let result_msg = err.message();
-            let string = OsString::from(result_msg);
-            Err(string)
+            Err(err.into())
         }
     }
 }
 
-fn is_eof(process: HANDLE, stream: HANDLE) -> Result<bool, OsString> {
+#[cfg(windows)]
+fn is_eof(process: &dyn PTYChild, stream: LocalHandle) -> Result<bool, PtyError> {
+    let stream: HANDLE = stream.into();
     let mut bytes = MaybeUninit::<u32>::uninit();
     unsafe {
         let bytes_ptr: *mut u32 = ptr::addr_of_mut!(*bytes.as_mut_ptr());
@@ -340,7 +434,7 @@ fn is_eof(process: HANDLE, stream: HANDLE) -> Result<bool, OsString> {
 
         let total_bytes = bytes.assume_init();
         if succ {
-            match is_alive(process) {
+            match process.is_alive() {
                 Ok(alive) => {
                     let eof = !alive && total_bytes == 0;
                     Ok(eof)
@@ -353,33 +447,597 @@ fn is_eof(process: HANDLE, stream: HANDLE) -> Result<bool, OsString> {
     }
 }
 
+#[cfg(windows)]
+fn signal_process(process: LocalHandle, signal: PtySignal) -> Result<(), PtyError> {
+    match signal {
+        PtySignal::Kill => unsafe {
+            TerminateProcess(Into::<HANDLE>::into(process), KILLED_EXIT_CODE).map_err(PtyError::from)
+        },
+        // Console control events can only be generated inside a console.
+        _ => Err(PtyError::other("A process without a console can only be killed on Windows"))
+    }
+}
+
+#[cfg(windows)]
+fn get_process_id(process: LocalHandle) -> u32 {
+    unsafe { GetProcessId(Into::<HANDLE>::into(process)) }
+}
+
+#[cfg(windows)]
+fn cancel_io(stream: LocalHandle) {
+    unsafe {
+        let _ = CancelIoEx(Into::<HANDLE>::into(stream), None);
+    }
+}
+
+#[cfg(windows)]
+fn close_handle(handle: LocalHandle) {
+    unsafe {
+        let _ = CloseHandle(Into::<HANDLE>::into(handle));
+    }
+}
+
+#[cfg(windows)]
+fn close_process(process: LocalHandle) {
+    close_handle(process)
+}
+
+#[cfg(unix)]
+fn available_bytes(stream: LocalHandle) -> Result<u32, PtyError> {
+    let mut available: c_int = 0;
+    if unsafe { libc::ioctl(stream.0, libc::FIONREAD, &mut available) } < 0 {
+        return Err(PtyError::last_os_error());
+    }
+    Ok(available as u32)
+}
+
+/// Check if some output is waiting to be read. Unlike FIONREAD, polling a
+/// pseudoterminal first flushes what was written to the slave side, so the
+/// output of a process that just exited is never missed.
+#[cfg(unix)]
+fn output_pending(stream: LocalHandle) -> Result<bool, PtyError> {
+    let mut poll_fd = libc::pollfd { fd: stream.0, events: libc::POLLIN, revents: 0 };
+    loop {
+        if unsafe { libc::poll(&mut poll_fd, 1, 0) } < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(err.into());
+        }
+        return Ok(poll_fd.revents & libc::POLLIN != 0);
+    }
+}
+
+#[cfg(unix)]
+fn read(mut length: u32, blocking: bool, stream: LocalHandle, _using_pipes: bool) -> Result<Vec<u8>, PtyError> {
+    if blocking {
+        // Wait in bounded slices, so that the reading thread is able to notice
+        // that it was asked to stop even if the process never writes again.
+        let mut poll_fd = libc::pollfd { fd: stream.0, events: libc::POLLIN, revents: 0 };
+        let ready = unsafe { libc::poll(&mut poll_fd, 1, 100) };
+        if ready < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                return Ok(Vec::new());
+            }
+            return Err(err.into());
+        }
+        if ready == 0 {
+            return Ok(Vec::new());
+        }
+    } else {
+        length = min(length, available_bytes(stream)?);
+    }
+
+    if length == 0 {
+        return Ok(Vec::new());
+    }
+
+    let mut buf: Vec<u8> = vec![0; length as usize];
+    let bytes_read = unsafe { libc::read(stream.0, buf.as_mut_ptr().cast(), buf.len()) };
+    if bytes_read < 0 {
+        let err = io::Error::last_os_error();
+        // Reading the master side fails with EIO once every process attached
+        // to the slave side has exited, which is not an error for us.
+        if err.raw_os_error() == Some(libc::EIO) || err.kind() == io::ErrorKind::Interrupted {
+            return Ok(Vec::new());
+        }
+        return Err(err.into());
+    }
+
+    buf.truncate(bytes_read as usize);
+    Ok(buf)
+}
+
+#[cfg(unix)]
+fn write(stream: LocalHandle, buf: &[u8]) -> Result<u32, PtyError> {
+    let mut written = 0;
+    while written < buf.len() {
+        let remaining = &buf[written..];
+        let result = unsafe { libc::write(stream.0, remaining.as_ptr().cast(), remaining.len()) };
+        if result < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(err.into());
+        }
+        written += result as usize;
+    }
+    Ok(written as u32)
+}
+
+/// Query the state of a child process without reaping it, so that its exit
+/// status stays available until the [`PTYProcess`] is dropped.
+#[cfg(unix)]
+fn wait_process(process: LocalHandle, options: c_int) -> Result<Option<libc::siginfo_t>, PtyError> {
+    loop {
+        let mut info: libc::siginfo_t = unsafe { mem::zeroed() };
+        let result = unsafe {
+            libc::waitid(libc::P_PID, process.0 as libc::id_t, &mut info,
+                         libc::WEXITED | libc::WNOWAIT | options)
+        };
+        if result < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(err.into());
+        }
+
+        // With WNOHANG, waitid leaves si_pid zeroed if the process is still running.
+        if unsafe { info.si_pid() } == 0 {
+            return Ok(None);
+        }
+        return Ok(Some(info));
+    }
+}
+
+#[cfg(unix)]
+fn is_alive(process: LocalHandle) -> Result<bool, PtyError> {
+    wait_process(process, libc::WNOHANG).map(|info| info.is_none())
+}
+
+#[cfg(unix)]
+fn wait_for_exit(process: LocalHandle) -> Result<bool, PtyError> {
+    wait_process(process, 0).map(|info| info.is_some())
+}
+
+#[cfg(unix)]
+fn wait_timeout(process: LocalHandle, timeout: Duration) -> Result<bool, PtyError> {
+    poll_exit(|| is_alive(process), timeout)
+}
+
+#[cfg(unix)]
+fn exit_status(process: LocalHandle) -> Result<Option<ExitStatus>, PtyError> {
+    let status = wait_process(process, libc::WNOHANG)?.map(|info| {
+        let status = unsafe { info.si_status() };
+        match info.si_code {
+            libc::CLD_EXITED => ExitStatus::Exited(status as u32),
+            _ => ExitStatus::Signaled(status)
+        }
+    });
+    Ok(status)
+}
+
+#[cfg(unix)]
+fn get_exitstatus(process: LocalHandle) -> Result<Option<u32>, PtyError> {
+    // Follow the shell convention for processes terminated by a signal.
+    exit_status(process).map(|status| status.map(|status| status.exit_code()))
+}
+
+#[cfg(unix)]
+fn signal_process(process: LocalHandle, signal: PtySignal) -> Result<(), PtyError> {
+    // The process is not reaped before the PTYProcess is dropped, so its PID cannot be reused.
+    if unsafe { libc::kill(process.0, signal.number()) } < 0 {
+        let err = io::Error::last_os_error();
+        if err.raw_os_error() != Some(libc::ESRCH) {
+            return Err(err.into());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn is_eof(process: &dyn PTYChild, stream: LocalHandle) -> Result<bool, PtyError> {
+    match (process.is_alive(), output_pending(stream)) {
+        (Ok(alive), Ok(pending)) => Ok(!alive && !pending),
+        _ => Ok(true)
+    }
+}
+
+#[cfg(unix)]
+fn get_process_id(process: LocalHandle) -> u32 {
+    process.0 as u32
+}
+
+#[cfg(unix)]
+fn cancel_io(_stream: LocalHandle) {
+    // Nothing to cancel, blocking reads on Unix give up on their own after a while.
+}
+
+#[cfg(unix)]
+fn close_handle(handle: LocalHandle) {
+    unsafe {
+        libc::close(handle.0);
+    }
+}
+
+#[cfg(unix)]
+fn close_process(process: LocalHandle) {
+    // Reap the process in the background, it may take a while for it to
+    // react to the hangup of its controlling terminal.
+    thread::spawn(move || unsafe {
+        libc::waitpid(process.0, ptr::null_mut(), 0);
+    });
+}
+
+/// Create an anonymous pipe, returning its read and write sides.
+#[cfg(windows)]
+pub(crate) fn anonymous_pipe() -> Result<(LocalHandle, LocalHandle), PtyError> {
+    let mut read_side = INVALID_HANDLE_VALUE;
+    let mut write_side = INVALID_HANDLE_VALUE;
+    unsafe {
+        if CreatePipe(&mut read_side, &mut write_side, None, 0).is_err() {
+            let result: HRESULT = Error::from_win32().into();
+            return Err(PtyError::from(result));
+        }
+    }
+    Ok((read_side.into(), write_side.into()))
+}
+
+/// Create an anonymous pipe, returning its read and write sides.
+#[cfg(unix)]
+pub(crate) fn anonymous_pipe() -> Result<(LocalHandle, LocalHandle), PtyError> {
+    use std::os::fd::{IntoRawFd, OwnedFd};
+
+    let (reader, writer) = io::pipe()?;
+    let reader: OwnedFd = reader.into();
+    let writer: OwnedFd = writer.into();
+    Ok((reader.into_raw_fd().into(), writer.into_raw_fd().into()))
+}
+
+/// Parse a NUL-separated `VAR=VALUE` environment block.
+pub(crate) fn parse_env_block(env: &OsString) -> Vec<(String, String)> {
+    env.to_string_lossy()
+        .split('\0')
+        .filter_map(|var| var.split_once('='))
+        .filter(|(name, _)| !name.is_empty())
+        .map(|(name, value)| (name.to_owned(), value.to_owned()))
+        .collect()
+}
+
+/// Unblock every signal, called between fork and exec so that the process
+/// can be interrupted and terminated even if the caller blocks some signals.
+#[cfg(unix)]
+pub(crate) fn reset_signal_mask() -> io::Result<()> {
+    unsafe {
+        let mut set: libc::sigset_t = std::mem::zeroed();
+        libc::sigemptyset(&mut set);
+        match libc::pthread_sigmask(libc::SIG_SETMASK, &set, std::ptr::null_mut()) {
+            0 => Ok(()),
+            err => Err(io::Error::from_raw_os_error(err))
+        }
+    }
+}
+
+/// Ask the kernel to kill the process when the thread that spawned it exits,
+/// called between fork and exec. `parent` is the process ID of the caller,
+/// if it exited before this call the process is killed right away.
+///
+/// Only Linux supports this, it does nothing on other Unix systems.
+#[cfg(unix)]
+pub(crate) fn set_parent_death_signal(parent: libc::pid_t) -> io::Result<()> {
+    #[cfg(target_os = "linux")]
+    unsafe {
+        if libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if libc::getppid() != parent {
+            libc::_exit(128 + libc::SIGKILL);
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = parent;
+    Ok(())
+}
+
+/// Every process spawned by a process inside a PTY, all of them are killed
+/// when the tree is dropped.
+///
+/// On Windows this is a Job Object that kills its processes when its last
+/// handle is closed, on Unix it is the process group of the process.
+pub(crate) struct ProcessTree {
+    #[cfg(windows)]
+    job: HANDLE,
+    #[cfg(unix)]
+    group: libc::pid_t,
+}
+
+#[cfg(windows)]
+impl ProcessTree {
+    /// Create an empty Job Object, see [`ProcessTree::add`].
+    pub(crate) fn new() -> Result<ProcessTree, PtyError> {
+        unsafe {
+            let job = CreateJobObjectW(None, PCWSTR::null())?;
+            let mut info = JOBOBJECT_EXTENDED_LIMIT_INFORMATION::default();
+            info.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+            let tree = ProcessTree { job };
+            SetInformationJobObject(
+                job, JobObjectExtendedLimitInformation,
+                &info as *const _ as *const c_void,
+                mem::size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as u32)?;
+            Ok(tree)
+        }
+    }
+
+    /// Put a process in the tree, every process it spawns afterwards
+    /// belongs to the tree as well.
+    pub(crate) fn add(&self, process: LocalHandle) -> Result<(), PtyError> {
+        unsafe {
+            AssignProcessToJobObject(self.job, process.into())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+impl ProcessTree {
+    /// Track the process group `group`, the process that leads it must have
+    /// been spawned in a new group or session.
+    pub(crate) fn new(group: LocalHandle) -> ProcessTree {
+        ProcessTree { group: group.0 }
+    }
+}
+
+impl Drop for ProcessTree {
+    fn drop(&mut self) {
+        #[cfg(windows)]
+        unsafe {
+            let _ = CloseHandle(self.job);
+        }
+        // The group may be gone already, there is nothing to do then.
+        #[cfg(unix)]
+        unsafe {
+            libc::kill(-self.group, libc::SIGKILL);
+        }
+    }
+}
+
+unsafe impl Send for ProcessTree {}
+unsafe impl Sync for ProcessTree {}
+
+/// Check every few milliseconds whether a process is still alive, until it
+/// exits or `timeout` is over.
+pub(crate) fn poll_exit<F>(is_alive: F, timeout: Duration) -> Result<bool, PtyError>
+where
+    F: Fn() -> Result<bool, PtyError>
+{
+    let deadline = Instant::now() + timeout;
+    loop {
+        if !is_alive()? {
+            return Ok(true);
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return Ok(false);
+        }
+        thread::sleep(min(deadline - now, Duration::from_millis(10)));
+    }
+}
+
+/// Process running inside a PTY, as seen by a [`PTYProcess`].
+///
+/// Backends that spawn an operating system process can simply call
+/// [`PTYProcess::set_process`], this trait allows backends to provide
+/// something else, e.g., a simulated process.
+pub trait PTYChild: Sync + Send {
+    /// Determine if the process is still alive.
+    fn is_alive(&self) -> Result<bool, PtyError>;
+
+    /// Retrieve the exit status of the process, `None` if it has not exited.
+    fn get_exitstatus(&self) -> Result<Option<u32>, PtyError>;
+
+    /// Wait for the process to exit/finish.
+    fn wait_for_exit(&self) -> Result<bool, PtyError>;
+
+    /// Wait at most `timeout` for the process to exit, `true` if it has exited.
+    fn wait_timeout(&self, timeout: Duration) -> Result<bool, PtyError> {
+        poll_exit(|| self.is_alive(), timeout)
+    }
+
+    /// Retrieve how the process ended, `None` if it has not exited. By
+    /// default, it is told from the exit code.
+    fn exit_status(&self) -> Result<Option<ExitStatus>, PtyError> {
+        self.get_exitstatus().map(|exitstatus| exitstatus.map(ExitStatus::from_code))
+    }
+
+    /// Retrieve the process ID (PID) of the process.
+    fn get_pid(&self) -> u32;
+
+    /// Retrieve the process handle ID of the process.
+    fn get_fd(&self) -> isize;
+
+    /// Send a signal to the process, a process that already exited is left alone.
+    fn signal(&self, _signal: PtySignal) -> Result<(), PtyError> {
+        Err(PtyError::other("This process cannot be signalled"))
+    }
+}
+
+/// Operating system process, identified by its handle (PID on Unix).
+pub(crate) struct OSProcess {
+    /// Handle to the process.
+    handle: LocalHandle,
+    /// Close the process handle when the struct is dropped.
+    close_process: bool,
+}
+
+impl OSProcess {
+    /// Track the process behind `handle`, closing it on drop if `close_process` is set.
+    pub(crate) fn new(handle: LocalHandle, close_process: bool) -> OSProcess {
+        OSProcess { handle, close_process }
+    }
+}
+
+impl PTYChild for OSProcess {
+    fn is_alive(&self) -> Result<bool, PtyError> {
+        is_alive(self.handle)
+    }
+
+    fn get_exitstatus(&self) -> Result<Option<u32>, PtyError> {
+        get_exitstatus(self.handle)
+    }
+
+    fn wait_for_exit(&self) -> Result<bool, PtyError> {
+        wait_for_exit(self.handle)
+    }
+
+    fn wait_timeout(&self, timeout: Duration) -> Result<bool, PtyError> {
+        wait_timeout(self.handle, timeout)
+    }
+
+    fn exit_status(&self) -> Result<Option<ExitStatus>, PtyError> {
+        exit_status(self.handle)
+    }
+
+    fn get_pid(&self) -> u32 {
+        get_process_id(self.handle)
+    }
+
+    fn get_fd(&self) -> isize {
+        self.handle.0 as isize
+    }
+
+    fn signal(&self, signal: PtySignal) -> Result<(), PtyError> {
+        if !self.is_alive()? {
+            return Ok(());
+        }
+        signal_process(self.handle, signal)
+    }
+}
+
+impl Drop for OSProcess {
+    fn drop(&mut self) {
+        if self.close_process && !self.handle.is_invalid() {
+            close_process(self.handle);
+        }
+    }
+}
+
+unsafe impl Send for OSProcess {}
+unsafe impl Sync for OSProcess {}
+
+/// Event delivered to the subscriber of a [`PTYProcess`] output.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PTYEvent {
+    /// Raw bytes written by the process, in the order they were read.
+    Output(Vec<u8>),
+    /// Error raised while reading the output.
+    Error(PtyError),
+    /// The output reached EOF, along with the exit code of the process if it is known.
+    /// No event follows it.
+    Exit(Option<u32>),
+}
+
+/// Result of a read from the output of a process, which tells apart the lack
+/// of output from its end.
+///
+/// Every byte of output is returned before [`ReadOutcome::Eof`], whichever the
+/// backend, and reading again after EOF keeps returning it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ReadOutcome<T> {
+    /// Output read from the process, empty only if the read asked for none.
+    Data(T),
+    /// No output is available yet. Only returned by non-blocking reads.
+    WouldBlock,
+    /// The process has exited and all its output was read.
+    Eof,
+}
+
+impl<T> ReadOutcome<T> {
+    /// Convert the output read, if any.
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> ReadOutcome<U> {
+        match self {
+            ReadOutcome::Data(data) => ReadOutcome::Data(f(data)),
+            ReadOutcome::WouldBlock => ReadOutcome::WouldBlock,
+            ReadOutcome::Eof => ReadOutcome::Eof,
+        }
+    }
+
+    /// Retrieve the output read, `None` if there was none or the output reached EOF.
+    pub fn data(self) -> Option<T> {
+        match self {
+            ReadOutcome::Data(data) => Some(data),
+            _ => None,
+        }
+    }
+
+    /// `true` if the output reached EOF.
+    pub fn is_eof(&self) -> bool {
+        matches!(self, ReadOutcome::Eof)
+    }
+}
+
+impl<T: Default> ReadOutcome<T> {
+    /// Convert into the result of [`super::PTY::read`], which returns an empty
+    /// output when none is available and [`PtyError::Eof`] at EOF.
+    pub(crate) fn into_result(self) -> Result<T, PtyError> {
+        match self {
+            ReadOutcome::Data(data) => Ok(data),
+            ReadOutcome::WouldBlock => Ok(T::default()),
+            ReadOutcome::Eof => Err(PtyError::Eof),
+        }
+    }
+}
+
+/// Signal that can be sent to the process running inside a PTY.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PtySignal {
+    /// Interrupt the process, as Ctrl+C does. `SIGINT` on Unix, sent to the
+    /// foreground process group of a pseudoterminal. Consoles on Windows
+    /// receive a Ctrl+C keystroke, which raises `CTRL_C_EVENT`.
+    Interrupt,
+    /// Ask the process to exit. `SIGTERM` on Unix, Ctrl+C on Windows consoles,
+    /// since they have no other polite way to stop a process.
+    Terminate,
+    /// Stop the process right away. `SIGKILL` on Unix, `TerminateProcess` on Windows.
+    Kill,
+}
+
+impl PtySignal {
+    /// Number of the matching Unix signal.
+    #[cfg(unix)]
+    pub(crate) fn number(self) -> c_int {
+        match self {
+            PtySignal::Interrupt => libc::SIGINT,
+            PtySignal::Terminate => libc::SIGTERM,
+            PtySignal::Kill => libc::SIGKILL,
+        }
+    }
+}
+
 /// This struct handles the I/O operations to the standard streams, as well
 /// the lifetime of a process running inside a PTY.
 pub struct PTYProcess {
-    /// Handle to the process to read from.
-    process: LocalHandle,
+    /// Process running inside the PTY, if any.
+    process: Option<Arc<dyn PTYChild>>,
     /// Handle to the standard input stream.
     conin: LocalHandle,
     /// Handle to the standard output stream.
     conout: LocalHandle,
     /// Identifier of the process running inside the PTY.
     pid: u32,
-    /// Close process when the struct is dropped.
-    close_process: bool,
     /// Handle to the thread used to read from the standard output.
     reading_thread: Option<thread::JoinHandle<()>>,
     /// Channel used to keep the thread alive.
     reader_alive: mpsc::Sender<bool>,
-    /// Channel used to send the process handle to the reading thread.
-    reader_process_out: mpsc::Sender<Option<LocalHandle>>,
-    /// Handle to the thread used to cache read output.
-    cache_thread: Option<thread::JoinHandle<()>>,
-    /// Channel used to submit a retrieval request to the cache.
-    cache_req: mpsc::Sender<Option<(u32, bool)>>,
-    /// Channel used to receive a response from the cache.
-    cache_resp: mpsc::Receiver<Result<OsString, OsString>>,
-    /// Channel used to keep the cache alive.
-    cache_alive: mpsc::Sender<bool>,
+    /// Channel used to send the process to the reading thread.
+    reader_process_out: mpsc::Sender<Option<Arc<dyn PTYChild>>>,
+    /// Output read and not retrieved yet.
+    output: Arc<OutputBuffer>,
+    /// `true` once the output is forwarded to a subscriber instead of being cached.
+    subscribed: AtomicBool,
 }
 
 impl PTYProcess {
@@ -394,144 +1052,145 @@ impl PTYProcess {
     /// * `pty` - A new [`PTYProcess`] instance.
     pub fn new(conin: LocalHandle, conout: LocalHandle, using_pipes: bool) -> PTYProcess {
         // Continuous reading thread channels
-        let (reader_out_tx, reader_out_rx) = mpsc::channel::<Option<Result<OsString, OsString>>>();
         let (reader_alive_tx, reader_alive_rx) = mpsc::channel::<bool>();
-        let (reader_process_tx, reader_process_rx) = mpsc::channel::<Option<LocalHandle>>();
+        let (reader_process_tx, reader_process_rx) = mpsc::channel::<Option<Arc<dyn PTYChild>>>();
 
-        // Reading cache thread channels
-        let (cache_alive_tx, cache_alive_rx) = mpsc::channel::<bool>();
-        let (cache_req_tx, cache_req_rx) = mpsc::channel::<Option<(u32, bool)>>();
-        let (cache_resp_tx, cache_resp_rx) = mpsc::channel::<Result<OsString, OsString>>();
+        let output = Arc::new(OutputBuffer::new());
+        let reader_output = Arc::clone(&output);
 
         let reader_thread = thread::spawn(move || {
             let process_result = reader_process_rx.recv();
             if let Ok(Some(process)) = process_result {
-                // let mut alive = reader_alive_rx.recv_timeout(Duration::from_millis(300)).unwrap_or(true);
-                // alive = alive && !is_eof(process, conout).unwrap();
-
-                while reader_alive_rx.recv_timeout(Duration::from_millis(100)).unwrap_or(true) {
-                    if !is_eof(process.into(), conout.into()).unwrap() {
-                        let result = read(4096, true, conout.into(), using_pipes);
-                        reader_out_tx.send(Some(result)).unwrap();
+                let mut alive = true;
+                while alive {
+                    if !is_eof(process.as_ref(), conout).unwrap() {
+                        match read(4096, true, conout, using_pipes) {
+                            // Do not wake up a blocking read if nothing was read.
+                            Ok(bytes) if bytes.is_empty() => {
+                                alive = reader_alive_rx.recv_timeout(Duration::from_millis(100)).unwrap_or(true);
+                                continue;
+                            }
+                            Ok(bytes) => reader_output.push(bytes),
+                            Err(err) => reader_output.push_error(err),
+                        }
+                        // Read again right away, so that output is delivered without delay.
+                        alive = reader_alive_rx.try_recv().unwrap_or(true);
                     } else {
-                        reader_out_tx.send(None).unwrap();
+                        reader_output.finish(process.get_exitstatus().unwrap_or(None));
+                        alive = reader_alive_rx.recv_timeout(Duration::from_millis(100)).unwrap_or(true);
                     }
-                    // alive = reader_alive_rx.recv_timeout(Duration::from_millis(300)).unwrap_or(true);
-                    // alive = alive && !is_eof(process, conout).unwrap();
                 }
             }
 
+            reader_output.close();
             drop(reader_process_rx);
             drop(reader_alive_rx);
-            drop(reader_out_tx);
-        });
-
-        let cache_thread = thread::spawn(move || {
-            let mut read_buf = OsString::new();
-            let mut eof_reached;
-            while cache_alive_rx.try_recv().unwrap_or(true) {
-                if let Ok(Some((length, blocking))) = cache_req_rx.recv() {
-                    let mut pending_read: Option<OsString> = None;
-
-                    if (length as usize) <= read_buf.len() {
-                        pending_read = Some(OsString::new());
-                    }
-
-                    eof_reached = false;
-
-                    let out =
-                        match pending_read {
-                            Some(bytes) => Ok(bytes),
-                            None => {
-                                match blocking {
-                                    true => {
-                                        match reader_out_rx.recv() {
-                                            Ok(None) => {
-                                                eof_reached = true;
-                                                Ok(OsString::new())
-                                            }
-                                            Ok(Some(bytes)) => bytes,
-                                            Err(_) => Ok(OsString::new())
-                                        }
-                                    },
-                                    false => {
-                                        match reader_out_rx.recv_timeout(Duration::from_millis(200)) {
-                                            Ok(None) => {
-                                                eof_reached = true;
-                                                Ok(OsString::new())
-                                            }
-                                            Ok(Some(bytes)) => bytes,
-                                            Err(_) => Ok(OsString::new())
-                                        }
-                                    }
-                                }
-                            }
-                        };
-
-                    match out {
-                        Ok(bytes) => {
-                            read_buf.push(bytes);
-                            let vec_buf: Vec<u16> = read_buf.encode_wide().collect();
-                            let to_read = min(length as usize, vec_buf.len());
-                            let (left, right) = vec_buf.split_at(to_read);
-                            let to_return = OsString::from_wide(left);
-                            read_buf = OsString::from_wide(right);
-                            if eof_reached && to_return.is_empty() && length != 0 {
-                                cache_resp_tx.send(Err(OsString::from("Standard out reached EOF"))).unwrap();
-                            } else {
-                                cache_resp_tx.send(Ok(to_return)).unwrap();
-                            }
-                        },
-                        Err(err) => { cache_resp_tx.send(Err(err)).unwrap(); }
-                    }
-                }
-            }
-
-            drop(reader_out_rx);
-            drop(cache_alive_rx);
-            drop(cache_resp_tx);
         });
 
         PTYProcess {
-            process: LocalHandle(std::ptr::null_mut()),
+            process: None,
             conin,
             conout,
             pid: 0,
-            close_process: true,
             reading_thread: Some(reader_thread),
             reader_alive: reader_alive_tx,
             reader_process_out: reader_process_tx,
-            cache_thread: Some(cache_thread),
-            cache_req: cache_req_tx,
-            cache_resp: cache_resp_rx,
-            cache_alive: cache_alive_tx,
+            output,
+            subscribed: AtomicBool::new(false),
         }
     }
 
-    /// Read at least `length` characters from a process standard output.
+    /// Read at most `length` characters from a process standard output.
     ///
     /// # Arguments
     /// * `length` - Upper limit on the number of characters to read.
-    /// * `blocking` - Block the reading thread if no bytes are available.
+    /// * `blocking` - Block the reading thread until some output is available
+    ///   or the output reaches EOF.
     ///
     /// # Notes
-    /// * If `blocking = false`, then the function will check how much characters are available on
-    /// the stream and will read the minimum between the input argument and the total number of
-    /// characters available.
+    /// * If `blocking = false`, then the function waits a short while for output, and
+    ///   returns [`ReadOutcome::WouldBlock`] if there is still none.
     ///
     /// * The bytes returned are represented using a [`OsString`] since Windows operates over
-    /// `u16` strings.
-    pub fn read(&self, length: u32, blocking: bool) -> Result<OsString, OsString> {
-        // read(length, blocking, self.conout, self.using_pipes)
-        self.cache_req.send(Some((length, blocking))).unwrap();
+    ///   `u16` strings. The output is decoded as UTF-8, use [`PTYProcess::read_bytes`] to
+    ///   retrieve it unchanged.
+    ///
+    /// * A character split between two reads of the output is kept until the rest of it
+    ///   arrives, see [`super::Utf8Decoder`].
+    pub fn read(&self, length: u32, blocking: bool) -> Result<ReadOutcome<OsString>, PtyError> {
+        self.check_subscribed()?;
+        let outcome = self.output.read_text(length as usize, blocking)?;
+        Ok(outcome.map(|bytes| {
+            #[cfg(windows)]
+            {
+                // NUL characters are not forwarded as text, as it was always the case on Windows.
+                let mut text = String::from_utf8(bytes).unwrap();
+                text.retain(|character| character != '\0');
+                OsString::from(text)
+            }
+            #[cfg(unix)]
+            OsString::from(String::from_utf8(bytes).unwrap())
+        }))
+    }
+
+    /// Read raw bytes from a process standard output, without any decoding.
+    ///
+    /// # Arguments
+    /// * `buf` - Buffer to fill, at most `buf.len()` bytes are read.
+    /// * `blocking` - Block the reading thread until some output is available
+    ///   or the output reaches EOF.
+    ///
+    /// # Returns
+    /// The number of bytes placed at the start of `buf`, see [`ReadOutcome`].
+    pub fn read_bytes(&self, buf: &mut [u8], blocking: bool) -> Result<ReadOutcome<usize>, PtyError> {
+        self.check_subscribed()?;
+        let outcome = self.output.read_bytes(buf.len(), blocking)?;
+        Ok(outcome.map(|bytes| {
+            buf[..bytes.len()].copy_from_slice(&bytes);
+            bytes.len()
+        }))
+    }
 
-        match self.cache_resp.recv() {
-            Ok(Ok(bytes)) => Ok(bytes),
-            Ok(Err(err)) => Err(err),
-            Err(err) => Err(err.to_string().into())
+    /// Receive the output of the process as soon as the reading thread gets it,
+    /// instead of polling [`PTYProcess::read`].
+    ///
+    /// Every chunk of output is delivered as a [`PTYEvent::Output`], starting with
+    /// the output read before subscribing and not retrieved yet. Once the output
+    /// reaches EOF, a single [`PTYEvent::Exit`] closes the stream of events.
+    ///
+    /// # Returns
+    /// The receiving end of the events. The output can be subscribed to only once,
+    /// and it cannot be read through [`PTYProcess::read`] or [`PTYProcess::read_bytes`]
+    /// afterwards.
+    pub fn subscribe(&self) -> Result<mpsc::Receiver<PTYEvent>, PtyError> {
+        if self.subscribed.swap(true, Ordering::SeqCst) {
+            return Err(PtyError::Subscribed);
         }
-        // let out = self.reader_in.recv().unwrap();
-        // out
+        let (events_tx, events_rx) = mpsc::channel::<PTYEvent>();
+        self.output.subscribe(events_tx);
+        Ok(events_rx)
+    }
+
+    fn check_subscribed(&self) -> Result<(), PtyError> {
+        if self.subscribed.load(Ordering::SeqCst) {
+            return Err(PtyError::Subscribed);
+        }
+        Ok(())
+    }
+
+    /// Change how much output is kept until it is read, and what happens
+    /// once it does not fit, see [`OverflowPolicy`].
+    ///
+    /// # Arguments
+    /// * `capacity` - Maximum number of bytes kept, [`super::DEFAULT_OUTPUT_CAPACITY`] by default.
+    /// * `policy` - What happens to the output once `capacity` bytes are kept.
+    pub fn set_output_buffer(&self, capacity: usize, policy: OverflowPolicy) -> Result<(), PtyError> {
+        self.output.configure(capacity, policy)
+    }
+
+    /// Retrieve the counters of the output buffer.
+    pub fn output_stats(&self) -> OutputStats {
+        self.output.stats()
     }
 
     /// Write an (possibly) UTF-16 string into the standard input of a process.
@@ -541,51 +1200,93 @@ impl PTYProcess {
     ///
     /// # Returns
     /// The total number of characters written if the call was successful, else
-    /// an [`OsString`] containing an human-readable error.
-    pub fn write(&self, buf: OsString) -> Result<u32, OsString> {
+    /// a [`PtyError`] describing the failure.
+    #[cfg(windows)]
+    pub fn write(&self, buf: OsString) -> Result<u32, PtyError> {
         let vec_buf: Vec<u16> = buf.encode_wide().collect();
-        let result: HRESULT;
 
         unsafe {
             let required_size = WideCharToMultiByte(
                 CP_UTF8, 0, &vec_buf[..], None,
                 PCSTR(ptr::null_mut::<u8>()), None);
 
-            let mut bytes_buf: Vec<u8> = std::iter::repeat(0).take((required_size) as usize).collect();
+            let mut bytes_buf: Vec<u8> = std::iter::repeat_n(0, required_size as usize).collect();
 
             WideCharToMultiByte(
                 CP_UTF8, 0, &vec_buf[..], Some(&mut bytes_buf[..]),
                 PCSTR(ptr::null_mut::<u8>()),
                 None);
 
+            self.write_bytes(&bytes_buf)
+        }
+    }
+
+    /// Write raw bytes into the standard input of a process, without any encoding.
+    ///
+    /// # Arguments
+    /// * `buf` - Bytes to write.
+    ///
+    /// # Returns
+    /// The total number of bytes written if the call was successful, else
+    /// a [`PtyError`] describing the failure.
+    #[cfg(windows)]
+    pub fn write_bytes(&self, buf: &[u8]) -> Result<u32, PtyError> {
+        let result: HRESULT;
+
+        unsafe {
             let mut written_bytes = MaybeUninit::<u32>::uninit();
             let bytes_ptr: *mut u32 = ptr::addr_of_mut!(*written_bytes.as_mut_ptr());
             let bytes_ref = Some(bytes_ptr);
             // let bytes_ref = bytes_ptr.as_mut();
 
             result =
-                if WriteFile(Into::<HANDLE>::into(self.conin), Some(&bytes_buf[..]), bytes_ref, None).is_ok() {
+                if WriteFile(Into::<HANDLE>::into(self.conin), Some(buf), bytes_ref, None).is_ok() {
                     S_OK
                 } else {
                     Error::from_win32().into()
                 };
 
             if result.is_err() {
-                let result_msg = result.message();
-                let string = OsString::from(result_msg);
-                return Err(string);
+                return Err(result.into());
             }
             let total_bytes = written_bytes.assume_init();
             Ok(total_bytes)
         }
     }
 
+    /// Write an (possibly) UTF-16 string into the standard input of a process.
+    ///
+    /// # Arguments
+    /// * `buf` - [`OsString`] containing the string to write.
+    ///
+    /// # Returns
+    /// The total number of characters written if the call was successful, else
+    /// a [`PtyError`] describing the failure.
+    #[cfg(unix)]
+    pub fn write(&self, buf: OsString) -> Result<u32, PtyError> {
+        self.write_bytes(buf.as_bytes())
+    }
+
+    /// Write raw bytes into the standard input of a process, without any encoding.
+    ///
+    /// # Arguments
+    /// * `buf` - Bytes to write.
+    ///
+    /// # Returns
+    /// The total number of bytes written if the call was successful, else
+    /// a [`PtyError`] describing the failure.
+    #[cfg(unix)]
+    pub fn write_bytes(&self, buf: &[u8]) -> Result<u32, PtyError> {
+        write(self.conin, buf)
+    }
+
     /// Check if a process reached End-of-File (EOF).
     ///
     /// # Returns
-    /// `true` if the process reached EOL, false otherwise. If an error occurs, then a [`OsString`]
-    /// containing a human-readable error is raised.
-    pub fn is_eof(&self) -> Result<bool, OsString> {
+    /// `true` if the process reached EOL, false otherwise. If an error occurs, then a [`PtyError`]
+    /// describing it is raised.
+    #[cfg(windows)]
+    pub fn is_eof(&self) -> Result<bool, PtyError> {
         // let mut available_bytes: Box<u32> = Box::new_uninit();
         // let bytes_ptr: *mut u32 = &mut *available_bytes;
         // let bytes_ptr: *mut u32 = ptr::null_mut();
@@ -617,37 +1318,44 @@ impl PTYProcess {
 
     }
 
+    /// Check if a process reached End-of-File (EOF).
+    ///
+    /// # Returns
+    /// `true` if the process reached EOL, false otherwise. If an error occurs, then a [`PtyError`]
+    /// describing it is raised.
+    #[cfg(unix)]
+    pub fn is_eof(&self) -> Result<bool, PtyError> {
+        let alive = self.is_alive()?;
+        Ok(!alive && !output_pending(self.conout)?)
+    }
+
     /// Retrieve the exit status of the process
     ///
     /// # Returns
     /// `None` if the process has not exited, else the exit code of the process.
-    pub fn get_exitstatus(&self) -> Result<Option<u32>, OsString> {
-        if self.pid == 0 {
-            return Ok(None);
-        }
-
-        match get_exitstatus(self.process.into()) {
-            Ok(exitstatus) => Ok(exitstatus),
-            Err(err) => Err(err)
+    pub fn get_exitstatus(&self) -> Result<Option<u32>, PtyError> {
+        match &self.process {
+            Some(process) => process.get_exitstatus(),
+            None => Ok(None)
         }
     }
 
     /// Determine if the process is still alive.
-    pub fn is_alive(&self) -> Result<bool, OsString> {
-        // let mut exit_code: Box<u32> = Box::new_uninit();
-        // let exit_ptr: *mut u32 = &mut *exit_code;
-        match is_alive(self.process.into()) {
-            Ok(alive) => {
-                Ok(alive)
-            },
-            Err(err) => Err(err)
+    pub fn is_alive(&self) -> Result<bool, PtyError> {
+        match &self.process {
+            Some(process) => process.is_alive(),
+            None => Err(PtyError::NotSpawned)
         }
     }
 
     /// Set the running process behind the PTY.
-    pub fn set_process(&mut self, process: HANDLE, close_process: bool) {
-        self.process = process.into();
-        self.close_process = close_process;
+    pub fn set_process(&mut self, process: LocalHandle, close_process: bool) {
+        self.set_child(Arc::new(OSProcess::new(process, close_process)));
+    }
+
+    /// Set the running process behind the PTY, when it is not an operating
+    /// system process handle.
+    pub fn set_child(&mut self, process: Arc<dyn PTYChild>) {
 
         // if env::var_os("CONPTY_CI").is_some() {
         //     // For some reason, the CI requires a flush of the handle before
@@ -656,14 +1364,13 @@ impl PTYProcess {
         //     println!("{:?}", result);
         //     let result = read(4096, true, self.conout, false).unwrap();
         //     println!("{:?}", result);
-        //     let res: Result<u32, OsString> = self.write(OsString::from("\r\n\r\n"));
+        //     let res: Result<u32, PtyError> = self.write(OsString::from("\r\n\r\n"));
         //     res.unwrap();
         // }
 
-        self.reader_process_out.send(Some(process.into())).unwrap();
-        unsafe {
-            self.pid = GetProcessId(Into::<HANDLE>::into(self.process));
-        }
+        self.pid = process.get_pid();
+        self.process = Some(process.clone());
+        self.reader_process_out.send(Some(process)).unwrap();
     }
 
     /// Retrieve the Process ID associated to the current process.
@@ -673,55 +1380,73 @@ impl PTYProcess {
 
     /// Retrieve the process handle ID of the spawned program.
 	pub fn get_fd(&self) -> isize {
-        self.process.0 as isize
+        match &self.process {
+            Some(process) => process.get_fd(),
+            None => 0
+        }
     }
 
     /// Wait for the process to exit
-    pub fn wait_for_exit(&self) -> Result<bool, OsString> {
-        wait_for_exit(self.process.into())
+    pub fn wait_for_exit(&self) -> Result<bool, PtyError> {
+        match &self.process {
+            Some(process) => process.wait_for_exit(),
+            None => Err(PtyError::NotSpawned)
+        }
+    }
+
+    /// Wait at most `timeout` for the process to exit, `true` if it has exited.
+    pub fn wait_timeout(&self, timeout: Duration) -> Result<bool, PtyError> {
+        match &self.process {
+            Some(process) => process.wait_timeout(timeout),
+            None => Err(PtyError::NotSpawned)
+        }
     }
 
+    /// Retrieve how the process ended, `None` if it has not exited.
+    pub fn exit_status(&self) -> Result<Option<ExitStatus>, PtyError> {
+        match &self.process {
+            Some(process) => process.exit_status(),
+            None => Ok(None)
+        }
+    }
+
+    /// Send a signal to the process, see [`PtySignal`].
+    pub fn signal(&self, signal: PtySignal) -> Result<(), PtyError> {
+        match &self.process {
+            Some(process) => process.signal(signal),
+            None => Err(PtyError::NotSpawned)
+        }
+    }
 }
 
 impl Drop for PTYProcess {
     fn drop(&mut self) {
-        unsafe {
-            // Unblock thread if it is waiting for a process handle.
-            if self.reader_process_out.send(None).is_ok() { }
+        // Unblock thread if it is waiting for a process handle.
+        let _ = self.reader_process_out.send(None);
 
-            // Cancel all pending IO operations on conout
-            let _ = CancelIoEx(Into::<HANDLE>::into(self.conout), None);
+        // Cancel all pending IO operations on conout
+        cancel_io(self.conout);
 
-            // Send instruction to thread to finish
-            if self.reader_alive.send(false).is_ok() { }
+        // Send instruction to thread to finish
+        let _ = self.reader_alive.send(false);
 
-            // Wait for the thread to be down
-            if let Some(thread_handle) = self.reading_thread.take() {
-                thread_handle.join().unwrap();
-            }
-
-            // Send message to cache to finish
-            if self.cache_alive.send(false).is_ok() {}
-
-            // Send a None request to the cache in order to prevent blockage
-            if self.cache_req.send(None).is_ok() {}
+        // Unblock the thread if it waits for room in the output buffer
+        self.output.close();
 
-            // Wait for the cache to be down
-            if let Some(cache_handle) = self.cache_thread.take() {
-                cache_handle.join().unwrap();
-            }
-
-            if !self.conin.is_invalid() {
-                let _ = CloseHandle(Into::<HANDLE>::into(self.conin));
-            }
+        // Wait for the thread to be down
+        if let Some(thread_handle) = self.reading_thread.take() {
+            thread_handle.join().unwrap();
+        }
 
-            if !self.conout.is_invalid() {
-                let _ = CloseHandle(Into::<HANDLE>::into(self.conout));
-            }
+        if !self.conin.is_invalid() {
+            close_handle(self.conin);
+        }
 
-            if self.close_process && !self.process.is_invalid() {
-                let _ = CloseHandle(Into::<HANDLE>::into(self.process));
-            }
+        if !self.conout.is_invalid() {
+            close_handle(self.conout);
         }
+
+        // The process itself is closed once the last reference to it is dropped.
+        self.process = None;
     }
-}
+}
\ No newline at end of file
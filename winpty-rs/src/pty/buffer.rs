@@ -0,0 +1,326 @@
+//! Bounded buffer that holds the output of a process until it is read.
+//!
+//! The reading thread of a [`super::PTYProcess`] pushes every chunk of output
+//! into an [`OutputBuffer`], which the PTY reads from. Once the buffer is full,
+//! its [`OverflowPolicy`] decides whether the reading thread waits, the oldest
+//! output is dropped or the new output is dropped and reported as an error.
+
+use std::cmp::min;
+use std::sync::{mpsc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use super::{PTYEvent, PtyError, ReadOutcome};
+use super::decoder::Utf8Decoder;
+
+/// Capacity of the output buffer of a new PTY, in bytes.
+pub const DEFAULT_OUTPUT_CAPACITY: usize = 4 << 20;
+
+/// Smallest capacity of the output buffer, enough for any UTF-8 character.
+const MIN_OUTPUT_CAPACITY: usize = 4;
+
+/// Time a non-blocking read waits for output when none is buffered.
+const NONBLOCKING_WAIT: Duration = Duration::from_millis(200);
+
+/// What happens to the output of a process once its buffer is full.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Stop reading the output until some of it is read from the PTY. The process
+    /// blocks once the operating system buffers are full as well, as with a pipe.
+    #[default]
+    Block,
+    /// Keep reading, dropping the oldest output to make room for the new one.
+    /// A character cut by the drop is dropped whole, so the buffer may hold
+    /// up to three bytes less than its capacity.
+    DropOldest,
+    /// Keep reading, dropping the new output until there is room for it again.
+    /// The next read reports [`PtyError::Overflow`].
+    Error,
+}
+
+/// Counters of the output buffer of a PTY, see [`super::PTY::output_stats`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct OutputStats {
+    /// Bytes read from the process and not retrieved yet.
+    pub buffered: usize,
+    /// Maximum number of bytes the buffer holds.
+    pub capacity: usize,
+    /// Total number of bytes dropped because the buffer was full.
+    pub dropped_bytes: u64,
+    /// Number of chunks of output that did not fit whole in the buffer.
+    pub overflows: u64,
+}
+
+/// State shared by the reading thread and the readers of an [`OutputBuffer`].
+struct BufferState {
+    /// Bytes read and not retrieved yet, an incomplete character at the
+    /// end is kept until the rest of it is read.
+    decoder: Utf8Decoder,
+    capacity: usize,
+    policy: OverflowPolicy,
+    /// Error raised by the reading thread, returned by the next read.
+    error: Option<PtyError>,
+    /// Bytes dropped by [`OverflowPolicy::Error`] and not reported yet.
+    unreported: u64,
+    dropped_bytes: u64,
+    overflows: u64,
+    /// Exit status of the process once the output reached EOF.
+    eof: Option<Option<u32>>,
+    /// Channel every output is forwarded to instead of being buffered.
+    subscriber: Option<mpsc::Sender<PTYEvent>>,
+    /// `true` once the reading thread has stopped.
+    closed: bool,
+}
+
+impl BufferState {
+    /// Room left in the buffer, in bytes.
+    fn room(&self) -> usize {
+        self.capacity.saturating_sub(self.decoder.len())
+    }
+
+    /// Account for `count` bytes of output that did not fit in the buffer.
+    fn drop_output(&mut self, count: usize) {
+        self.dropped_bytes += count as u64;
+        self.overflows += 1;
+    }
+}
+
+/// Bounded buffer of the output of a process, in which every byte is stored
+/// once and retrieved in constant time, whatever the amount of buffered output.
+pub(crate) struct OutputBuffer {
+    state: Mutex<BufferState>,
+    /// Notified whenever output is pushed or retrieved, or the state changes.
+    changed: Condvar,
+}
+
+impl OutputBuffer {
+    /// Create an empty buffer of [`DEFAULT_OUTPUT_CAPACITY`] bytes, which blocks when full.
+    pub(crate) fn new() -> OutputBuffer {
+        OutputBuffer {
+            state: Mutex::new(BufferState {
+                decoder: Utf8Decoder::new(),
+                capacity: DEFAULT_OUTPUT_CAPACITY,
+                policy: OverflowPolicy::default(),
+                error: None,
+                unreported: 0,
+                dropped_bytes: 0,
+                overflows: 0,
+                eof: None,
+                subscriber: None,
+                closed: false,
+            }),
+            changed: Condvar::new(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BufferState> {
+        self.state.lock().unwrap()
+    }
+
+    /// Change the capacity and the overflow policy of the buffer. Output already
+    /// buffered beyond the new capacity is only dropped with [`OverflowPolicy::DropOldest`].
+    pub(crate) fn configure(&self, capacity: usize, policy: OverflowPolicy) -> Result<(), PtyError> {
+        if capacity < MIN_OUTPUT_CAPACITY {
+            return Err(PtyError::other(format!(
+                "The capacity of the output buffer must be at least {} bytes", MIN_OUTPUT_CAPACITY
+            )));
+        }
+        let mut state = self.lock();
+        state.capacity = capacity;
+        state.policy = policy;
+        if policy == OverflowPolicy::DropOldest && state.decoder.len() > capacity {
+            let excess = state.decoder.len() - capacity;
+            let dropped = state.decoder.drop_bytes(excess);
+            state.drop_output(dropped);
+        }
+        self.changed.notify_all();
+        Ok(())
+    }
+
+    pub(crate) fn stats(&self) -> OutputStats {
+        let state = self.lock();
+        OutputStats {
+            buffered: state.decoder.len(),
+            capacity: state.capacity,
+            dropped_bytes: state.dropped_bytes,
+            overflows: state.overflows,
+        }
+    }
+
+    /// Store a chunk of output read from the process, or forward it to the subscriber.
+    pub(crate) fn push(&self, bytes: Vec<u8>) {
+        let mut state = self.lock();
+        let mut rest = &bytes[..];
+        while !rest.is_empty() {
+            if let Some(events) = &state.subscriber {
+                let _ = events.send(PTYEvent::Output(rest.to_vec()));
+                return;
+            }
+            if state.closed {
+                return;
+            }
+
+            let room = state.room();
+            match state.policy {
+                OverflowPolicy::Block if room == 0 => {
+                    state = self.changed.wait(state).unwrap();
+                    continue;
+                }
+                OverflowPolicy::Block => {
+                    let count = min(room, rest.len());
+                    state.decoder.push(&rest[..count]);
+                    rest = &rest[count..];
+                }
+                OverflowPolicy::DropOldest => {
+                    // Only the end of a chunk larger than the whole buffer is kept,
+                    // from the start of a character so that none is cut in half.
+                    let skipped = rest.len().saturating_sub(state.capacity);
+                    state.decoder.push(&rest[skipped..]);
+                    let excess = state.decoder.len().saturating_sub(state.capacity);
+                    if skipped + excess > 0 {
+                        let dropped = state.decoder.drop_bytes(excess);
+                        state.drop_output(skipped + dropped);
+                    }
+                    rest = &[];
+                }
+                OverflowPolicy::Error => {
+                    let count = min(room, rest.len());
+                    state.decoder.push(&rest[..count]);
+                    let excess = rest.len() - count;
+                    if excess > 0 {
+                        state.drop_output(excess);
+                        state.unreported += excess as u64;
+                    }
+                    rest = &[];
+                }
+            }
+            self.changed.notify_all();
+        }
+    }
+
+    /// Report an error raised while reading the output.
+    pub(crate) fn push_error(&self, err: PtyError) {
+        let mut state = self.lock();
+        match &state.subscriber {
+            Some(events) => {
+                let _ = events.send(PTYEvent::Error(err));
+            }
+            None => {
+                state.error = Some(err);
+                self.changed.notify_all();
+            }
+        }
+    }
+
+    /// Mark the end of the output, along with the exit status of the process.
+    pub(crate) fn finish(&self, status: Option<u32>) {
+        let mut state = self.lock();
+        if state.eof.is_some() {
+            return;
+        }
+        state.eof = Some(status);
+        // No event follows the exit, dropping the sender ends the stream.
+        if let Some(events) = state.subscriber.take() {
+            let _ = events.send(PTYEvent::Exit(status));
+        }
+        self.changed.notify_all();
+    }
+
+    /// Mark that the reading thread has stopped, waking up every waiting reader.
+    pub(crate) fn close(&self) {
+        let mut state = self.lock();
+        state.closed = true;
+        // The reading thread stopped before the process exited.
+        if let Some(events) = state.subscriber.take() {
+            let _ = events.send(PTYEvent::Exit(None));
+        }
+        self.changed.notify_all();
+    }
+
+    /// Forward every output to `events` from now on, starting with the buffered one.
+    pub(crate) fn subscribe(&self, events: mpsc::Sender<PTYEvent>) {
+        let mut state = self.lock();
+        if let Some(err) = state.error.take() {
+            let _ = events.send(PTYEvent::Error(err));
+        }
+        // The subscriber may have gone away, the output is drained anyway.
+        if !state.decoder.is_empty() {
+            let length = state.decoder.len();
+            let _ = events.send(PTYEvent::Output(state.decoder.take_bytes(length)));
+        }
+        match state.eof {
+            Some(status) => {
+                let _ = events.send(PTYEvent::Exit(status));
+            }
+            None if state.closed => {
+                let _ = events.send(PTYEvent::Exit(None));
+            }
+            None => state.subscriber = Some(events),
+        }
+        self.changed.notify_all();
+    }
+
+    /// Retrieve up to `length` characters, counted in UTF-16 units, as UTF-8 bytes.
+    pub(crate) fn read_text(&self, length: usize, blocking: bool) -> Result<ReadOutcome<Vec<u8>>, PtyError> {
+        self.read(length, true, blocking)
+    }
+
+    /// Retrieve up to `length` raw bytes.
+    pub(crate) fn read_bytes(&self, length: usize, blocking: bool) -> Result<ReadOutcome<Vec<u8>>, PtyError> {
+        self.read(length, false, blocking)
+    }
+
+    /// Retrieve the buffered output, waiting for some if there is none.
+    ///
+    /// A blocking read waits until output is available or the output reached
+    /// EOF, a non-blocking one at most [`NONBLOCKING_WAIT`]. Errors come first,
+    /// and EOF is only reported once every buffered byte was retrieved.
+    fn read(&self, length: usize, text: bool, blocking: bool) -> Result<ReadOutcome<Vec<u8>>, PtyError> {
+        let deadline = (!blocking).then(|| Instant::now() + NONBLOCKING_WAIT);
+        let mut state = self.lock();
+        loop {
+            if state.unreported > 0 {
+                let dropped = state.unreported;
+                state.unreported = 0;
+                return Err(PtyError::Overflow { dropped });
+            }
+            if let Some(err) = state.error.take() {
+                return Err(err);
+            }
+
+            // The reading thread stops for good once closed, an incomplete
+            // character left at the end is decoded as is.
+            let finished = state.eof.is_some() || state.closed;
+            let out = match text {
+                true => {
+                    let out = state.decoder.decode(length, finished);
+                    // A character outside of the BMP takes two units, it is
+                    // returned whole rather than never.
+                    match out.is_empty() && length == 1 {
+                        true => state.decoder.decode(2, finished),
+                        false => out,
+                    }.into_bytes()
+                }
+                false => state.decoder.take_bytes(length),
+            };
+            if !out.is_empty() || length == 0 {
+                // Room was made for a reading thread that waits for it.
+                self.changed.notify_all();
+                return Ok(ReadOutcome::Data(out));
+            }
+            if finished {
+                return Ok(ReadOutcome::Eof);
+            }
+
+            match deadline {
+                None => state = self.changed.wait(state).unwrap(),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Ok(ReadOutcome::WouldBlock);
+                    }
+                    state = self.changed.wait_timeout(state, deadline - now).unwrap().0;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,173 @@
+//! Conversion between a list of arguments and a single command line.
+//!
+//! Windows processes receive their arguments as one string that they parse
+//! themselves, most of them following the rules of the Microsoft C runtime
+//! (the same ones as `CommandLineToArgvW`):
+//!
+//! * Arguments are separated by spaces and tabs, unless they are quoted.
+//! * Backslashes are literal, except right before a quote: each pair of them
+//!   becomes one backslash, and an odd one makes the quote literal.
+//! * Inside quotes, two consecutive quotes are a literal quote.
+//! * The program name, first in the command line, has no escapes: its quotes
+//!   only group characters.
+//!
+//! The same rules are used on Unix to split the command line given to
+//! [`super::PTY::spawn`] back into arguments, so that they survive the trip.
+//! [`super::PtyCommand`] skips the trip on Unix and passes the arguments as is.
+//!
+//! # Examples
+//! ```
+//! use winptyrs::pty::cmdline::{join_command_line, parse_command_line};
+//!
+//! let args = ["/c", "echo", "two words", "a \"quote\""];
+//! let cmdline = join_command_line("C:\\Program Files\\app.exe", args);
+//! assert_eq!(cmdline, r#""C:\Program Files\app.exe" /c echo "two words" "a \"quote\"""#);
+//!
+//! let argv = parse_command_line(&cmdline);
+//! assert_eq!(argv[0], "C:\\Program Files\\app.exe");
+//! assert_eq!(argv[1..], args);
+//! ```
+
+#[cfg(unix)]
+use std::ffi::OsString;
+use std::iter::repeat_n;
+
+/// Quote `arg` so that it is parsed back as a single argument.
+pub fn quote_arg(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '\n', '\x0b', '"']) {
+        return arg.to_owned();
+    }
+
+    let mut quoted = String::from("\"");
+    let mut backslashes = 0;
+    for character in arg.chars() {
+        match character {
+            '\\' => backslashes += 1,
+            '"' => {
+                // Double the backslashes before the quote, and escape the quote itself.
+                quoted.extend(repeat_n('\\', backslashes + 1));
+                backslashes = 0;
+            },
+            _ => backslashes = 0
+        }
+        quoted.push(character);
+    }
+    // The backslashes at the end are followed by the closing quote, double them.
+    quoted.extend(repeat_n('\\', backslashes));
+    quoted.push('"');
+    quoted
+}
+
+/// Quote the program name of a command line.
+///
+/// The program name cannot contain quotes, since they are not escaped.
+pub fn quote_program(program: &str) -> String {
+    if !program.is_empty() && !program.contains([' ', '\t']) {
+        return program.to_owned();
+    }
+    format!("\"{}\"", program)
+}
+
+/// Join `args` into a command line, quoting them as needed.
+pub fn join_args<I, S>(args: I) -> String
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>
+{
+    args.into_iter()
+        .map(|arg| quote_arg(arg.as_ref()))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Join `program` and its `args` into a full command line.
+pub fn join_command_line<I, S>(program: &str, args: I) -> String
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>
+{
+    let mut cmdline = quote_program(program);
+    let args = join_args(args);
+    if !args.is_empty() {
+        cmdline.push(' ');
+        cmdline.push_str(&args);
+    }
+    cmdline
+}
+
+/// Split the optional command line given to [`super::PTY::spawn`] back into arguments.
+#[cfg(unix)]
+pub(crate) fn split_cmdline(cmdline: Option<OsString>) -> Vec<OsString> {
+    match cmdline {
+        Some(cmdline) => split_args(&cmdline.to_string_lossy()).into_iter().map(OsString::from).collect(),
+        None => Vec::new(),
+    }
+}
+
+/// Split a command line that only holds arguments, without the program name.
+pub fn split_args(cmdline: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut chars = cmdline.chars().peekable();
+
+    loop {
+        while chars.next_if(|character| matches!(character, ' ' | '\t')).is_some() {}
+        if chars.peek().is_none() {
+            return args;
+        }
+
+        let mut arg = String::new();
+        let mut in_quotes = false;
+        while let Some(character) = chars.next() {
+            match character {
+                '\\' => {
+                    let mut backslashes = 1;
+                    while chars.next_if_eq(&'\\').is_some() {
+                        backslashes += 1;
+                    }
+                    if chars.peek() == Some(&'"') {
+                        // Backslashes are only special right before a quote.
+                        arg.extend(repeat_n('\\', backslashes / 2));
+                        if backslashes % 2 == 1 {
+                            arg.push('"');
+                            chars.next();
+                        }
+                    } else {
+                        arg.extend(repeat_n('\\', backslashes));
+                    }
+                },
+                '"' => {
+                    if in_quotes && chars.next_if_eq(&'"').is_some() {
+                        arg.push('"');
+                    } else {
+                        in_quotes = !in_quotes;
+                    }
+                },
+                ' ' | '\t' if !in_quotes => break,
+                _ => arg.push(character)
+            }
+        }
+        args.push(arg);
+    }
+}
+
+/// Split a full command line, the program name first, as a C program would
+/// receive it in `argv`.
+pub fn parse_command_line(cmdline: &str) -> Vec<String> {
+    let mut program = String::new();
+    let mut in_quotes = false;
+    let mut rest = "";
+    for (index, character) in cmdline.char_indices() {
+        match character {
+            '"' => in_quotes = !in_quotes,
+            ' ' | '\t' if !in_quotes => {
+                rest = &cmdline[index..];
+                break;
+            },
+            _ => program.push(character)
+        }
+    }
+
+    let mut argv = vec![program];
+    argv.extend(split_args(rest));
+    argv
+}
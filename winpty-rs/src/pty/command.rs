@@ -0,0 +1,249 @@
+//! Builder to spawn a process inside a new [`PTY`], in the manner of
+//! [`std::process::Command`].
+
+use std::ffi::{OsStr, OsString};
+
+use super::cmdline::{join_args, join_command_line, quote_program};
+use super::{PTY, PTYArgs, PTYBackend, PtyError, MouseMode, AgentConfig};
+
+/// Description of a process to spawn inside a pseudoterminal.
+///
+/// The arguments are quoted into the command line expected by the backend on
+/// Windows, and passed unchanged on Unix. The environment is built from the one of the current process, unless
+/// [`PtyCommand::env_clear`] is called.
+///
+/// # Examples
+/// ```
+/// use std::io::Read;
+/// use winptyrs::{PtyCommand, PTYBackend};
+///
+/// # #[cfg(unix)] {
+/// let pty = PtyCommand::new("/bin/sh")
+///     .args(["-c", "echo \"$GREETING\" from $(pwd)"])
+///     .env("GREETING", "hello world")
+///     .current_dir("/")
+///     .size(120, 40)
+///     .backend(PTYBackend::Pipes)
+///     .spawn()
+///     .unwrap();
+///
+/// let (mut reader, _, _) = pty.split();
+/// let mut output = String::new();
+/// reader.read_to_string(&mut output).unwrap();
+/// assert_eq!(output, "hello world from /\n");
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct PtyCommand {
+    program: OsString,
+    args: Vec<OsString>,
+    /// Changes to the inherited environment, applied in order. `None` removes the variable.
+    envs: Vec<(OsString, Option<OsString>)>,
+    env_clear: bool,
+    cwd: Option<OsString>,
+    cols: i32,
+    rows: i32,
+    backend: PTYBackend,
+    mouse_mode: MouseMode,
+    timeout: u32,
+    agent_config: AgentConfig,
+    kill_on_drop: bool,
+}
+
+impl PtyCommand {
+    /// Create a command that runs `program` in a 80x25 pseudoterminal, whose
+    /// backend is selected automatically.
+    pub fn new<S: AsRef<OsStr>>(program: S) -> PtyCommand {
+        PtyCommand {
+            program: program.as_ref().to_owned(),
+            args: Vec::new(),
+            envs: Vec::new(),
+            env_clear: false,
+            cwd: None,
+            cols: 80,
+            rows: 25,
+            backend: PTYBackend::Auto,
+            mouse_mode: MouseMode::WINPTY_MOUSE_MODE_NONE,
+            timeout: 10000,
+            agent_config: AgentConfig::WINPTY_FLAG_COLOR_ESCAPES,
+            kill_on_drop: false,
+        }
+    }
+
+    /// Add an argument to pass to the program.
+    pub fn arg<S: AsRef<OsStr>>(&mut self, arg: S) -> &mut PtyCommand {
+        self.args.push(arg.as_ref().to_owned());
+        self
+    }
+
+    /// Add several arguments to pass to the program.
+    pub fn args<I, S>(&mut self, args: I) -> &mut PtyCommand
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>
+    {
+        for arg in args {
+            self.arg(arg);
+        }
+        self
+    }
+
+    /// Set an environment variable of the process.
+    pub fn env<K: AsRef<OsStr>, V: AsRef<OsStr>>(&mut self, key: K, value: V) -> &mut PtyCommand {
+        self.envs.push((key.as_ref().to_owned(), Some(value.as_ref().to_owned())));
+        self
+    }
+
+    /// Set several environment variables of the process.
+    pub fn envs<I, K, V>(&mut self, vars: I) -> &mut PtyCommand
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>
+    {
+        for (key, value) in vars {
+            self.env(key, value);
+        }
+        self
+    }
+
+    /// Remove an environment variable, whether it is inherited or was set before.
+    pub fn env_remove<K: AsRef<OsStr>>(&mut self, key: K) -> &mut PtyCommand {
+        self.envs.push((key.as_ref().to_owned(), None));
+        self
+    }
+
+    /// Do not inherit the environment of the current process, nor keep the
+    /// variables set so far.
+    pub fn env_clear(&mut self) -> &mut PtyCommand {
+        self.env_clear = true;
+        self.envs.clear();
+        self
+    }
+
+    /// Set the working directory of the process.
+    pub fn current_dir<P: AsRef<OsStr>>(&mut self, dir: P) -> &mut PtyCommand {
+        self.cwd = Some(dir.as_ref().to_owned());
+        self
+    }
+
+    /// Set the initial size of the pseudoterminal.
+    pub fn size(&mut self, cols: i32, rows: i32) -> &mut PtyCommand {
+        self.cols = cols;
+        self.rows = rows;
+        self
+    }
+
+    /// Set the backend of the pseudoterminal, [`PTYBackend::Auto`] by default.
+    pub fn backend(&mut self, backend: PTYBackend) -> &mut PtyCommand {
+        self.backend = backend;
+        self
+    }
+
+    /// Set the mouse capture mode of the winpty backend, see [`PTYArgs::mouse_mode`].
+    /// [`MouseMode::WINPTY_MOUSE_MODE_NONE`] by default.
+    pub fn mouse_mode(&mut self, mode: MouseMode) -> &mut PtyCommand {
+        self.mouse_mode = mode;
+        self
+    }
+
+    /// Set the time to wait for the winpty agent, in milliseconds, see
+    /// [`PTYArgs::timeout`]. 10000 by default.
+    pub fn timeout(&mut self, timeout: u32) -> &mut PtyCommand {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Set the configuration flags of the pseudoterminal, see [`PTYArgs::agent_config`].
+    /// [`AgentConfig::WINPTY_FLAG_COLOR_ESCAPES`] by default.
+    pub fn agent_config(&mut self, config: AgentConfig) -> &mut PtyCommand {
+        self.agent_config = config;
+        self
+    }
+
+    /// Kill every process spawned by the process when the [`PTY`] is dropped,
+    /// see [`PTY::set_kill_on_drop`].
+    pub fn kill_on_drop(&mut self, enabled: bool) -> &mut PtyCommand {
+        self.kill_on_drop = enabled;
+        self
+    }
+
+    /// Create the pseudoterminal and spawn the process inside it.
+    ///
+    /// # Returns
+    /// The [`PTY`] that hosts the process, used to communicate with it.
+    pub fn spawn(&self) -> Result<PTY, PtyError> {
+        let pty_args = PTYArgs {
+            cols: self.cols,
+            rows: self.rows,
+            mouse_mode: self.mouse_mode,
+            timeout: self.timeout,
+            agent_config: self.agent_config
+        };
+        let mut pty = PTY::new_with_backend(&pty_args, self.backend)?;
+        if self.kill_on_drop {
+            pty.set_kill_on_drop(true)?;
+        }
+
+        let program = self.program.to_string_lossy();
+        let args: Vec<_> = self.args.iter().map(|arg| arg.to_string_lossy()).collect();
+        let (cwd, env) = (self.cwd.clone(), self.env_block());
+        match pty.get_backend() {
+            // ConPTY puts the program at the start of the command line as is.
+            PTYBackend::ConPTY => {
+                let cmdline = Some(join_args(&args)).filter(|cmdline| !cmdline.is_empty()).map(OsString::from);
+                pty.spawn(OsString::from(quote_program(&program)), cmdline, cwd, env)?
+            },
+            // winpty expects the whole command line, the program included.
+            PTYBackend::WinPTY => {
+                let cmdline = OsString::from(join_command_line(&program, &args));
+                pty.spawn(self.program.clone(), Some(cmdline), cwd, env)?
+            },
+            // The other backends quote the arguments themselves, if they need to.
+            _ => pty.spawn_args(self.program.clone(), self.args.clone(), cwd, env)?
+        };
+        Ok(pty)
+    }
+
+    /// Build the environment block given to [`PTY::spawn`], `None` if the
+    /// environment is inherited unchanged.
+    fn env_block(&self) -> Option<OsString> {
+        if !self.env_clear && self.envs.is_empty() {
+            return None;
+        }
+
+        let mut vars: Vec<(OsString, OsString)> = if self.env_clear {
+            Vec::new()
+        } else {
+            std::env::vars_os().collect()
+        };
+        for (key, value) in &self.envs {
+            vars.retain(|(name, _)| !same_key(name, key));
+            if let Some(value) = value {
+                vars.push((key.clone(), value.clone()));
+            }
+        }
+
+        let mut block = OsString::new();
+        for (key, value) in vars {
+            block.push(key);
+            block.push("=");
+            block.push(value);
+            block.push("\0");
+        }
+        // An empty block still needs its terminator.
+        if block.is_empty() {
+            block.push("\0");
+        }
+        Some(block)
+    }
+}
+
+/// Compare environment variable names, which are case insensitive on Windows.
+fn same_key(first: &OsStr, second: &OsStr) -> bool {
+    if cfg!(windows) {
+        first.eq_ignore_ascii_case(second)
+    } else {
+        first == second
+    }
+}
@@ -1,42 +1,68 @@
 
 use std::ffi::OsString;
+use std::sync::mpsc;
+use std::time::Duration;
 
 // Default implementation if winpty is not available
-use crate::pty::{PTYArgs, PTYImpl};
+use crate::pty::{PTYArgs, PTYBackend, PTYEvent, PTYImpl, PtyError, PtySignal, ExitStatus, ReadOutcome, OverflowPolicy, OutputStats};
 
 pub struct ConPTY {}
 
 impl PTYImpl for ConPTY {
-    fn new(_args: &PTYArgs) -> Result<Box<dyn PTYImpl>, OsString> {
-        Err(OsString::from("pty_rs was compiled without ConPTY enabled"))
+    fn new(_args: &PTYArgs) -> Result<Box<dyn PTYImpl>, PtyError> {
+        Err(PtyError::unavailable(PTYBackend::ConPTY, "pty_rs was compiled without ConPTY enabled"))
     }
 
-    fn spawn(&mut self, _appname: OsString, _cmdline: Option<OsString>, _cwd: Option<OsString>, _env: Option<OsString>) -> Result<bool, OsString> {
-        Err(OsString::from("pty_rs was compiled without ConPTY enabled"))
+    fn spawn(&mut self, _appname: OsString, _cmdline: Option<OsString>, _cwd: Option<OsString>, _env: Option<OsString>) -> Result<bool, PtyError> {
+        Err(PtyError::unavailable(PTYBackend::ConPTY, "pty_rs was compiled without ConPTY enabled"))
     }
 
-    fn set_size(&self, _cols: i32, _rows: i32) -> Result<(), OsString> {
-        Err(OsString::from("pty_rs was compiled without ConPTY enabled"))
+    fn set_kill_on_drop(&mut self, _enabled: bool) -> Result<(), PtyError> {
+        Err(PtyError::unavailable(PTYBackend::ConPTY, "pty_rs was compiled without ConPTY enabled"))
     }
 
-    fn read(&self, _length: u32, _blocking: bool) -> Result<OsString, OsString> {
-        Err(OsString::from("pty_rs was compiled without ConPTY enabled"))
+    fn set_output_buffer(&self, _capacity: usize, _policy: OverflowPolicy) -> Result<(), PtyError> {
+        Err(PtyError::unavailable(PTYBackend::ConPTY, "pty_rs was compiled without ConPTY enabled"))
     }
 
-    fn write(&self, _buf: OsString) -> Result<u32, OsString> {
-        Err(OsString::from("pty_rs was compiled without ConPTY enabled"))
+    fn output_stats(&self) -> Result<OutputStats, PtyError> {
+        Err(PtyError::unavailable(PTYBackend::ConPTY, "pty_rs was compiled without ConPTY enabled"))
     }
 
-    fn is_eof(&self) -> Result<bool, OsString> {
-        Err(OsString::from("pty_rs was compiled without ConPTY enabled"))
+    fn set_size(&self, _cols: i32, _rows: i32) -> Result<(), PtyError> {
+        Err(PtyError::unavailable(PTYBackend::ConPTY, "pty_rs was compiled without ConPTY enabled"))
     }
 
-    fn get_exitstatus(&self) -> Result<Option<u32>, OsString> {
-        Err(OsString::from("pty_rs was compiled without ConPTY enabled"))
+    fn read(&self, _length: u32, _blocking: bool) -> Result<ReadOutcome<OsString>, PtyError> {
+        Err(PtyError::unavailable(PTYBackend::ConPTY, "pty_rs was compiled without ConPTY enabled"))
     }
 
-    fn is_alive(&self) -> Result<bool, OsString> {
-        Err(OsString::from("pty_rs was compiled without ConPTY enabled"))
+    fn read_bytes(&self, _buf: &mut [u8], _blocking: bool) -> Result<ReadOutcome<usize>, PtyError> {
+        Err(PtyError::unavailable(PTYBackend::ConPTY, "pty_rs was compiled without ConPTY enabled"))
+    }
+
+    fn subscribe(&self) -> Result<mpsc::Receiver<PTYEvent>, PtyError> {
+        Err(PtyError::unavailable(PTYBackend::ConPTY, "pty_rs was compiled without ConPTY enabled"))
+    }
+
+    fn write(&self, _buf: OsString) -> Result<u32, PtyError> {
+        Err(PtyError::unavailable(PTYBackend::ConPTY, "pty_rs was compiled without ConPTY enabled"))
+    }
+
+    fn write_bytes(&self, _buf: &[u8]) -> Result<u32, PtyError> {
+        Err(PtyError::unavailable(PTYBackend::ConPTY, "pty_rs was compiled without ConPTY enabled"))
+    }
+
+    fn is_eof(&self) -> Result<bool, PtyError> {
+        Err(PtyError::unavailable(PTYBackend::ConPTY, "pty_rs was compiled without ConPTY enabled"))
+    }
+
+    fn get_exitstatus(&self) -> Result<Option<u32>, PtyError> {
+        Err(PtyError::unavailable(PTYBackend::ConPTY, "pty_rs was compiled without ConPTY enabled"))
+    }
+
+    fn is_alive(&self) -> Result<bool, PtyError> {
+        Err(PtyError::unavailable(PTYBackend::ConPTY, "pty_rs was compiled without ConPTY enabled"))
     }
 
     fn get_pid(&self) -> u32 {
@@ -47,7 +73,19 @@ impl PTYImpl for ConPTY {
         -1
     }
 
-    fn wait_for_exit(&self) -> Result<bool, OsString> {
-        Err(OsString::from("pty_rs was compiled without ConPTY enabled"))
+    fn wait_for_exit(&self) -> Result<bool, PtyError> {
+        Err(PtyError::unavailable(PTYBackend::ConPTY, "pty_rs was compiled without ConPTY enabled"))
+    }
+
+    fn wait_timeout(&self, _timeout: Duration) -> Result<bool, PtyError> {
+        Err(PtyError::unavailable(PTYBackend::ConPTY, "pty_rs was compiled without ConPTY enabled"))
+    }
+
+    fn exit_status(&self) -> Result<Option<ExitStatus>, PtyError> {
+        Err(PtyError::unavailable(PTYBackend::ConPTY, "pty_rs was compiled without ConPTY enabled"))
+    }
+
+    fn signal(&self, _signal: PtySignal) -> Result<(), PtyError> {
+        Err(PtyError::unavailable(PTYBackend::ConPTY, "pty_rs was compiled without ConPTY enabled"))
     }
 }
@@ -1,4 +1,4 @@
-/// Actual ConPTY implementation.
+//! Actual ConPTY implementation.
 
 use windows::core::{ PWSTR, PCWSTR, Error };
 use windows::Win32::Foundation::{ CloseHandle, S_OK, INVALID_HANDLE_VALUE };
@@ -41,7 +41,10 @@ use windows::Win32::System::Threading::{
     CreateProcessW,
     EXTENDED_STARTUPINFO_PRESENT,
     CREATE_UNICODE_ENVIRONMENT,
+    CREATE_SUSPENDED,
     DeleteProcThreadAttributeList,
+    ResumeThread,
+    TerminateProcess,
 };
 use windows::Win32::UI::WindowsAndMessaging::{ ShowWindow, SW_HIDE };
 use windows::core::HRESULT;
@@ -49,10 +52,13 @@ use windows::core::HRESULT;
 use std::{ mem, ptr };
 use std::mem::MaybeUninit;
 use std::ffi::OsString;
+use std::sync::mpsc;
+use std::time::Duration;
 use std::os::windows::ffi::OsStrExt;
 
-use crate::pty::{ PTYProcess, PTYImpl };
+use crate::pty::{ PTYProcess, PTYImpl, PTYEvent, PtyError, PtySignal, ExitStatus, ReadOutcome, OverflowPolicy, OutputStats };
 use crate::pty::PTYArgs;
+use crate::pty::base::ProcessTree;
 
 /// Struct that contains the required information to spawn a console
 /// using the Windows API `CreatePseudoConsole` call.
@@ -62,30 +68,26 @@ pub struct ConPTY {
     startup_info: STARTUPINFOEXW,
     process: PTYProcess,
     console_allocated: bool,
+    /// Whether every process spawned by the process is killed on drop.
+    kill_on_drop: bool,
+    /// Job Object holding the process and its descendants, if `kill_on_drop` is set.
+    tree: Option<ProcessTree>,
 }
 
 unsafe impl Send for ConPTY {}
 unsafe impl Sync for ConPTY {}
 
 impl PTYImpl for ConPTY {
-    fn new(args: &PTYArgs) -> Result<Box<dyn PTYImpl>, OsString> {
+    fn new(args: &PTYArgs) -> Result<Box<dyn PTYImpl>, PtyError> {
         let mut result: HRESULT;
-        if args.cols <= 0 || args.rows <= 0 {
-            let err: OsString = OsString::from(
-                format!(
-                    "PTY cols and rows must be positive and non-zero. Got: ({}, {})",
-                    args.cols,
-                    args.rows
-                )
-            );
-            return Err(err);
-        }
+        PtyError::check_size(args.cols, args.rows)?;
 
         unsafe {
             // Create a console window in case ConPTY is running in a GUI application.
             let console_allocated = AllocConsole().is_ok();
             if console_allocated {
-                let _ = ShowWindow(GetConsoleWindow(), SW_HIDE).unwrap();
+                // The previous visibility of the window is returned, not an error.
+                let _ = ShowWindow(GetConsoleWindow(), SW_HIDE);
             }
 
             // Recreate the standard stream inputs in case the parent process
@@ -105,9 +107,7 @@ impl PTYImpl for ConPTY {
             );
 
             if let Err(err) = h_console_res {
-                let result_msg = err.message();
-                let string = OsString::from(result_msg);
-                return Err(string);
+                return Err(err.into());
             }
 
             let h_console = h_console_res.unwrap();
@@ -127,9 +127,7 @@ impl PTYImpl for ConPTY {
             );
 
             if let Err(err) = h_in_res {
-                let result_msg = err.message();
-                let string = OsString::from(result_msg);
-                return Err(string);
+                return Err(err.into());
             }
 
             let h_in = h_in_res.unwrap();
@@ -144,9 +142,7 @@ impl PTYImpl for ConPTY {
             };
 
             if result.is_err() {
-                let result_msg = result.message();
-                let string = OsString::from(result_msg);
-                return Err(string);
+                return Err(result.into());
             }
 
             let console_mode = console_mode_un.assume_init();
@@ -161,9 +157,7 @@ impl PTYImpl for ConPTY {
             };
 
             if result.is_err() {
-                let result_msg = result.message();
-                let string = OsString::from(result_msg);
-                return Err(string);
+                return Err(result.into());
             }
 
             // Set new streams
@@ -174,9 +168,7 @@ impl PTYImpl for ConPTY {
             };
 
             if result.is_err() {
-                let result_msg = result.message();
-                let string = OsString::from(result_msg);
-                return Err(string);
+                return Err(result.into());
             }
 
             result = if SetStdHandle(STD_ERROR_HANDLE, h_console).is_ok() {
@@ -186,9 +178,7 @@ impl PTYImpl for ConPTY {
             };
 
             if result.is_err() {
-                let result_msg = result.message();
-                let string = OsString::from(result_msg);
-                return Err(string);
+                return Err(result.into());
             }
 
             result = if SetStdHandle(STD_INPUT_HANDLE, h_in).is_ok() {
@@ -197,9 +187,7 @@ impl PTYImpl for ConPTY {
                 Error::from_win32().into()
             };
             if result.is_err() {
-                let result_msg = result.message();
-                let string = OsString::from(result_msg);
-                return Err(string);
+                return Err(result.into());
             }
 
             // Create communication channels
@@ -214,26 +202,20 @@ impl PTYImpl for ConPTY {
             // Setup PTY size
             let size = COORD { X: args.cols as i16, Y: args.rows as i16 };
 
-            if !CreatePipe(&mut input_read_side, &mut input_write_side, None, 0).is_ok() {
+            if CreatePipe(&mut input_read_side, &mut input_write_side, None, 0).is_err() {
                 result = Error::from_win32().into();
-                let result_msg = result.message();
-                let string = OsString::from(result_msg);
-                return Err(string);
+                return Err(result.into());
             }
 
-            if !CreatePipe(&mut output_read_side, &mut output_write_side, None, 0).is_ok() {
+            if CreatePipe(&mut output_read_side, &mut output_write_side, None, 0).is_err() {
                 result = Error::from_win32().into();
-                let result_msg = result.message();
-                let string = OsString::from(result_msg);
-                return Err(string);
+                return Err(result.into());
             }
 
             let pty_handle = match CreatePseudoConsole(size, input_read_side, output_write_side, 0) {
                 Ok(pty) => pty,
                 Err(err) => {
-                    let result_msg = err.message();
-                    let string = OsString::from(result_msg);
-                    return Err(string);
+                    return Err(err.into());
                 }
             };
 
@@ -253,6 +235,8 @@ impl PTYImpl for ConPTY {
                     startup_info: STARTUPINFOEXW::default(),
                     process: pty_process,
                     console_allocated,
+                    kill_on_drop: false,
+                    tree: None,
                 }) as Box<dyn PTYImpl>
             )
         }
@@ -264,8 +248,7 @@ impl PTYImpl for ConPTY {
         cmdline: Option<OsString>,
         cwd: Option<OsString>,
         env: Option<OsString>
-    ) -> Result<bool, OsString> {
-        let result: HRESULT;
+    ) -> Result<bool, PtyError> {
         let mut environ: *const u16 = ptr::null();
         let mut working_dir: *const u16 = ptr::null_mut();
         let mut env_buf: Vec<u16>;
@@ -326,22 +309,19 @@ impl PTYImpl for ConPTY {
 
             // Initialize the list memory location
             if
-                !InitializeProcThreadAttributeList(
+                InitializeProcThreadAttributeList(
                     Some(start_info.lpAttributeList),
                     1,
                     None,
                     &mut required_bytes
-                ).is_ok()
+                ).is_err()
             {
-                result = Error::from_win32().into();
-                let result_msg = result.message();
-                let string = OsString::from(result_msg);
-                return Err(string);
+                return Err(PtyError::spawn(Error::from_win32()));
             }
 
             // Set the pseudoconsole information into the list
             if
-                !UpdateProcThreadAttribute(
+                UpdateProcThreadAttribute(
                     start_info.lpAttributeList,
                     0,
                     0x00020016,
@@ -349,12 +329,9 @@ impl PTYImpl for ConPTY {
                     mem::size_of::<HPCON>(),
                     None,
                     None
-                ).is_ok()
+                ).is_err()
             {
-                result = Error::from_win32().into();
-                let result_msg = result.message();
-                let string = OsString::from(result_msg);
-                return Err(string);
+                return Err(PtyError::spawn(Error::from_win32()));
             }
 
             self.startup_info = start_info;
@@ -362,13 +339,19 @@ impl PTYImpl for ConPTY {
             let si_ptr_addr = si_ptr as usize;
             let si_w_ptr = si_ptr_addr as *const STARTUPINFOW;
 
+            // The process is put in the Job Object before it gets the chance
+            // to spawn any process of its own.
+            let tree = if self.kill_on_drop { Some(ProcessTree::new()?) } else { None };
+            let creation_flags = EXTENDED_STARTUPINFO_PRESENT | CREATE_UNICODE_ENVIRONMENT;
+            let creation_flags = if tree.is_some() { creation_flags | CREATE_SUSPENDED } else { creation_flags };
+
             let succ = CreateProcessW(
                 PCWSTR(ptr::null_mut()),
                 Some(PWSTR(cmd)),
                 None,
                 None,
                 false,
-                EXTENDED_STARTUPINFO_PRESENT | CREATE_UNICODE_ENVIRONMENT,
+                creation_flags,
                 Some(environ as _),
                 PCWSTR(working_dir),
                 si_w_ptr.as_ref().unwrap(),
@@ -376,59 +359,74 @@ impl PTYImpl for ConPTY {
             ).is_ok();
 
             if !succ {
-                result = Error::from_win32().into();
-                let result_msg = result.message();
-                let string = OsString::from(result_msg);
-                return Err(string);
+                return Err(PtyError::spawn(Error::from_win32()));
+            }
+
+            if let Some(tree) = tree {
+                if let Err(err) = tree.add(self.process_info.hProcess.into()) {
+                    let _ = TerminateProcess(self.process_info.hProcess, 1);
+                    return Err(PtyError::spawn(err));
+                }
+                ResumeThread(self.process_info.hThread);
+                self.tree = Some(tree);
             }
 
-            self.process.set_process(self.process_info.hProcess, false);
+            self.process.set_process(self.process_info.hProcess.into(), false);
             Ok(true)
         }
     }
 
-    fn set_size(&self, cols: i32, rows: i32) -> Result<(), OsString> {
-        if cols <= 0 || rows <= 0 {
-            let err: OsString = OsString::from(
-                format!(
-                    "PTY cols and rows must be positive and non-zero. Got: ({}, {})",
-                    cols,
-                    rows
-                )
-            );
-            return Err(err);
-        }
+    fn set_size(&self, cols: i32, rows: i32) -> Result<(), PtyError> {
+        PtyError::check_size(cols, rows)?;
 
         let size = COORD { X: cols as i16, Y: rows as i16 };
         unsafe {
             match ResizePseudoConsole(self.handle, size) {
                 Ok(_) => Ok(()),
                 Err(err) => {
-                    let result_msg = err.message();
-                    let string = OsString::from(result_msg);
-                    Err(string)
+                    Err(err.into())
                 }
             }
         }
     }
 
-    fn read(&self, length: u32, blocking: bool) -> Result<OsString, OsString> {
+    fn set_output_buffer(&self, capacity: usize, policy: OverflowPolicy) -> Result<(), PtyError> {
+        self.process.set_output_buffer(capacity, policy)
+    }
+
+    fn output_stats(&self) -> Result<OutputStats, PtyError> {
+        Ok(self.process.output_stats())
+    }
+
+    fn read(&self, length: u32, blocking: bool) -> Result<ReadOutcome<OsString>, PtyError> {
         self.process.read(length, blocking)
     }
 
-    fn write(&self, buf: OsString) -> Result<u32, OsString> {
+    fn read_bytes(&self, buf: &mut [u8], blocking: bool) -> Result<ReadOutcome<usize>, PtyError> {
+        self.process.read_bytes(buf, blocking)
+    }
+
+    fn subscribe(&self) -> Result<mpsc::Receiver<PTYEvent>, PtyError> {
+        self.process.subscribe()
+    }
+
+    fn write(&self, buf: OsString) -> Result<u32, PtyError> {
         self.process.write(buf)
     }
 
-    fn is_eof(&self) -> Result<bool, OsString> {
+    fn write_bytes(&self, buf: &[u8]) -> Result<u32, PtyError> {
+        self.process.write_bytes(buf)
+    }
+
+    fn is_eof(&self) -> Result<bool, PtyError> {
         self.process.is_eof()
     }
 
-    fn get_exitstatus(&self) -> Result<Option<u32>, OsString> {
+    fn get_exitstatus(&self) -> Result<Option<u32>, PtyError> {
         self.process.get_exitstatus()
     }
 
-    fn is_alive(&self) -> Result<bool, OsString> {
+    fn is_alive(&self) -> Result<bool, PtyError> {
         self.process.is_alive()
     }
 
@@ -440,13 +438,43 @@ impl PTYImpl for ConPTY {
         self.process.get_fd()
     }
 
-    fn wait_for_exit(&self) -> Result<bool, OsString> {
+    fn wait_for_exit(&self) -> Result<bool, PtyError> {
         self.process.wait_for_exit()
     }
+
+    fn wait_timeout(&self, timeout: Duration) -> Result<bool, PtyError> {
+        self.process.wait_timeout(timeout)
+    }
+
+    fn exit_status(&self) -> Result<Option<ExitStatus>, PtyError> {
+        self.process.exit_status()
+    }
+
+    fn set_kill_on_drop(&mut self, enabled: bool) -> Result<(), PtyError> {
+        self.kill_on_drop = enabled;
+        Ok(())
+    }
+
+    fn signal(&self, signal: PtySignal) -> Result<(), PtyError> {
+        match signal {
+            // The pseudoconsole turns the Ctrl+C keystroke into a CTRL_C_EVENT.
+            PtySignal::Interrupt | PtySignal::Terminate => {
+                if self.process.is_alive()? {
+                    self.process.write_bytes(b"\x03")?;
+                }
+                Ok(())
+            },
+            PtySignal::Kill => self.process.signal(signal)
+        }
+    }
 }
 
 impl Drop for ConPTY {
     fn drop(&mut self) {
+        // Kill the processes first, so that closing the pseudoconsole does
+        // not wait on any of them.
+        self.tree = None;
+
         unsafe {
             if !self.process_info.hThread.is_invalid() {
                 let _ = CloseHandle(self.process_info.hThread);
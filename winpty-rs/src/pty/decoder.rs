@@ -0,0 +1,147 @@
+//! Streaming UTF-8 decoder used to turn the output of a process into text.
+//!
+//! The output is read in chunks of arbitrary size, so a multibyte character
+//! may be split between two of them. [`Utf8Decoder`] keeps an incomplete
+//! sequence at the end of the received bytes until the rest of it arrives.
+
+use std::cmp::min;
+use std::collections::VecDeque;
+
+/// Largest number of bytes a UTF-16 unit is decoded from: a three-byte
+/// character, or an invalid sequence replaced with `U+FFFD`.
+const MAX_UNIT_WIDTH: usize = 3;
+
+/// Width in bytes of the sequence started by `byte`, zero if it cannot start one.
+fn sequence_width(byte: u8) -> usize {
+    match byte {
+        0x00..=0x7f => 1,
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => 0,
+    }
+}
+
+/// Stateful UTF-8 decoder, characters are counted in UTF-16 units as the
+/// [`super::PTY::read`] length is.
+///
+/// # Examples
+/// ```
+/// use winptyrs::pty::Utf8Decoder;
+///
+/// let mut decoder = Utf8Decoder::new();
+/// decoder.push(b"\xe4\xbd");
+/// assert_eq!(decoder.decode(10, false), "");
+/// decoder.push(b"\xa0\xe5\xa5\xbd");
+/// assert_eq!(decoder.decode(10, false), "你好");
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct Utf8Decoder {
+    /// Bytes received and not consumed yet, consumed from the front without
+    /// moving the rest of them.
+    pending: VecDeque<u8>,
+}
+
+impl Utf8Decoder {
+    /// Create an empty decoder.
+    pub fn new() -> Utf8Decoder {
+        Utf8Decoder::default()
+    }
+
+    /// Append the next chunk of bytes to decode.
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.pending.extend(bytes);
+    }
+
+    /// Number of bytes received and not consumed yet.
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// `true` if every byte received has been consumed.
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Number of bytes that can be decoded without waiting for more input,
+    /// that is, every byte but an incomplete sequence at the end.
+    pub fn available(&self) -> usize {
+        self.pending.len() - self.incomplete_tail()
+    }
+
+    /// Decode up to `length` characters, counted in UTF-16 units.
+    ///
+    /// # Arguments
+    /// * `length` - Upper limit on the number of UTF-16 units to return.
+    /// * `finished` - `true` if no more bytes will be pushed, so that an
+    ///   incomplete sequence at the end is decoded instead of kept.
+    ///
+    /// # Returns
+    /// The decoded text, invalid sequences are replaced with `U+FFFD`.
+    pub fn decode(&mut self, length: usize, finished: bool) -> String {
+        let end = if finished { self.pending.len() } else { self.available() };
+        // Only the bytes that may be returned are looked at, one more character
+        // makes sure that the last of them is not cut.
+        let window = min(end, length.saturating_add(1).saturating_mul(MAX_UNIT_WIDTH + 1));
+        let bytes: Vec<u8> = self.pending.range(..window).copied().collect();
+        let mut text = String::new();
+        let mut units = 0;
+        let mut consumed = 0;
+        'chunks: for chunk in bytes.utf8_chunks() {
+            for character in chunk.valid().chars() {
+                if units + character.len_utf16() > length {
+                    break 'chunks;
+                }
+                units += character.len_utf16();
+                consumed += character.len_utf8();
+                text.push(character);
+            }
+            if !chunk.invalid().is_empty() {
+                if units == length {
+                    break;
+                }
+                units += 1;
+                consumed += chunk.invalid().len();
+                text.push(char::REPLACEMENT_CHARACTER);
+            }
+        }
+        self.pending.drain(..consumed);
+        text
+    }
+
+    /// Remove up to `length` bytes without decoding them.
+    pub fn take_bytes(&mut self, length: usize) -> Vec<u8> {
+        let length = min(length, self.pending.len());
+        self.pending.drain(..length).collect()
+    }
+
+    /// Remove at least `length` bytes without decoding them, along with the
+    /// rest of a character the removal cuts, so that the pending bytes start
+    /// on a character boundary again.
+    ///
+    /// # Returns
+    /// The number of bytes removed.
+    pub fn drop_bytes(&mut self, length: usize) -> usize {
+        let mut length = min(length, self.pending.len());
+        // A character has at most three continuation bytes (0b10xx_xxxx).
+        let limit = min(length + 3, self.pending.len());
+        while length < limit && self.pending[length] & 0xc0 == 0x80 {
+            length += 1;
+        }
+        self.pending.drain(..length);
+        length
+    }
+
+    /// Length of the incomplete sequence at the end of the pending bytes.
+    fn incomplete_tail(&self) -> usize {
+        let len = self.pending.len();
+        for start in (len.saturating_sub(3)..len).rev() {
+            let byte = self.pending[start];
+            if byte & 0xc0 != 0x80 {
+                let width = sequence_width(byte);
+                return if len - start < width { len - start } else { 0 };
+            }
+        }
+        0
+    }
+}
@@ -0,0 +1,175 @@
+//! Error type returned by every PTY operation.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+#[cfg(windows)]
+use windows::core::HRESULT;
+
+use super::PTYBackend;
+
+/// Error raised by a [`super::PTY`], its backends and [`super::PTYProcess`].
+///
+/// # Examples
+/// ```
+/// use winptyrs::{PTY, PTYArgs, PTYBackend, PtyError, MouseMode, AgentConfig};
+///
+/// let pty_args = PTYArgs {
+///     cols: 0,
+///     rows: 25,
+///     mouse_mode: MouseMode::WINPTY_MOUSE_MODE_NONE,
+///     timeout: 10000,
+///     agent_config: AgentConfig::WINPTY_FLAG_COLOR_ESCAPES
+/// };
+///
+/// match PTY::new_with_backend(&pty_args, PTYBackend::Pipes) {
+///     Err(PtyError::InvalidSize { cols, rows }) => assert_eq!((cols, rows), (0, 25)),
+///     _ => unreachable!(),
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PtyError {
+    /// The backend cannot be used, e.g., it was not compiled in, the platform
+    /// does not support it, or its library could not be loaded.
+    BackendUnavailable {
+        /// Backend that was requested.
+        backend: PTYBackend,
+        /// Human-readable reason.
+        reason: String,
+    },
+    /// The number of columns or rows is not valid.
+    InvalidSize {
+        /// Number of columns requested.
+        cols: i32,
+        /// Number of rows requested.
+        rows: i32,
+    },
+    /// The process could not be spawned.
+    Spawn {
+        /// Operating system error code, if the operating system reported the failure.
+        code: Option<i32>,
+        /// Human-readable description.
+        message: String,
+    },
+    /// An I/O operation on the PTY or on the process failed.
+    Io {
+        /// Operating system error code, if the operating system reported the failure.
+        code: Option<i32>,
+        /// Human-readable description.
+        message: String,
+    },
+    /// The standard output reached EOF: the process has exited and all of
+    /// its output has been read.
+    Eof,
+    /// The operation did not complete in time.
+    Timeout,
+    /// No process has been spawned inside the PTY.
+    NotSpawned,
+    /// The output of the process is delivered to a subscriber, see
+    /// [`super::PTY::subscribe`].
+    Subscribed,
+    /// Output was dropped because the output buffer was full, see
+    /// [`super::OverflowPolicy::Error`].
+    Overflow {
+        /// Number of bytes dropped since the last time it was reported.
+        dropped: u64,
+    },
+}
+
+impl PtyError {
+    /// Error for a backend that cannot be used.
+    pub(crate) fn unavailable(backend: PTYBackend, reason: &str) -> PtyError {
+        PtyError::BackendUnavailable { backend, reason: reason.to_owned() }
+    }
+
+    /// Error for an I/O failure described only by a message.
+    pub(crate) fn other(message: impl Into<String>) -> PtyError {
+        PtyError::Io { code: None, message: message.into() }
+    }
+
+    /// Error for the last operating system failure of the calling thread.
+    /// Only the Unix backends need it, the Windows API returns its errors.
+    #[cfg(unix)]
+    pub(crate) fn last_os_error() -> PtyError {
+        io::Error::last_os_error().into()
+    }
+
+    /// Report an I/O failure as the reason why the process could not be spawned.
+    pub(crate) fn spawn(err: impl Into<PtyError>) -> PtyError {
+        match err.into() {
+            PtyError::Io { code, message } => PtyError::Spawn { code, message },
+            err => err,
+        }
+    }
+
+    /// Check that the PTY size is valid, as every backend requires.
+    pub(crate) fn check_size(cols: i32, rows: i32) -> Result<(), PtyError> {
+        if cols <= 0 || rows <= 0 {
+            return Err(PtyError::InvalidSize { cols, rows });
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for PtyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PtyError::BackendUnavailable { backend, reason } =>
+                write!(f, "The {:?} backend is not available: {}", backend, reason),
+            PtyError::InvalidSize { cols, rows } =>
+                write!(f, "PTY cols and rows must be positive and non-zero. Got: ({}, {})", cols, rows),
+            PtyError::Spawn { message, .. } => write!(f, "Could not spawn the process: {}", message),
+            PtyError::Io { message, .. } => f.write_str(message),
+            PtyError::Eof => f.write_str("Standard out reached EOF"),
+            PtyError::Timeout => f.write_str("The operation timed out"),
+            PtyError::NotSpawned => f.write_str("No process has been spawned inside the PTY"),
+            PtyError::Subscribed => f.write_str("The output of the process is delivered to a subscriber"),
+            PtyError::Overflow { dropped } =>
+                write!(f, "The output buffer is full, {} bytes of output were dropped", dropped),
+        }
+    }
+}
+
+impl Error for PtyError {}
+
+impl From<io::Error> for PtyError {
+    fn from(err: io::Error) -> PtyError {
+        PtyError::Io { code: err.raw_os_error(), message: err.to_string() }
+    }
+}
+
+#[cfg(windows)]
+impl From<HRESULT> for PtyError {
+    fn from(result: HRESULT) -> PtyError {
+        // Report the Win32 error code when the HRESULT wraps one, as io::Error does.
+        let code = if (result.0 as u32) & 0xffff_0000 == 0x8007_0000 {
+            result.0 & 0xffff
+        } else {
+            result.0
+        };
+        PtyError::Io { code: Some(code), message: result.message() }
+    }
+}
+
+#[cfg(windows)]
+impl From<windows::core::Error> for PtyError {
+    fn from(err: windows::core::Error) -> PtyError {
+        err.code().into()
+    }
+}
+
+impl From<PtyError> for io::Error {
+    fn from(err: PtyError) -> io::Error {
+        let kind = match &err {
+            PtyError::Io { code: Some(code), .. } | PtyError::Spawn { code: Some(code), .. } =>
+                io::Error::from_raw_os_error(*code).kind(),
+            PtyError::Eof => io::ErrorKind::UnexpectedEof,
+            PtyError::Timeout => io::ErrorKind::TimedOut,
+            PtyError::InvalidSize { .. } => io::ErrorKind::InvalidInput,
+            PtyError::BackendUnavailable { .. } => io::ErrorKind::Unsupported,
+            _ => io::ErrorKind::Other,
+        };
+        io::Error::new(kind, err)
+    }
+}
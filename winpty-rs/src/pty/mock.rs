@@ -0,0 +1,10 @@
+//! This module provides a [`super::PTY`] backend whose process is simulated in
+//! memory, either by a Rust closure or by a [`MockScript`], which can also be
+//! loaded from a recorded session file.
+//! This backend is available on every platform and is meant for testing.
+
+mod pty_impl;
+mod script;
+
+pub use pty_impl::{MockPTY, MockIO, MockProgram};
+pub use script::{MockScript, MockStep};
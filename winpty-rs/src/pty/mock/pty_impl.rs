@@ -0,0 +1,404 @@
+//! Simulated PTY implementation.
+
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+#[cfg(windows)]
+use std::os::windows::io::FromRawHandle;
+#[cfg(unix)]
+use std::os::fd::{FromRawFd, OwnedFd};
+
+use crate::pty::{PTYArgs, PTYChild, PTYEvent, PTYImpl, PTYProcess, PtyError, PtySignal, ExitStatus, ReadOutcome, OverflowPolicy, OutputStats};
+use crate::pty::base::{anonymous_pipe, LocalHandle};
+use crate::pty::status::SIGKILL;
+use super::script::MockScript;
+
+/// Synthetic process IDs handed to simulated processes.
+static NEXT_PID: AtomicU32 = AtomicU32::new(1);
+
+/// Take ownership of one side of a pipe created by [`anonymous_pipe`].
+fn into_file(handle: LocalHandle) -> File {
+    #[cfg(unix)]
+    unsafe { File::from(OwnedFd::from_raw_fd(handle.0)) }
+    #[cfg(windows)]
+    unsafe { File::from_raw_handle(handle.0) }
+}
+
+fn window_size(cols: i32, rows: i32) -> Result<(i32, i32), PtyError> {
+    PtyError::check_size(cols, rows)?;
+    Ok((cols, rows))
+}
+
+/// Body of a simulated process, it receives the streams of the PTY and
+/// returns the exit code of the process.
+pub type MockFn = Box<dyn FnOnce(&mut MockIO) -> u32 + Send>;
+
+/// Program run by a [`MockPTY`] in place of a real process.
+pub enum MockProgram {
+    /// Replay a scripted session.
+    Script(MockScript),
+    /// Run an arbitrary closure.
+    Function(MockFn),
+}
+
+impl MockProgram {
+    /// Simulate a process using a closure.
+    pub fn from_fn<F>(function: F) -> MockProgram
+    where F: FnOnce(&mut MockIO) -> u32 + Send + 'static {
+        MockProgram::Function(Box::new(function))
+    }
+
+    fn run(self, io: &mut MockIO) -> u32 {
+        match self {
+            MockProgram::Script(script) => script.run(io),
+            MockProgram::Function(function) => function(io)
+        }
+    }
+}
+
+impl From<MockScript> for MockProgram {
+    fn from(script: MockScript) -> Self {
+        MockProgram::Script(script)
+    }
+}
+
+/// Standard streams of a simulated process.
+pub struct MockIO {
+    /// Receives the bytes written into the PTY.
+    stdin: File,
+    /// Standard output of the process, it is taken away when the PTY is dropped.
+    stdout: Arc<Mutex<Option<File>>>,
+    /// Current size of the PTY.
+    size: Arc<Mutex<(i32, i32)>>,
+    /// Input that was read but not consumed yet.
+    input: Vec<u8>,
+}
+
+impl MockIO {
+    /// Emit output, as if the process wrote it into its standard output.
+    pub fn write(&mut self, output: &str) -> io::Result<()> {
+        self.write_bytes(output.as_bytes())
+    }
+
+    /// Emit raw bytes, which do not need to be valid UTF-8.
+    pub fn write_bytes(&mut self, output: &[u8]) -> io::Result<()> {
+        match self.stdout.lock().unwrap().as_mut() {
+            Some(stdout) => stdout.write_all(output),
+            None => Err(io::Error::from(io::ErrorKind::BrokenPipe))
+        }
+    }
+
+    /// Read the next bytes written into the PTY, `None` if the PTY was closed.
+    pub fn read(&mut self) -> Option<Vec<u8>> {
+        if self.input.is_empty() && !self.fill_input() {
+            return None;
+        }
+        Some(std::mem::take(&mut self.input))
+    }
+
+    /// Wait until the input written into the PTY contains `pattern`, the input
+    /// is consumed up to the end of the match.
+    ///
+    /// # Returns
+    /// `true` if the pattern was found, `false` if the PTY was closed first.
+    pub fn expect(&mut self, pattern: &str) -> bool {
+        let pattern = pattern.as_bytes();
+        loop {
+            let position = self.input.windows(pattern.len().max(1))
+                .position(|window| window == pattern);
+            match position {
+                Some(position) => {
+                    self.input.drain(..position + pattern.len());
+                    return true;
+                }
+                None if pattern.is_empty() => return true,
+                None => {
+                    if !self.fill_input() {
+                        return false;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Pause the process for `millis` milliseconds.
+    pub fn sleep(&self, millis: u64) {
+        thread::sleep(Duration::from_millis(millis));
+    }
+
+    /// Retrieve the current size of the PTY as `(cols, rows)`.
+    pub fn size(&self) -> (i32, i32) {
+        *self.size.lock().unwrap()
+    }
+
+    fn fill_input(&mut self) -> bool {
+        let mut buf = [0u8; 4096];
+        loop {
+            match self.stdin.read(&mut buf) {
+                Ok(0) => return false,
+                Ok(n) => {
+                    self.input.extend_from_slice(&buf[..n]);
+                    return true;
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => return false
+            }
+        }
+    }
+}
+
+/// Simulated process tracked by a [`PTYProcess`].
+struct MockChild {
+    pid: u32,
+    status: Mutex<Option<ExitStatus>>,
+    exited: Condvar,
+}
+
+impl MockChild {
+    fn exit(&self, status: ExitStatus) {
+        let mut current = self.status.lock().unwrap();
+        // A killed process keeps the status it was given.
+        if current.is_none() {
+            *current = Some(status);
+        }
+        self.exited.notify_all();
+    }
+}
+
+impl PTYChild for MockChild {
+    fn is_alive(&self) -> Result<bool, PtyError> {
+        Ok(self.status.lock().unwrap().is_none())
+    }
+
+    fn get_exitstatus(&self) -> Result<Option<u32>, PtyError> {
+        Ok(self.status.lock().unwrap().map(|status| status.exit_code()))
+    }
+
+    fn wait_for_exit(&self) -> Result<bool, PtyError> {
+        let mut status = self.status.lock().unwrap();
+        while status.is_none() {
+            status = self.exited.wait(status).unwrap();
+        }
+        Ok(true)
+    }
+
+    fn wait_timeout(&self, timeout: Duration) -> Result<bool, PtyError> {
+        let status = self.status.lock().unwrap();
+        let (status, _) = self.exited.wait_timeout_while(status, timeout, |status| status.is_none()).unwrap();
+        Ok(status.is_some())
+    }
+
+    fn exit_status(&self) -> Result<Option<ExitStatus>, PtyError> {
+        Ok(*self.status.lock().unwrap())
+    }
+
+    fn get_pid(&self) -> u32 {
+        self.pid
+    }
+
+    fn get_fd(&self) -> isize {
+        -1
+    }
+}
+
+/// Struct that simulates a process inside a pseudoterminal. The I/O goes
+/// through real pipes, so the [`PTYProcess`] caching and EOF handling are
+/// the same as in the other backends.
+///
+/// An interrupt or a termination request reaches the program as a Ctrl+C
+/// keystroke (`\x03`) in its input, while killing it ends the process right
+/// away, as `SIGKILL` does (exit code 137).
+pub struct MockPTY {
+    /// Program to run on spawn, if none was given the session file passed
+    /// to spawn is replayed.
+    program: Option<MockProgram>,
+    /// Read side of the process standard input, handed to the process on spawn.
+    stdin: Option<File>,
+    /// Write side of the process standard output.
+    stdout: Arc<Mutex<Option<File>>>,
+    /// Current size of the PTY.
+    size: Arc<Mutex<(i32, i32)>>,
+    /// Simulated process, once spawned.
+    child: Option<Arc<MockChild>>,
+    process: PTYProcess,
+}
+
+impl MockPTY {
+    /// Create a mock PTY that runs `program` when a process is spawned.
+    pub fn new_with_program(args: &PTYArgs, program: MockProgram) -> Result<Box<dyn PTYImpl>, PtyError> {
+        let mut pty = MockPTY::create(args)?;
+        pty.program = Some(program);
+        Ok(Box::new(pty))
+    }
+
+    fn create(args: &PTYArgs) -> Result<MockPTY, PtyError> {
+        let size = window_size(args.cols, args.rows)?;
+        let (stdin_read, stdin_write) = anonymous_pipe()?;
+        let (stdout_read, stdout_write) = anonymous_pipe()?;
+
+        Ok(MockPTY {
+            program: None,
+            stdin: Some(into_file(stdin_read)),
+            stdout: Arc::new(Mutex::new(Some(into_file(stdout_write)))),
+            size: Arc::new(Mutex::new(size)),
+            child: None,
+            process: PTYProcess::new(stdin_write, stdout_read, true),
+        })
+    }
+}
+
+impl PTYImpl for MockPTY {
+    fn new(args: &PTYArgs) -> Result<Box<dyn PTYImpl>, PtyError> {
+        Ok(Box::new(MockPTY::create(args)?))
+    }
+
+    fn spawn(&mut self, appname: OsString, _cmdline: Option<OsString>, cwd: Option<OsString>, _env: Option<OsString>) -> Result<bool, PtyError> {
+        let stdin = match self.stdin.take() {
+            Some(stdin) => stdin,
+            None => return Err(PtyError::Spawn {
+                code: None,
+                message: String::from("A process was already spawned inside this PTY")
+            })
+        };
+
+        let program = match self.program.take() {
+            Some(program) => program,
+            None => {
+                let mut session = PathBuf::from(cwd.unwrap_or_default());
+                session.push(appname);
+                MockProgram::Script(MockScript::from_file(session).map_err(PtyError::spawn)?)
+            }
+        };
+
+        let child = Arc::new(MockChild {
+            pid: NEXT_PID.fetch_add(1, Ordering::Relaxed),
+            status: Mutex::new(None),
+            exited: Condvar::new(),
+        });
+
+        let mut io = MockIO {
+            stdin,
+            stdout: self.stdout.clone(),
+            size: self.size.clone(),
+            input: Vec::new(),
+        };
+        let process = child.clone();
+        thread::spawn(move || {
+            let code = program.run(&mut io);
+            // Exit before closing the output, so that EOF is only reported
+            // once everything that was written has been read.
+            process.exit(ExitStatus::Exited(code));
+            io.stdout.lock().unwrap().take();
+        });
+
+        self.child = Some(child.clone());
+        self.process.set_child(child);
+        Ok(true)
+    }
+
+    fn set_size(&self, cols: i32, rows: i32) -> Result<(), PtyError> {
+        *self.size.lock().unwrap() = window_size(cols, rows)?;
+        Ok(())
+    }
+
+    fn set_output_buffer(&self, capacity: usize, policy: OverflowPolicy) -> Result<(), PtyError> {
+        self.process.set_output_buffer(capacity, policy)
+    }
+
+    fn output_stats(&self) -> Result<OutputStats, PtyError> {
+        Ok(self.process.output_stats())
+    }
+
+    fn read(&self, length: u32, blocking: bool) -> Result<ReadOutcome<OsString>, PtyError> {
+        self.process.read(length, blocking)
+    }
+
+    fn read_bytes(&self, buf: &mut [u8], blocking: bool) -> Result<ReadOutcome<usize>, PtyError> {
+        self.process.read_bytes(buf, blocking)
+    }
+
+    fn subscribe(&self) -> Result<mpsc::Receiver<PTYEvent>, PtyError> {
+        self.process.subscribe()
+    }
+
+    fn write(&self, buf: OsString) -> Result<u32, PtyError> {
+        self.process.write(buf)
+    }
+
+    fn write_bytes(&self, buf: &[u8]) -> Result<u32, PtyError> {
+        self.process.write_bytes(buf)
+    }
+
+    fn is_eof(&self) -> Result<bool, PtyError> {
+        self.process.is_eof()
+    }
+
+    fn get_exitstatus(&self) -> Result<Option<u32>, PtyError> {
+        self.process.get_exitstatus()
+    }
+
+    fn is_alive(&self) -> Result<bool, PtyError> {
+        self.process.is_alive()
+    }
+
+    fn get_pid(&self) -> u32 {
+        self.process.get_pid()
+    }
+
+    fn get_fd(&self) -> isize {
+        self.process.get_fd()
+    }
+
+    fn wait_for_exit(&self) -> Result<bool, PtyError> {
+        self.process.wait_for_exit()
+    }
+
+    fn wait_timeout(&self, timeout: Duration) -> Result<bool, PtyError> {
+        self.process.wait_timeout(timeout)
+    }
+
+    fn exit_status(&self) -> Result<Option<ExitStatus>, PtyError> {
+        self.process.exit_status()
+    }
+
+    fn set_kill_on_drop(&mut self, _enabled: bool) -> Result<(), PtyError> {
+        // A simulated program never spawns processes of its own.
+        Ok(())
+    }
+
+    fn signal(&self, signal: PtySignal) -> Result<(), PtyError> {
+        let child = self.child.as_ref().ok_or(PtyError::NotSpawned)?;
+        if !child.is_alive()? {
+            return Ok(());
+        }
+        match signal {
+            PtySignal::Interrupt | PtySignal::Terminate => {
+                self.process.write_bytes(b"\x03")?;
+            },
+            PtySignal::Kill => {
+                // Killed as SIGKILL would, whatever the platform.
+                child.exit(ExitStatus::Signaled(SIGKILL));
+                // Nothing the program writes afterwards is output.
+                self.stdout.lock().unwrap().take();
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for MockPTY {
+    fn drop(&mut self) {
+        // Close the process output, so that the reading thread of the
+        // PTYProcess does not wait forever for it.
+        self.stdout.lock().unwrap().take();
+    }
+}
+
+unsafe impl Send for MockPTY {}
+unsafe impl Sync for MockPTY {}
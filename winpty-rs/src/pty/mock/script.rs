@@ -0,0 +1,210 @@
+//! Scripted sessions for the mock backend.
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::pty::PtyError;
+use super::pty_impl::MockIO;
+
+/// A single step of a [`MockScript`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MockStep {
+    /// Emit some output.
+    Output(String),
+    /// Wait for the given number of milliseconds.
+    Delay(u64),
+    /// Wait until the input written into the PTY contains the given text.
+    Expect(String),
+    /// Exit with the given code, the remaining steps are ignored.
+    Exit(u32),
+}
+
+/// Sequence of steps that a simulated process follows.
+///
+/// A script can be built in code, or loaded from a session file where each
+/// line is a step, identified by its first character:
+///
+/// ```text
+/// # Comments and blank lines are ignored.
+/// > Output text\r\n
+/// < input to wait for\r
+/// @ 250
+/// = 3
+/// ```
+///
+/// `>` emits output, `<` waits for input, `@` waits for some milliseconds
+/// and `=` exits with the given code. Text accepts the escapes `\r`, `\n`,
+/// `\t`, `\e` (escape), `\0`, `\\` and `\xHH`. A script that runs out of steps
+/// exits with code 0.
+///
+/// # Examples
+/// ```
+/// use winptyrs::pty::MockScript;
+///
+/// let script = MockScript::new()
+///     .expect("ping\r")
+///     .delay(10)
+///     .output("pong\r\n")
+///     .exit(3);
+///
+/// assert_eq!(MockScript::parse(&script.to_string()).unwrap(), script);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MockScript {
+    /// Steps of the script, in order.
+    pub steps: Vec<MockStep>,
+}
+
+impl MockScript {
+    /// Create an empty script.
+    pub fn new() -> MockScript {
+        MockScript::default()
+    }
+
+    /// Append a step that emits `output`.
+    pub fn output(mut self, output: &str) -> MockScript {
+        self.steps.push(MockStep::Output(output.to_owned()));
+        self
+    }
+
+    /// Append a step that waits for `millis` milliseconds.
+    pub fn delay(mut self, millis: u64) -> MockScript {
+        self.steps.push(MockStep::Delay(millis));
+        self
+    }
+
+    /// Append a step that waits until the input contains `input`.
+    pub fn expect(mut self, input: &str) -> MockScript {
+        self.steps.push(MockStep::Expect(input.to_owned()));
+        self
+    }
+
+    /// Append a step that exits with `code`.
+    pub fn exit(mut self, code: u32) -> MockScript {
+        self.steps.push(MockStep::Exit(code));
+        self
+    }
+
+    /// Parse a script from the contents of a session file.
+    pub fn parse(session: &str) -> Result<MockScript, PtyError> {
+        let mut script = MockScript::new();
+        for (number, line) in session.lines().enumerate() {
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            let mut chars = line.chars();
+            let marker = match chars.next() {
+                None | Some('#') => continue,
+                Some(marker) => marker
+            };
+            let rest = chars.as_str();
+            let value = rest.strip_prefix(' ').unwrap_or(rest);
+            let error = |reason: &str| PtyError::other(format!(
+                "Invalid mock session, line {}: {} ({:?})", number + 1, reason, line));
+
+            let step = match marker {
+                '>' => MockStep::Output(unescape(value).map_err(|err| error(&err))?),
+                '<' => MockStep::Expect(unescape(value).map_err(|err| error(&err))?),
+                '@' => MockStep::Delay(value.trim().parse().map_err(|_| error("invalid delay"))?),
+                '=' => MockStep::Exit(value.trim().parse().map_err(|_| error("invalid exit code"))?),
+                _ => return Err(error("unknown step"))
+            };
+            script.steps.push(step);
+        }
+        Ok(script)
+    }
+
+    /// Load a script from a session file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<MockScript, PtyError> {
+        let path = path.as_ref();
+        let session = fs::read_to_string(path).map_err(|err| PtyError::Io {
+            code: err.raw_os_error(),
+            message: format!("Could not read mock session {:?}: {}", path, err)
+        })?;
+        MockScript::parse(&session)
+    }
+
+    /// Run the script against the streams of a simulated process.
+    ///
+    /// # Returns
+    /// The exit code of the process.
+    pub fn run(&self, io: &mut MockIO) -> u32 {
+        for step in &self.steps {
+            match step {
+                MockStep::Output(output) => {
+                    if io.write(output).is_err() {
+                        break;
+                    }
+                }
+                MockStep::Delay(millis) => io.sleep(*millis),
+                MockStep::Expect(input) => {
+                    if !io.expect(input) {
+                        break;
+                    }
+                }
+                MockStep::Exit(code) => return *code
+            }
+        }
+        0
+    }
+}
+
+impl fmt::Display for MockScript {
+    /// Write the script using the session file format.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for step in &self.steps {
+            match step {
+                MockStep::Output(output) => writeln!(f, "> {}", escape(output))?,
+                MockStep::Delay(millis) => writeln!(f, "@ {}", millis)?,
+                MockStep::Expect(input) => writeln!(f, "< {}", escape(input))?,
+                MockStep::Exit(code) => writeln!(f, "= {}", code)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\r' => escaped.push_str("\\r"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\x1b' => escaped.push_str("\\e"),
+            '\0' => escaped.push_str("\\0"),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_ascii_control() => escaped.push_str(&format!("\\x{:02x}", c as u8)),
+            c => escaped.push(c)
+        }
+    }
+    escaped
+}
+
+fn unescape(text: &str) -> Result<String, String> {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('r') => unescaped.push('\r'),
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('e') => unescaped.push('\x1b'),
+            Some('0') => unescaped.push('\0'),
+            Some('\\') => unescaped.push('\\'),
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                match u8::from_str_radix(&hex, 16) {
+                    Ok(byte) if hex.len() == 2 && byte.is_ascii() => unescaped.push(byte as char),
+                    _ => return Err(format!("invalid escape \\x{}", hex))
+                }
+            }
+            Some(other) => return Err(format!("invalid escape \\{}", other)),
+            None => return Err(String::from("dangling backslash"))
+        }
+    }
+    Ok(unescaped)
+}
@@ -0,0 +1,8 @@
+//! This module provides a [`super::PTY`] backend that spawns the process with
+//! plain pipes as its standard streams, without any pseudoconsole.
+//! The standard error is kept separate from the standard output.
+//! This backend is available on every platform.
+
+mod pty_impl;
+
+pub use pty_impl::PipesPTY;
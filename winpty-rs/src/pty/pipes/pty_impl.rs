@@ -0,0 +1,272 @@
+//! Actual plain pipes implementation.
+
+use std::ffi::OsString;
+use std::process::{Command, Stdio};
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+
+#[cfg(windows)]
+use std::os::windows::io::{FromRawHandle, IntoRawHandle, OwnedHandle};
+#[cfg(windows)]
+use std::os::windows::process::CommandExt;
+#[cfg(windows)]
+use windows::Win32::System::Threading::TerminateProcess;
+#[cfg(unix)]
+use std::os::fd::{FromRawFd, OwnedFd, RawFd};
+#[cfg(unix)]
+use std::os::unix::process::CommandExt;
+
+use crate::pty::{PTYArgs, PTYEvent, PTYImpl, PTYProcess, PtyError, PtySignal, ExitStatus, ReadOutcome, OverflowPolicy, OutputStats};
+use crate::pty::base::{anonymous_pipe, parse_env_block, LocalHandle, OSProcess};
+use crate::pty::base::ProcessTree;
+#[cfg(unix)]
+use crate::pty::base::{reset_signal_mask, set_parent_death_signal};
+#[cfg(unix)]
+use crate::pty::cmdline::split_cmdline;
+
+/// Take ownership of one side of a pipe created by [`anonymous_pipe`].
+fn into_stdio(handle: LocalHandle) -> Stdio {
+    #[cfg(unix)]
+    unsafe { Stdio::from(OwnedFd::from_raw_fd(handle.0)) }
+    #[cfg(windows)]
+    unsafe { Stdio::from(OwnedHandle::from_raw_handle(handle.0)) }
+}
+
+/// Ask the process to finish, as a terminal would do when it is closed.
+#[cfg(unix)]
+fn terminate(process: LocalHandle) {
+    unsafe {
+        libc::kill(process.0, libc::SIGHUP);
+    }
+}
+
+/// Ask the process to finish, as a terminal would do when it is closed.
+#[cfg(windows)]
+fn terminate(process: LocalHandle) {
+    unsafe {
+        let _ = TerminateProcess(process.into(), 1);
+    }
+}
+
+/// Struct that holds a process spawned with plain pipes as its standard
+/// streams. The size of the "terminal" is validated but otherwise ignored.
+pub struct PipesPTY {
+    /// Processes spawned by the process, if `kill_on_drop` is set. Declared
+    /// first so that they are killed before the process is reaped.
+    tree: Option<ProcessTree>,
+    /// Whether every process spawned by the process is killed on drop.
+    kill_on_drop: bool,
+    /// Process sides of the standard input, output and error, handed to the process on spawn.
+    stdio: Option<(Stdio, Stdio, Stdio)>,
+    /// Handle to the spawned process, used to terminate it when the PTY is dropped.
+    child: Option<LocalHandle>,
+    /// Standard input and output of the process.
+    process: PTYProcess,
+    /// Standard error of the process.
+    stderr: PTYProcess,
+}
+
+impl PipesPTY {
+    /// Spawn `command`, which already holds the arguments, with the pipes as its standard streams.
+    fn spawn_command(&mut self, mut command: Command, cwd: Option<OsString>, env: Option<OsString>) -> Result<bool, PtyError> {
+        let (stdin, stdout, stderr) = match self.stdio.take() {
+            Some(stdio) => stdio,
+            None => return Err(PtyError::Spawn {
+                code: None,
+                message: String::from("A process was already spawned inside this PTY")
+            })
+        };
+
+        if let Some(env_opt) = env {
+            command.env_clear();
+            command.envs(parse_env_block(&env_opt));
+        }
+
+        if let Some(cwd_opt) = cwd {
+            command.current_dir(cwd_opt);
+        }
+
+        command.stdin(stdin).stdout(stdout).stderr(stderr);
+        #[cfg(unix)]
+        {
+            let kill_on_drop = self.kill_on_drop;
+            let parent = unsafe { libc::getpid() };
+            if kill_on_drop {
+                // A group of its own, which can be killed without the caller.
+                command.process_group(0);
+            }
+            unsafe {
+                command.pre_exec(move || {
+                    if kill_on_drop {
+                        set_parent_death_signal(parent)?;
+                    }
+                    reset_signal_mask()
+                });
+            }
+        }
+
+        // The process handle is closed (reaped on Unix) by the PTYProcess.
+        let child = command.spawn().map_err(PtyError::spawn)?;
+        #[cfg(unix)]
+        let handle: LocalHandle = (child.id() as RawFd).into();
+        #[cfg(windows)]
+        let handle = LocalHandle(child.into_raw_handle());
+
+        let process = Arc::new(OSProcess::new(handle, true));
+        self.child = Some(handle);
+        self.process.set_child(process.clone());
+        self.stderr.set_child(process);
+
+        if self.kill_on_drop {
+            #[cfg(unix)]
+            let tree = ProcessTree::new(handle);
+            // std cannot spawn a suspended process, so the processes it spawns
+            // before this point escape the Job Object.
+            #[cfg(windows)]
+            let tree = {
+                let tree = ProcessTree::new()?;
+                tree.add(handle)?;
+                tree
+            };
+            self.tree = Some(tree);
+        }
+        Ok(true)
+    }
+}
+
+impl PTYImpl for PipesPTY {
+    fn new(args: &PTYArgs) -> Result<Box<dyn PTYImpl>, PtyError> {
+        PtyError::check_size(args.cols, args.rows)?;
+        let (stdin_read, stdin_write) = anonymous_pipe()?;
+        let (stdout_read, stdout_write) = anonymous_pipe()?;
+        let (stderr_read, stderr_write) = anonymous_pipe()?;
+
+        let stdio = (into_stdio(stdin_read), into_stdio(stdout_write), into_stdio(stderr_write));
+        Ok(Box::new(PipesPTY {
+            tree: None,
+            kill_on_drop: false,
+            stdio: Some(stdio),
+            child: None,
+            process: PTYProcess::new(stdin_write, stdout_read, true),
+            stderr: PTYProcess::new(LocalHandle::null(), stderr_read, true),
+        }) as Box<dyn PTYImpl>)
+    }
+
+    fn spawn(&mut self, appname: OsString, cmdline: Option<OsString>, cwd: Option<OsString>, env: Option<OsString>) -> Result<bool, PtyError> {
+        #[cfg(unix)]
+        { self.spawn_args(appname, split_cmdline(cmdline), cwd, env) }
+        #[cfg(windows)]
+        {
+            let mut command = Command::new(&appname);
+            // Windows programs parse their own command line.
+            if let Some(cmdline_opt) = cmdline {
+                command.raw_arg(cmdline_opt);
+            }
+            self.spawn_command(command, cwd, env)
+        }
+    }
+
+    #[cfg(unix)]
+    fn spawn_args(&mut self, appname: OsString, args: Vec<OsString>, cwd: Option<OsString>, env: Option<OsString>) -> Result<bool, PtyError> {
+        let mut command = Command::new(&appname);
+        command.args(args);
+        self.spawn_command(command, cwd, env)
+    }
+
+    fn set_kill_on_drop(&mut self, enabled: bool) -> Result<(), PtyError> {
+        self.kill_on_drop = enabled;
+        Ok(())
+    }
+
+    fn set_size(&self, cols: i32, rows: i32) -> Result<(), PtyError> {
+        PtyError::check_size(cols, rows)
+    }
+
+    fn set_output_buffer(&self, capacity: usize, policy: OverflowPolicy) -> Result<(), PtyError> {
+        self.process.set_output_buffer(capacity, policy)?;
+        self.stderr.set_output_buffer(capacity, policy)
+    }
+
+    fn output_stats(&self) -> Result<OutputStats, PtyError> {
+        Ok(self.process.output_stats())
+    }
+
+    fn read(&self, length: u32, blocking: bool) -> Result<ReadOutcome<OsString>, PtyError> {
+        self.process.read(length, blocking)
+    }
+
+    fn read_bytes(&self, buf: &mut [u8], blocking: bool) -> Result<ReadOutcome<usize>, PtyError> {
+        self.process.read_bytes(buf, blocking)
+    }
+
+    fn subscribe(&self) -> Result<mpsc::Receiver<PTYEvent>, PtyError> {
+        self.process.subscribe()
+    }
+
+    fn read_stderr(&self, length: u32, blocking: bool) -> Result<ReadOutcome<OsString>, PtyError> {
+        self.stderr.read(length, blocking)
+    }
+
+    fn read_stderr_bytes(&self, buf: &mut [u8], blocking: bool) -> Result<ReadOutcome<usize>, PtyError> {
+        self.stderr.read_bytes(buf, blocking)
+    }
+
+    fn write(&self, buf: OsString) -> Result<u32, PtyError> {
+        self.process.write(buf)
+    }
+
+    fn write_bytes(&self, buf: &[u8]) -> Result<u32, PtyError> {
+        self.process.write_bytes(buf)
+    }
+
+    fn is_eof(&self) -> Result<bool, PtyError> {
+        self.process.is_eof()
+    }
+
+    fn get_exitstatus(&self) -> Result<Option<u32>, PtyError> {
+        self.process.get_exitstatus()
+    }
+
+    fn is_alive(&self) -> Result<bool, PtyError> {
+        self.process.is_alive()
+    }
+
+    fn get_pid(&self) -> u32 {
+        self.process.get_pid()
+    }
+
+    fn get_fd(&self) -> isize {
+        self.process.get_fd()
+    }
+
+    fn wait_for_exit(&self) -> Result<bool, PtyError> {
+        self.process.wait_for_exit()
+    }
+
+    fn wait_timeout(&self, timeout: Duration) -> Result<bool, PtyError> {
+        self.process.wait_timeout(timeout)
+    }
+
+    fn exit_status(&self) -> Result<Option<ExitStatus>, PtyError> {
+        self.process.exit_status()
+    }
+
+    fn signal(&self, signal: PtySignal) -> Result<(), PtyError> {
+        self.process.signal(signal)
+    }
+}
+
+impl Drop for PipesPTY {
+    fn drop(&mut self) {
+        // Nothing else would close the output pipes of a running process,
+        // which the reading threads of the PTYProcess may be waiting on.
+        if let Some(child) = self.child {
+            if self.process.is_alive().unwrap_or(false) {
+                terminate(child);
+            }
+        }
+    }
+}
+
+unsafe impl Send for PipesPTY {}
+unsafe impl Sync for PipesPTY {}
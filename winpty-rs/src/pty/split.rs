@@ -0,0 +1,168 @@
+//! Independent halves of a [`PTY`], obtained through [`PTY::split`].
+//!
+//! The halves share the same pseudoterminal without any lock, so that the
+//! output can be read on one thread while the input is written on another.
+
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::{PTY, PTYBackend, PtyError, ExitStatus, ReadOutcome};
+
+/// Reading half of a [`PTY`], it implements [`io::Read`] over the raw output
+/// of the process.
+///
+/// A read blocks until some output is available, and returns `Ok(0)` once
+/// the process has exited and all its output was read.
+pub struct PtyReader {
+    pty: Arc<PTY>,
+}
+
+/// Writing half of a [`PTY`], it implements [`io::Write`] over the standard
+/// input of the process.
+pub struct PtyWriter {
+    pty: Arc<PTY>,
+}
+
+/// Controlling half of a [`PTY`], used to resize it and to query the state
+/// of the process while the other halves are busy.
+#[derive(Clone)]
+pub struct PtyControl {
+    pty: Arc<PTY>,
+}
+
+impl PTY {
+    /// Split the pseudoterminal into its reading, writing and controlling halves.
+    ///
+    /// Each half can be sent to a different thread. The process must be spawned
+    /// before splitting the PTY, since the halves cannot spawn processes.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::ffi::OsString;
+    /// use std::io::{Read, Write};
+    /// use winptyrs::{PTY, PTYArgs, MouseMode, AgentConfig, MockProgram};
+    ///
+    /// let pty_args = PTYArgs {
+    ///     cols: 80,
+    ///     rows: 25,
+    ///     mouse_mode: MouseMode::WINPTY_MOUSE_MODE_NONE,
+    ///     timeout: 10000,
+    ///     agent_config: AgentConfig::WINPTY_FLAG_COLOR_ESCAPES
+    /// };
+    ///
+    /// let mut pty = PTY::new_mock(&pty_args, MockProgram::from_fn(|io| {
+    ///     io.expect("exit\r");
+    ///     io.write("bye\r\n").unwrap();
+    ///     0
+    /// })).unwrap();
+    /// pty.spawn(OsString::from("mock"), None, None, None).unwrap();
+    ///
+    /// let (mut reader, mut writer, control) = pty.split();
+    /// writer.write_all(b"exit\r").unwrap();
+    ///
+    /// let mut output = String::new();
+    /// reader.read_to_string(&mut output).unwrap();
+    /// assert_eq!(output, "bye\r\n");
+    /// assert_eq!(control.get_exitstatus().unwrap(), Some(0));
+    /// ```
+    pub fn split(self) -> (PtyReader, PtyWriter, PtyControl) {
+        let pty = Arc::new(self);
+        (
+            PtyReader { pty: pty.clone() },
+            PtyWriter { pty: pty.clone() },
+            PtyControl { pty },
+        )
+    }
+}
+
+impl io::Read for PtyReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        match self.pty.try_read_bytes(buf, true) {
+            Ok(ReadOutcome::Data(n)) => Ok(n),
+            Ok(ReadOutcome::WouldBlock) => Err(io::ErrorKind::WouldBlock.into()),
+            Ok(ReadOutcome::Eof) => Ok(0),
+            Err(err) => Err(err.into())
+        }
+    }
+}
+
+impl io::Write for PtyWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pty.write_bytes(buf).map(|n| n as usize).map_err(io::Error::from)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl PtyControl {
+    /// Change the PTY size, see [`PTY::set_size`].
+    pub fn set_size(&self, cols: i32, rows: i32) -> Result<(), PtyError> {
+        self.pty.set_size(cols, rows)
+    }
+
+    /// Get the backend used by the PTY.
+    pub fn get_backend(&self) -> PTYBackend {
+        self.pty.get_backend()
+    }
+
+    /// Check if the process reached End-of-File (EOF), see [`PTY::is_eof`].
+    pub fn is_eof(&self) -> Result<bool, PtyError> {
+        self.pty.is_eof()
+    }
+
+    /// Retrieve the exit status of the process, see [`PTY::get_exitstatus`].
+    pub fn get_exitstatus(&self) -> Result<Option<u32>, PtyError> {
+        self.pty.get_exitstatus()
+    }
+
+    /// Determine if the process is still alive.
+    pub fn is_alive(&self) -> Result<bool, PtyError> {
+        self.pty.is_alive()
+    }
+
+    /// Retrieve the Process ID associated to the current process.
+    pub fn get_pid(&self) -> u32 {
+        self.pty.get_pid()
+    }
+
+    /// Retrieve the process handle ID of the spawned program.
+    pub fn get_fd(&self) -> isize {
+        self.pty.get_fd()
+    }
+
+    /// Wait for the process to exit/finish.
+    pub fn wait_for_exit(&self) -> Result<bool, PtyError> {
+        self.pty.wait_for_exit()
+    }
+
+    /// Wait at most `timeout` for the process to exit, see [`PTY::wait_timeout`].
+    pub fn wait_timeout(&self, timeout: Duration) -> Result<Option<ExitStatus>, PtyError> {
+        self.pty.wait_timeout(timeout)
+    }
+
+    /// Retrieve how the process ended, see [`PTY::exit_status`].
+    pub fn exit_status(&self) -> Result<Option<ExitStatus>, PtyError> {
+        self.pty.exit_status()
+    }
+
+    /// Interrupt the process, see [`PTY::send_interrupt`].
+    pub fn send_interrupt(&self) -> Result<(), PtyError> {
+        self.pty.send_interrupt()
+    }
+
+    /// Stop the process right away, see [`PTY::kill`].
+    pub fn kill(&self) -> Result<(), PtyError> {
+        self.pty.kill()
+    }
+
+    /// Ask the process to exit and kill it after `grace`, see [`PTY::terminate`].
+    pub fn terminate(&self, grace: Duration) -> Result<bool, PtyError> {
+        self.pty.terminate(grace)
+    }
+}
@@ -0,0 +1,88 @@
+//! Structured exit status of a process spawned inside a PTY.
+
+use std::fmt;
+
+/// Signal number of `SIGKILL`, the same on every Unix system.
+pub(crate) const SIGKILL: i32 = 9;
+
+/// Exit code given to `TerminateProcess` when a process is killed on Windows.
+pub(crate) const KILLED_EXIT_CODE: u32 = 1;
+
+/// How a process spawned inside a PTY ended.
+///
+/// # Examples
+/// ```
+/// use winptyrs::ExitStatus;
+///
+/// assert!(ExitStatus::Exited(0).success());
+/// assert_eq!(ExitStatus::Signaled(15).exit_code(), 143);
+/// assert_eq!(ExitStatus::NtStatus(0xC0000005).to_string(), "crashed with NTSTATUS 0xC0000005");
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ExitStatus {
+    /// The process exited on its own with this exit code.
+    Exited(u32),
+    /// The process was terminated by this signal (Unix).
+    Signaled(i32),
+    /// The process ended with this NTSTATUS error code, usually because it
+    /// crashed, e.g., `0xC0000005` for an access violation (Windows).
+    NtStatus(u32),
+    /// The process was killed through [`super::PTY::kill`] or [`super::PTY::terminate`].
+    Killed,
+}
+
+impl ExitStatus {
+    /// Classify the exit code of a Windows process, NTSTATUS error codes have
+    /// both of their severity bits set.
+    pub(crate) fn from_code(code: u32) -> ExitStatus {
+        if cfg!(windows) && code & 0xC000_0000 == 0xC000_0000 {
+            ExitStatus::NtStatus(code)
+        } else {
+            ExitStatus::Exited(code)
+        }
+    }
+
+    /// `true` if the process exited on its own with a zero exit code.
+    pub fn success(&self) -> bool {
+        *self == ExitStatus::Exited(0)
+    }
+
+    /// Exit code of a process that exited on its own.
+    pub fn code(&self) -> Option<u32> {
+        match self {
+            ExitStatus::Exited(code) => Some(*code),
+            _ => None
+        }
+    }
+
+    /// Signal that terminated the process, if any.
+    pub fn signal(&self) -> Option<i32> {
+        match self {
+            ExitStatus::Signaled(signal) => Some(*signal),
+            _ => None
+        }
+    }
+
+    /// Single exit code describing the status, as [`super::PTY::get_exitstatus`]
+    /// reports it: a signal is 128 plus its number, as shells do, and a killed
+    /// process has the code `SIGKILL` or `TerminateProcess` leaves.
+    pub fn exit_code(&self) -> u32 {
+        match self {
+            ExitStatus::Exited(code) | ExitStatus::NtStatus(code) => *code,
+            ExitStatus::Signaled(signal) => 128 + *signal as u32,
+            ExitStatus::Killed if cfg!(windows) => KILLED_EXIT_CODE,
+            ExitStatus::Killed => 128 + SIGKILL as u32
+        }
+    }
+}
+
+impl fmt::Display for ExitStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExitStatus::Exited(code) => write!(f, "exited with code {}", code),
+            ExitStatus::Signaled(signal) => write!(f, "terminated by signal {}", signal),
+            ExitStatus::NtStatus(code) => write!(f, "crashed with NTSTATUS {:#010X}", code),
+            ExitStatus::Killed => f.write_str("killed")
+        }
+    }
+}
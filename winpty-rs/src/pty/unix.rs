@@ -0,0 +1,20 @@
+//! This module provides a [`super::PTY`] backend that uses the Unix pseudoterminal
+//! interface (`openpty`, `fork`, `setsid` and `exec`) as its implementation.
+//! This backend is only available on Unix platforms.
+
+// Actual implementation if running on Unix
+#[cfg(unix)]
+mod pty_impl;
+
+#[cfg(unix)]
+pub use pty_impl::UnixPTY;
+
+#[cfg(all(unix, feature = "tokio"))]
+pub(crate) use pty_impl::{open_pty, pty_command, resize_pty, signal_pty};
+
+// Default implementation if not running on Unix
+#[cfg(not(unix))]
+mod default_impl;
+
+#[cfg(not(unix))]
+pub use default_impl::UnixPTY;
@@ -0,0 +1,90 @@
+use std::ffi::OsString;
+use std::sync::mpsc;
+use std::time::Duration;
+
+// Default implementation if not running on Unix
+use crate::pty::{PTYArgs, PTYBackend, PTYEvent, PTYImpl, PtyError, PtySignal, ExitStatus, ReadOutcome, OverflowPolicy, OutputStats};
+
+pub struct UnixPTY {}
+
+impl PTYImpl for UnixPTY {
+    fn new(_args: &PTYArgs) -> Result<Box<dyn PTYImpl>, PtyError> {
+        Err(PtyError::unavailable(PTYBackend::Unix, "The Unix PTY backend is only available on Unix platforms"))
+    }
+
+    fn spawn(&mut self, _appname: OsString, _cmdline: Option<OsString>, _cwd: Option<OsString>, _env: Option<OsString>) -> Result<bool, PtyError> {
+        Err(PtyError::unavailable(PTYBackend::Unix, "The Unix PTY backend is only available on Unix platforms"))
+    }
+
+    fn set_kill_on_drop(&mut self, _enabled: bool) -> Result<(), PtyError> {
+        Err(PtyError::unavailable(PTYBackend::Unix, "The Unix PTY backend is only available on Unix platforms"))
+    }
+
+    fn set_output_buffer(&self, _capacity: usize, _policy: OverflowPolicy) -> Result<(), PtyError> {
+        Err(PtyError::unavailable(PTYBackend::Unix, "The Unix PTY backend is only available on Unix platforms"))
+    }
+
+    fn output_stats(&self) -> Result<OutputStats, PtyError> {
+        Err(PtyError::unavailable(PTYBackend::Unix, "The Unix PTY backend is only available on Unix platforms"))
+    }
+
+    fn set_size(&self, _cols: i32, _rows: i32) -> Result<(), PtyError> {
+        Err(PtyError::unavailable(PTYBackend::Unix, "The Unix PTY backend is only available on Unix platforms"))
+    }
+
+    fn read(&self, _length: u32, _blocking: bool) -> Result<ReadOutcome<OsString>, PtyError> {
+        Err(PtyError::unavailable(PTYBackend::Unix, "The Unix PTY backend is only available on Unix platforms"))
+    }
+
+    fn read_bytes(&self, _buf: &mut [u8], _blocking: bool) -> Result<ReadOutcome<usize>, PtyError> {
+        Err(PtyError::unavailable(PTYBackend::Unix, "The Unix PTY backend is only available on Unix platforms"))
+    }
+
+    fn subscribe(&self) -> Result<mpsc::Receiver<PTYEvent>, PtyError> {
+        Err(PtyError::unavailable(PTYBackend::Unix, "The Unix PTY backend is only available on Unix platforms"))
+    }
+
+    fn write(&self, _buf: OsString) -> Result<u32, PtyError> {
+        Err(PtyError::unavailable(PTYBackend::Unix, "The Unix PTY backend is only available on Unix platforms"))
+    }
+
+    fn write_bytes(&self, _buf: &[u8]) -> Result<u32, PtyError> {
+        Err(PtyError::unavailable(PTYBackend::Unix, "The Unix PTY backend is only available on Unix platforms"))
+    }
+
+    fn is_eof(&self) -> Result<bool, PtyError> {
+        Err(PtyError::unavailable(PTYBackend::Unix, "The Unix PTY backend is only available on Unix platforms"))
+    }
+
+    fn get_exitstatus(&self) -> Result<Option<u32>, PtyError> {
+        Err(PtyError::unavailable(PTYBackend::Unix, "The Unix PTY backend is only available on Unix platforms"))
+    }
+
+    fn is_alive(&self) -> Result<bool, PtyError> {
+        Err(PtyError::unavailable(PTYBackend::Unix, "The Unix PTY backend is only available on Unix platforms"))
+    }
+
+    fn get_pid(&self) -> u32 {
+        0
+    }
+
+    fn get_fd(&self) -> isize {
+        -1
+    }
+
+    fn wait_for_exit(&self) -> Result<bool, PtyError> {
+        Err(PtyError::unavailable(PTYBackend::Unix, "The Unix PTY backend is only available on Unix platforms"))
+    }
+
+    fn wait_timeout(&self, _timeout: Duration) -> Result<bool, PtyError> {
+        Err(PtyError::unavailable(PTYBackend::Unix, "The Unix PTY backend is only available on Unix platforms"))
+    }
+
+    fn exit_status(&self) -> Result<Option<ExitStatus>, PtyError> {
+        Err(PtyError::unavailable(PTYBackend::Unix, "The Unix PTY backend is only available on Unix platforms"))
+    }
+
+    fn signal(&self, _signal: PtySignal) -> Result<(), PtyError> {
+        Err(PtyError::unavailable(PTYBackend::Unix, "The Unix PTY backend is only available on Unix platforms"))
+    }
+}
@@ -0,0 +1,322 @@
+//! Actual Unix PTY implementation.
+
+use std::ffi::OsString;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, IntoRawFd, OwnedFd, RawFd};
+use std::os::unix::process::CommandExt;
+use std::process::{Command, Stdio};
+use std::ptr;
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+
+use crate::pty::{PTYProcess, PTYImpl, PTYEvent, PtyError, PtySignal, ExitStatus, ReadOutcome, OverflowPolicy, OutputStats};
+use crate::pty::{AgentConfig, PTYArgs};
+use crate::pty::base::{anonymous_pipe, parse_env_block, reset_signal_mask, set_parent_death_signal, LocalHandle, OSProcess, ProcessTree};
+use crate::pty::cmdline::split_cmdline;
+
+fn set_cloexec(fd: RawFd) -> Result<(), PtyError> {
+    unsafe {
+        let flags = libc::fcntl(fd, libc::F_GETFD);
+        if flags < 0 || libc::fcntl(fd, libc::F_SETFD, flags | libc::FD_CLOEXEC) < 0 {
+            return Err(PtyError::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+fn window_size(cols: i32, rows: i32) -> Result<libc::winsize, PtyError> {
+    PtyError::check_size(cols, rows)?;
+
+    Ok(libc::winsize {
+        ws_row: rows as u16,
+        ws_col: cols as u16,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    })
+}
+
+/// Open a new pseudoterminal of the requested size.
+///
+/// # Returns
+/// The master and slave sides, neither of them is inherited by spawned processes.
+pub(crate) fn open_pty(args: &PTYArgs) -> Result<(OwnedFd, OwnedFd), PtyError> {
+    let mut size = window_size(args.cols, args.rows)?;
+    let mut master: RawFd = -1;
+    let mut slave: RawFd = -1;
+
+    unsafe {
+        if libc::openpty(&mut master, &mut slave, ptr::null_mut(), ptr::null_mut(), ptr::addr_of_mut!(size)) < 0 {
+            return Err(PtyError::last_os_error());
+        }
+
+        let master = OwnedFd::from_raw_fd(master);
+        let slave = OwnedFd::from_raw_fd(slave);
+
+        // Neither side should leak into the spawned process, the slave is
+        // explicitly attached to its standard streams.
+        set_cloexec(master.as_raw_fd())?;
+        set_cloexec(slave.as_raw_fd())?;
+        Ok((master, slave))
+    }
+}
+
+/// Change the size of the pseudoterminal behind `master`.
+pub(crate) fn resize_pty(master: RawFd, cols: i32, rows: i32) -> Result<(), PtyError> {
+    let size = window_size(cols, rows)?;
+    if unsafe { libc::ioctl(master, libc::TIOCSWINSZ, &size) } < 0 {
+        return Err(PtyError::last_os_error());
+    }
+    Ok(())
+}
+
+/// Send `signal` to the foreground process group of the pseudoterminal behind
+/// `master`, as the terminal does for Ctrl+C. Terminating and killing also
+/// reach the process group of the process spawned with `pid`, in case a job
+/// of its own holds the foreground.
+pub(crate) fn signal_pty(master: RawFd, pid: u32, signal: PtySignal) -> Result<(), PtyError> {
+    let leader = pid as libc::pid_t;
+    let foreground = unsafe { libc::tcgetpgrp(master) };
+
+    let mut groups = Vec::new();
+    if foreground > 0 {
+        groups.push(foreground);
+    }
+    if (foreground <= 0 || signal != PtySignal::Interrupt) && foreground != leader {
+        groups.push(leader);
+    }
+
+    for group in groups {
+        if unsafe { libc::kill(-group, signal.number()) } < 0 {
+            let err = io::Error::last_os_error();
+            // The group may have exited in the meantime.
+            if err.raw_os_error() != Some(libc::ESRCH) {
+                return Err(err.into());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Prepare a command that runs inside the pseudoterminal behind `slave`,
+/// as the leader of a new session. With `kill_on_drop` the command is killed
+/// as soon as the calling thread exits, on Linux.
+pub(crate) fn pty_command(slave: &OwnedFd, appname: OsString, args: Vec<OsString>, cwd: Option<OsString>, env: Option<OsString>, kill_on_drop: bool) -> Result<Command, PtyError> {
+    let mut command = Command::new(&appname);
+    command.args(args);
+
+    if let Some(env_opt) = env {
+        command.env_clear();
+        command.envs(parse_env_block(&env_opt));
+    }
+
+    if let Some(cwd_opt) = cwd {
+        command.current_dir(cwd_opt);
+    }
+
+    let stdio = || slave.try_clone().map(Stdio::from).map_err(PtyError::spawn);
+    command.stdin(stdio()?).stdout(stdio()?).stderr(stdio()?);
+
+    let parent = unsafe { libc::getpid() };
+    unsafe {
+        command.pre_exec(move || {
+            if kill_on_drop {
+                set_parent_death_signal(parent)?;
+            }
+            // Start a new session and make the PTY its controlling terminal.
+            if libc::setsid() < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            if libc::ioctl(0, libc::TIOCSCTTY as _, 0) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            reset_signal_mask()
+        });
+    }
+
+    Ok(command)
+}
+
+/// Struct that holds both sides of a Unix pseudoterminal, as well as the
+/// process spawned inside it.
+///
+/// With [`AgentConfig::WINPTY_FLAG_CONERR`], the standard error of the process
+/// is a pipe instead of the pseudoterminal, read through [`PTYImpl::read_stderr`].
+pub struct UnixPTY {
+    /// Session of the process, if `kill_on_drop` is set. Declared first so that
+    /// it is killed before the process is reaped and its ID can be reused.
+    tree: Option<ProcessTree>,
+    /// Whether every process spawned by the process is killed on drop.
+    kill_on_drop: bool,
+    /// Master side of the pseudoterminal, used to resize it.
+    master: OwnedFd,
+    /// Slave side of the pseudoterminal, handed to the process on spawn.
+    slave: Option<OwnedFd>,
+    process: PTYProcess,
+    /// Process side of the standard error pipe, handed to the process on spawn.
+    stderr_pipe: Option<OwnedFd>,
+    /// Standard error of the process, if it is kept separate.
+    stderr: Option<PTYProcess>,
+}
+
+impl PTYImpl for UnixPTY {
+    fn new(args: &PTYArgs) -> Result<Box<dyn PTYImpl>, PtyError> {
+        let (master, slave) = open_pty(args)?;
+
+        let conin = master.try_clone()?;
+        let conout = master.try_clone()?;
+        let process = PTYProcess::new(
+            conin.into_raw_fd().into(),
+            conout.into_raw_fd().into(),
+            false
+        );
+
+        let (stderr_pipe, stderr) = match args.agent_config.contains(AgentConfig::WINPTY_FLAG_CONERR) {
+            true => {
+                let (stderr_read, stderr_write) = anonymous_pipe()?;
+                let stderr_write = unsafe { OwnedFd::from_raw_fd(stderr_write.0) };
+                (Some(stderr_write), Some(PTYProcess::new(LocalHandle::null(), stderr_read, true)))
+            }
+            false => (None, None),
+        };
+
+        Ok(Box::new(UnixPTY {
+            tree: None,
+            kill_on_drop: false,
+            master,
+            slave: Some(slave),
+            process,
+            stderr_pipe,
+            stderr,
+        }) as Box<dyn PTYImpl>)
+    }
+
+    fn spawn(&mut self, appname: OsString, cmdline: Option<OsString>, cwd: Option<OsString>, env: Option<OsString>) -> Result<bool, PtyError> {
+        self.spawn_args(appname, split_cmdline(cmdline), cwd, env)
+    }
+
+    fn spawn_args(&mut self, appname: OsString, args: Vec<OsString>, cwd: Option<OsString>, env: Option<OsString>) -> Result<bool, PtyError> {
+        let slave = match self.slave.take() {
+            Some(slave) => slave,
+            None => return Err(PtyError::Spawn {
+                code: None,
+                message: String::from("A process was already spawned inside this PTY")
+            })
+        };
+
+        let mut command = pty_command(&slave, appname, args, cwd, env, self.kill_on_drop)?;
+        if let Some(stderr) = self.stderr_pipe.take() {
+            command.stderr(stderr);
+        }
+
+        // The child is reaped by the PTYProcess, so the std handle can be dropped.
+        let child = command.spawn().map_err(PtyError::spawn)?;
+        let handle = (child.id() as RawFd).into();
+        if self.kill_on_drop {
+            // The process leads a new session, and thus a process group of the same ID.
+            self.tree = Some(ProcessTree::new(handle));
+        }
+        let process = Arc::new(OSProcess::new(handle, true));
+        self.process.set_child(process.clone());
+        if let Some(stderr) = self.stderr.as_mut() {
+            stderr.set_child(process);
+        }
+        Ok(true)
+    }
+
+    fn set_kill_on_drop(&mut self, enabled: bool) -> Result<(), PtyError> {
+        self.kill_on_drop = enabled;
+        Ok(())
+    }
+
+    fn set_size(&self, cols: i32, rows: i32) -> Result<(), PtyError> {
+        resize_pty(self.master.as_raw_fd(), cols, rows)
+    }
+
+    fn set_output_buffer(&self, capacity: usize, policy: OverflowPolicy) -> Result<(), PtyError> {
+        self.process.set_output_buffer(capacity, policy)?;
+        match &self.stderr {
+            Some(stderr) => stderr.set_output_buffer(capacity, policy),
+            None => Ok(()),
+        }
+    }
+
+    fn output_stats(&self) -> Result<OutputStats, PtyError> {
+        Ok(self.process.output_stats())
+    }
+
+    fn read(&self, length: u32, blocking: bool) -> Result<ReadOutcome<OsString>, PtyError> {
+        self.process.read(length, blocking)
+    }
+
+    fn read_stderr(&self, length: u32, blocking: bool) -> Result<ReadOutcome<OsString>, PtyError> {
+        match &self.stderr {
+            Some(stderr) => stderr.read(length, blocking),
+            None => Err(PtyError::other("The standard error is only separate with WINPTY_FLAG_CONERR")),
+        }
+    }
+
+    fn read_stderr_bytes(&self, buf: &mut [u8], blocking: bool) -> Result<ReadOutcome<usize>, PtyError> {
+        match &self.stderr {
+            Some(stderr) => stderr.read_bytes(buf, blocking),
+            None => Err(PtyError::other("The standard error is only separate with WINPTY_FLAG_CONERR")),
+        }
+    }
+
+    fn read_bytes(&self, buf: &mut [u8], blocking: bool) -> Result<ReadOutcome<usize>, PtyError> {
+        self.process.read_bytes(buf, blocking)
+    }
+
+    fn subscribe(&self) -> Result<mpsc::Receiver<PTYEvent>, PtyError> {
+        self.process.subscribe()
+    }
+
+    fn write(&self, buf: OsString) -> Result<u32, PtyError> {
+        self.process.write(buf)
+    }
+
+    fn write_bytes(&self, buf: &[u8]) -> Result<u32, PtyError> {
+        self.process.write_bytes(buf)
+    }
+
+    fn is_eof(&self) -> Result<bool, PtyError> {
+        self.process.is_eof()
+    }
+
+    fn get_exitstatus(&self) -> Result<Option<u32>, PtyError> {
+        self.process.get_exitstatus()
+    }
+
+    fn is_alive(&self) -> Result<bool, PtyError> {
+        self.process.is_alive()
+    }
+
+    fn get_pid(&self) -> u32 {
+        self.process.get_pid()
+    }
+
+    fn get_fd(&self) -> isize {
+        self.process.get_fd()
+    }
+
+    fn wait_for_exit(&self) -> Result<bool, PtyError> {
+        self.process.wait_for_exit()
+    }
+
+    fn wait_timeout(&self, timeout: Duration) -> Result<bool, PtyError> {
+        self.process.wait_timeout(timeout)
+    }
+
+    fn exit_status(&self) -> Result<Option<ExitStatus>, PtyError> {
+        self.process.exit_status()
+    }
+
+    fn signal(&self, signal: PtySignal) -> Result<(), PtyError> {
+        if !self.process.is_alive()? {
+            return Ok(());
+        }
+        signal_pty(self.master.as_raw_fd(), self.process.get_pid(), signal)
+    }
+}
+
+unsafe impl Send for UnixPTY {}
+unsafe impl Sync for UnixPTY {}
@@ -1,30 +1,30 @@
-/// This module provides a [`super::PTY`] backend that uses
-/// [winpty](https://github.com/rprichard/winpty) as its implementation.
-/// This backend is useful as a fallback implementation to the newer ConPTY
-/// backend, which is only available on Windows 10 starting on build number 1809.
+//! This module provides a [`super::PTY`] backend that uses
+//! [winpty](https://github.com/rprichard/winpty) as its implementation.
+//! This backend is useful as a fallback implementation to the newer ConPTY
+//! backend, which is only available on Windows 10 starting on build number 1809.
 
 use bitflags::bitflags;
 use enum_primitive_derive::Primitive;
 
 // Actual implementation if winpty is available
-#[cfg(feature="winpty")]
+#[cfg(all(windows, feature="winpty"))]
 mod pty_impl;
 
-#[cfg(feature="winpty")]
+#[cfg(all(windows, feature="winpty"))]
 mod bindings;
 
-#[cfg(feature="winpty")]
+#[cfg(all(windows, feature="winpty"))]
 pub use pty_impl::WinPTY;
 
 // Default implementation if winpty is not available
-#[cfg(not(feature="winpty"))]
+#[cfg(not(all(windows, feature="winpty")))]
 mod default_impl;
 
-#[cfg(not(feature="winpty"))]
+#[cfg(not(all(windows, feature="winpty")))]
 pub use default_impl::WinPTY;
 
 ///  Mouse capture settings for the winpty backend.
-#[derive(Primitive)]
+#[derive(Primitive, Copy, Clone, Debug, PartialEq, Eq)]
 #[allow(non_camel_case_types)]
 pub enum MouseMode {
     /// QuickEdit mode is initially disabled, and the agent does not send mouse
@@ -46,6 +46,7 @@ pub enum MouseMode {
 
 bitflags! {
     /// General configuration settings for the winpty backend.
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
     pub struct AgentConfig: u64 {
         /// Create a new screen buffer (connected to the "conerr" terminal pipe) and
         /// pass it to child processes as the STDERR handle.  This flag also prevents
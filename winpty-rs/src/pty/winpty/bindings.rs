@@ -1,5 +1,5 @@
 #![allow(non_camel_case_types)]
-/// WinPTY C bindings.
+//! WinPTY C bindings.
 
 /*
  * Copyright (c) 2011-2016 Ryan Prichard
@@ -39,9 +39,14 @@ pub struct winpty_error_s {
 pub type winpty_error_t = winpty_error_s;
 pub type winpty_error_ptr_t = *mut winpty_error_t;
 
+/// The winpty-agent executable could not be found.
+pub const WINPTY_ERROR_AGENT_EXE_MISSING: u32 = 4;
+/// The agent did not answer in time.
+pub const WINPTY_ERROR_AGENT_TIMEOUT: u32 = 7;
+
 extern "C" {
     /// Gets the error code from the error object.
-    // pub fn winpty_error_code(err: *mut winpty_error_ptr_t) -> u32;
+    pub fn winpty_error_code(err: winpty_error_ptr_t) -> u32;
 
     /// Returns a textual representation of the error.  The string is freed when
     /// the error is freed.
@@ -114,7 +119,7 @@ extern "C" {
     /// `winpty_conerr_name` returns NULL unless `WINPTY_FLAG_CONERR` is specified.
     pub fn winpty_conin_name(wp: *mut winpty_t) -> *const u16;
     pub fn winpty_conout_name(wp: *mut winpty_t) -> *const u16;
-    // pub fn winpty_conerr_name(wp: *mut winpty_t) -> *const u16;
+    pub fn winpty_conerr_name(wp: *mut winpty_t) -> *const u16;
 }
 
 // winpty agent RPC call: process creation.
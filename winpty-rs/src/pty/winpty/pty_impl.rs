@@ -1,4 +1,4 @@
-/// Actual WinPTY backend implementation.
+//! Actual WinPTY backend implementation.
 
 use windows::Win32::Foundation::{HANDLE};
 use windows::core::{PCWSTR};
@@ -12,12 +12,15 @@ use std::ptr;
 use std::mem::MaybeUninit;
 use std::slice::from_raw_parts;
 use std::ffi::{OsString, c_void};
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
 use std::os::windows::prelude::*;
 use std::os::windows::ffi::OsStrExt;
 
 use super::bindings::*;
-use crate::pty::{PTYProcess, PTYImpl};
-use crate::pty::PTYArgs;
+use crate::pty::{PTYProcess, PTYImpl, PTYEvent, PTYBackend, PtyError, PtySignal, ExitStatus, ReadOutcome, OverflowPolicy, OutputStats};
+use crate::pty::{AgentConfig, PTYArgs};
+use crate::pty::base::{LocalHandle, OSProcess, ProcessTree};
 
 struct WinPTYPtr {
     ptr: *mut winpty_t,
@@ -39,7 +42,13 @@ impl WinPTYPtr {
         unsafe { winpty_conout_name(self.ptr) }
     }
 
-    pub fn spawn(&self, appname: *const u16, cmdline: *const u16, cwd: *const u16, env: *const u16) -> Result<HANDLE, OsString> {
+    /// Name of the pipe of the standard error, null unless the agent was
+    /// started with `WINPTY_FLAG_CONERR`.
+    pub fn get_conerr_name(&self) -> *const u16 {
+        unsafe { winpty_conerr_name(self.ptr) }
+    }
+
+    pub fn spawn(&self, appname: *const u16, cmdline: *const u16, cwd: *const u16, env: *const u16) -> Result<HANDLE, PtyError> {
         let mut err_ptr: winpty_error_ptr_t = ptr::null_mut();
         unsafe {
             let spawn_config = winpty_spawn_config_new(
@@ -51,7 +60,7 @@ impl WinPTYPtr {
                 &mut err_ptr as *mut winpty_error_ptr_t,
             );
             if spawn_config.is_null() {
-                return Err(get_error_message(&mut err_ptr as *mut winpty_error_ptr_t));
+                return Err(PtyError::spawn(get_error(&mut err_ptr as *mut winpty_error_ptr_t)));
             }
 
             err_ptr = ptr::null_mut();
@@ -68,13 +77,11 @@ impl WinPTYPtr {
                                     &mut err_ptr as *mut winpty_error_ptr_t);
             winpty_spawn_config_free(spawn_config);
             if !succ {
-                let wide_buf = format!(" os error {}", os_error)
-                    .encode_utf16()
-                    .collect::<Vec<_>>();
-                let os_err_str = OsString::from_wide(&wide_buf);
-                let mut error_msg = get_error_message(&mut err_ptr as *mut winpty_error_ptr_t);
-                error_msg.push(os_err_str);
-                return Err(error_msg);
+                let message = get_error_message(&mut err_ptr as *mut winpty_error_ptr_t);
+                return Err(PtyError::Spawn {
+                    code: if os_error != 0 { Some(os_error as i32) } else { None },
+                    message: message.to_string_lossy().into_owned()
+                });
             }
 
             handle_value.assume_init();
@@ -83,7 +90,7 @@ impl WinPTYPtr {
         }
     }
 
-    pub fn set_size(&self, cols: i32, rows: i32) -> Result<(), OsString> {
+    pub fn set_size(&self, cols: i32, rows: i32) -> Result<(), PtyError> {
         let mut err_ptr: winpty_error_ptr_t = ptr::null_mut();
         unsafe {
             let succ = winpty_set_size(
@@ -93,7 +100,7 @@ impl WinPTYPtr {
                 &mut err_ptr as *mut winpty_error_ptr_t,
             );
             if !succ {
-                return Err(get_error_message(&mut err_ptr as *mut winpty_error_ptr_t));
+                return Err(get_error(&mut err_ptr as *mut winpty_error_ptr_t));
             }
         }
         Ok(())
@@ -128,33 +135,53 @@ unsafe impl Sync for WinPTYPtr {}
 
 // fn from<'a>(_: &'a WinPTYPtr, handle: *const )
 
+/// Retrieve the message of a winpty error object, and free it.
 unsafe fn get_error_message(err_ptr: *mut winpty_error_ptr_t) -> OsString {
     let err_msg: *const u16 = winpty_error_msg(*err_ptr);
-    let mut size = 0;
-    let mut ptr = err_msg;
-    while *ptr != 0 {
-        size += 1;
-        ptr = ptr.wrapping_offset(1);
-
-    }
-    let msg_slice: &[u16] = from_raw_parts(err_msg, size);
-    if err_msg.is_null() {
-        OsString::from_wide(msg_slice)
-    } else {
-        winpty_error_free(*err_ptr);
+    let message = if err_msg.is_null() {
         OsString::from("Unknown error")
+    } else {
+        let mut size = 0;
+        let mut ptr = err_msg;
+        while *ptr != 0 {
+            size += 1;
+            ptr = ptr.wrapping_offset(1);
+        }
+        let msg_slice: &[u16] = from_raw_parts(err_msg, size);
+        OsString::from_wide(msg_slice)
+    };
+    winpty_error_free(*err_ptr);
+    message
+}
+
+/// Convert a winpty error object into a [`PtyError`], and free it.
+unsafe fn get_error(err_ptr: *mut winpty_error_ptr_t) -> PtyError {
+    let code = winpty_error_code(*err_ptr);
+    let message = get_error_message(err_ptr).to_string_lossy().into_owned();
+    match code {
+        WINPTY_ERROR_AGENT_TIMEOUT => PtyError::Timeout,
+        WINPTY_ERROR_AGENT_EXE_MISSING => PtyError::BackendUnavailable { backend: PTYBackend::WinPTY, reason: message },
+        _ => PtyError::other(message)
     }
 }
 
 
 /// FFi-safe wrapper around `winpty` library calls and objects.
 pub struct WinPTY {
+    /// Job Object holding the process and its descendants, if `kill_on_drop`
+    /// is set. Declared first so that they are killed before the agent is freed.
+    tree: Option<ProcessTree>,
+    /// Whether every process spawned by the process is killed on drop.
+    kill_on_drop: bool,
     ptr: WinPTYPtr,
-    process: PTYProcess
+    process: PTYProcess,
+    /// Standard error of the process, with `WINPTY_FLAG_CONERR`.
+    stderr: Option<PTYProcess>,
 }
 
 impl PTYImpl for WinPTY {
-    fn new(args: &PTYArgs) -> Result<Box<dyn PTYImpl>, OsString> {
+    fn new(args: &PTYArgs) -> Result<Box<dyn PTYImpl>, PtyError> {
+        PtyError::check_size(args.cols, args.rows)?;
         unsafe {
             //let mut err: Box<winpty_error_t> = Box::new_uninit();
             //let mut err_ptr: *mut winpty_error_t = &mut *err;
@@ -166,13 +193,7 @@ impl PTYImpl for WinPTY {
             //err.assume_init();
 
             if config.is_null() {
-                return Err(get_error_message(&mut err_ptr as *mut winpty_error_ptr_t));
-            }
-
-            if args.cols <= 0 || args.rows <= 0 {
-                let err: OsString = OsString::from(format!(
-                    "PTY cols and rows must be positive and non-zero. Got: ({}, {})", args.cols, args.rows));
-                return Err(err);
+                return Err(get_error(&mut err_ptr as *mut winpty_error_ptr_t));
             }
 
             winpty_config_set_initial_size(config, args.cols, args.rows);
@@ -187,7 +208,7 @@ impl PTYImpl for WinPTY {
             winpty_config_free(config);
 
             if pty_ref.is_null() {
-                return Err(get_error_message(&mut err_ptr as *mut winpty_error_ptr_t));
+                return Err(get_error(&mut err_ptr as *mut winpty_error_ptr_t));
             }
 
             let pty_ptr = WinPTYPtr { ptr: pty_ref };
@@ -196,14 +217,12 @@ impl PTYImpl for WinPTY {
             let conout_name = pty_ptr.get_conout_name();
 
             let conin_res = CreateFileW(
-                PCWSTR(conin_name as *const u16), FILE_GENERIC_WRITE.0, FILE_SHARE_NONE, None,
+                PCWSTR(conin_name), FILE_GENERIC_WRITE.0, FILE_SHARE_NONE, None,
                 OPEN_EXISTING, FILE_ATTRIBUTE_NORMAL, None
             );
 
             if let Err(err) = conin_res {
-                let result_msg = err.message();
-                let string = OsString::from(result_msg);
-                return Err(string);
+                return Err(err.into());
             }
 
             let conout_res = CreateFileW(
@@ -212,20 +231,28 @@ impl PTYImpl for WinPTY {
             );
 
             if let Err(err) = conout_res {
-                let result_msg = err.message();
-                let string = OsString::from(result_msg);
-                return Err(string);
+                return Err(err.into());
             }
 
             let conin = conin_res.unwrap();
             let conout = conout_res.unwrap();
 
+            let stderr = if args.agent_config.contains(AgentConfig::WINPTY_FLAG_CONERR) {
+                let conerr = CreateFileW(
+                    PCWSTR(pty_ptr.get_conerr_name()), FILE_GENERIC_READ.0, FILE_SHARE_NONE, None,
+                    OPEN_EXISTING, FILE_ATTRIBUTE_NORMAL, None
+                )?;
+                Some(PTYProcess::new(LocalHandle::null(), conerr.into(), false))
+            } else {
+                None
+            };
+
             let process = PTYProcess::new(conin.into(), conout.into(), false);
-            Ok(Box::new(WinPTY { ptr: pty_ptr, process }) as Box<dyn PTYImpl>)
+            Ok(Box::new(WinPTY { tree: None, kill_on_drop: false, ptr: pty_ptr, process, stderr }) as Box<dyn PTYImpl>)
         }
     }
 
-    fn spawn(&mut self, appname: OsString, cmdline: Option<OsString>, cwd: Option<OsString>, env: Option<OsString>) -> Result<bool, OsString> {
+    fn spawn(&mut self, appname: OsString, cmdline: Option<OsString>, cwd: Option<OsString>, env: Option<OsString>) -> Result<bool, PtyError> {
         let mut environ: *const u16 = ptr::null();
         let mut working_dir: *const u16 = ptr::null_mut();
         let mut cmd: *const u16 = ptr::null_mut();
@@ -260,7 +287,18 @@ impl PTYImpl for WinPTY {
         let app = app_oss_buf.as_ptr();
         match self.ptr.spawn(app, cmd, working_dir, environ) {
             Ok(handle) => {
-                self.process.set_process(handle, true);
+                let process = Arc::new(OSProcess::new(handle.into(), true));
+                self.process.set_child(process.clone());
+                if let Some(stderr) = self.stderr.as_mut() {
+                    stderr.set_child(process);
+                }
+                // The agent spawns the process, so it can only be put in the
+                // Job Object once it is already running.
+                if self.kill_on_drop {
+                    let tree = ProcessTree::new()?;
+                    tree.add(handle.into())?;
+                    self.tree = Some(tree);
+                }
                 Ok(true)
             },
             Err(err) => {
@@ -269,32 +307,71 @@ impl PTYImpl for WinPTY {
         }
     }
 
-    fn set_size(&self, cols: i32, rows: i32) -> Result<(), OsString> {
-        if cols <= 0 || rows <= 0 {
-            let err: OsString = OsString::from(format!(
-                "PTY cols and rows must be positive and non-zero. Got: ({}, {})", cols, rows));
-            return Err(err);
-        }
+    fn set_kill_on_drop(&mut self, enabled: bool) -> Result<(), PtyError> {
+        self.kill_on_drop = enabled;
+        Ok(())
+    }
+
+    fn set_size(&self, cols: i32, rows: i32) -> Result<(), PtyError> {
+        PtyError::check_size(cols, rows)?;
         self.ptr.set_size(cols, rows)
     }
 
-    fn read(&self, length: u32, blocking: bool) -> Result<OsString, OsString> {
+    fn set_output_buffer(&self, capacity: usize, policy: OverflowPolicy) -> Result<(), PtyError> {
+        self.process.set_output_buffer(capacity, policy)?;
+        match &self.stderr {
+            Some(stderr) => stderr.set_output_buffer(capacity, policy),
+            None => Ok(()),
+        }
+    }
+
+    fn output_stats(&self) -> Result<OutputStats, PtyError> {
+        Ok(self.process.output_stats())
+    }
+
+    fn read(&self, length: u32, blocking: bool) -> Result<ReadOutcome<OsString>, PtyError> {
         self.process.read(length, blocking)
     }
 
-    fn write(&self, buf: OsString) -> Result<u32, OsString> {
+    fn read_stderr(&self, length: u32, blocking: bool) -> Result<ReadOutcome<OsString>, PtyError> {
+        match &self.stderr {
+            Some(stderr) => stderr.read(length, blocking),
+            None => Err(PtyError::other("The standard error is only separate with WINPTY_FLAG_CONERR")),
+        }
+    }
+
+    fn read_stderr_bytes(&self, buf: &mut [u8], blocking: bool) -> Result<ReadOutcome<usize>, PtyError> {
+        match &self.stderr {
+            Some(stderr) => stderr.read_bytes(buf, blocking),
+            None => Err(PtyError::other("The standard error is only separate with WINPTY_FLAG_CONERR")),
+        }
+    }
+
+    fn read_bytes(&self, buf: &mut [u8], blocking: bool) -> Result<ReadOutcome<usize>, PtyError> {
+        self.process.read_bytes(buf, blocking)
+    }
+
+    fn subscribe(&self) -> Result<mpsc::Receiver<PTYEvent>, PtyError> {
+        self.process.subscribe()
+    }
+
+    fn write(&self, buf: OsString) -> Result<u32, PtyError> {
         self.process.write(buf)
     }
 
-    fn is_eof(&self) -> Result<bool, OsString> {
+    fn write_bytes(&self, buf: &[u8]) -> Result<u32, PtyError> {
+        self.process.write_bytes(buf)
+    }
+
+    fn is_eof(&self) -> Result<bool, PtyError> {
         self.process.is_eof()
     }
 
-    fn get_exitstatus(&self) -> Result<Option<u32>, OsString> {
+    fn get_exitstatus(&self) -> Result<Option<u32>, PtyError> {
         self.process.get_exitstatus()
     }
 
-    fn is_alive(&self) -> Result<bool, OsString> {
+    fn is_alive(&self) -> Result<bool, PtyError> {
         self.process.is_alive()
     }
 
@@ -306,9 +383,30 @@ impl PTYImpl for WinPTY {
         self.process.get_fd()
     }
 
-    fn wait_for_exit(&self) -> Result<bool, OsString> {
+    fn wait_for_exit(&self) -> Result<bool, PtyError> {
         self.process.wait_for_exit()
     }
+
+    fn wait_timeout(&self, timeout: Duration) -> Result<bool, PtyError> {
+        self.process.wait_timeout(timeout)
+    }
+
+    fn exit_status(&self) -> Result<Option<ExitStatus>, PtyError> {
+        self.process.exit_status()
+    }
+
+    fn signal(&self, signal: PtySignal) -> Result<(), PtyError> {
+        match signal {
+            // The winpty agent turns the Ctrl+C keystroke into a CTRL_C_EVENT.
+            PtySignal::Interrupt | PtySignal::Terminate => {
+                if self.process.is_alive()? {
+                    self.process.write_bytes(b"\x03")?;
+                }
+                Ok(())
+            },
+            PtySignal::Kill => self.process.signal(signal)
+        }
+    }
 }
 
 unsafe impl Send for WinPTY {}
@@ -0,0 +1,142 @@
+#![cfg(feature = "tokio")]
+
+use std::ffi::OsString;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use winptyrs::{PTY, PTYArgs, PTYBackend, MouseMode, AgentConfig, MockProgram};
+
+fn pty_args() -> PTYArgs {
+    PTYArgs {
+        cols: 80,
+        rows: 25,
+        mouse_mode: MouseMode::WINPTY_MOUSE_MODE_NONE,
+        timeout: 10000,
+        agent_config: AgentConfig::WINPTY_FLAG_COLOR_ESCAPES
+    }
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn spawn_read_write_async() {
+    let (mut reader, mut writer, control) = PTY::spawn_async(
+        &pty_args(), OsString::from("/bin/sh"), None, None, None).unwrap();
+    assert!(matches!(control.get_backend(), PTYBackend::Unix));
+    assert_ne!(control.get_pid(), 0);
+
+    writer.write_all(b"echo async $((40+2)); exit 6\n").await.unwrap();
+    let mut output = Vec::new();
+    reader.read_to_end(&mut output).await.unwrap();
+
+    assert!(String::from_utf8_lossy(&output).contains("async 42\r\n"));
+    assert_eq!(control.wait_for_exit().await.unwrap(), 6);
+    assert_eq!(control.get_exitstatus().unwrap(), Some(6));
+    assert!(!control.is_alive().unwrap());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn set_size_async() {
+    let (mut reader, mut writer, control) = PTY::spawn_async(
+        &pty_args(), OsString::from("/bin/sh"), None, None, None).unwrap();
+
+    control.set_size(100, 33).await.unwrap();
+    assert!(control.set_size(0, 33).await.is_err());
+    writer.write_all(b"stty size; exit\n").await.unwrap();
+
+    let mut output = Vec::new();
+    reader.read_to_end(&mut output).await.unwrap();
+    assert!(String::from_utf8_lossy(&output).contains("33 100"));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn many_sessions_on_one_thread_async() {
+    let mut sessions = Vec::new();
+    for i in 0..32 {
+        sessions.push(tokio::spawn(async move {
+            let (mut reader, _writer, control) = PTY::spawn_async(
+                &pty_args(), OsString::from("echo"), Some(OsString::from(format!("session{}", i))), None, None).unwrap();
+            let mut output = Vec::new();
+            reader.read_to_end(&mut output).await.unwrap();
+            assert_eq!(control.wait_for_exit().await.unwrap(), 0);
+            (i, String::from_utf8(output).unwrap())
+        }));
+    }
+    for session in sessions {
+        let (i, output) = session.await.unwrap();
+        assert_eq!(output, format!("session{}\r\n", i));
+    }
+}
+
+#[tokio::test]
+async fn split_blocking_backend_async() {
+    let (tx, rx) = mpsc::channel();
+    let mut pty = PTY::new_mock(&pty_args(), MockProgram::from_fn(move |io| {
+        io.expect("size\r");
+        tx.send(io.size()).unwrap();
+        io.write("abcdef").unwrap();
+        io.expect("exit\r");
+        4
+    })).unwrap();
+    pty.spawn(OsString::from("mock"), None, None, None).unwrap();
+    let (mut reader, mut writer, control) = pty.split_async();
+    assert!(matches!(control.get_backend(), PTYBackend::Mock));
+
+    control.set_size(90, 30).await.unwrap();
+    writer.write_all(b"size\r").await.unwrap();
+    writer.flush().await.unwrap();
+    assert_eq!(rx.recv().unwrap(), (90, 30));
+
+    // Read in pieces smaller than what the blocking pool returns.
+    let mut piece = [0u8; 2];
+    let mut output = Vec::new();
+    while output.len() < 6 {
+        let n = reader.read(&mut piece).await.unwrap();
+        output.extend_from_slice(&piece[..n]);
+    }
+    assert_eq!(output, b"abcdef");
+
+    writer.write_all(b"exit\r").await.unwrap();
+    writer.shutdown().await.unwrap();
+    assert_eq!(reader.read(&mut piece).await.unwrap(), 0);
+    assert_eq!(control.wait_for_exit().await.unwrap(), 4);
+    assert!(!control.is_alive().unwrap());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn kill_and_terminate_async() {
+    let (_reader, _writer, control) = PTY::spawn_async(
+        &pty_args(), OsString::from("sleep"), Some(OsString::from("100")), None, None).unwrap();
+    control.kill().unwrap();
+    assert_eq!(control.wait_for_exit().await.unwrap(), 128 + 9);
+    control.kill().unwrap();
+
+    let (mut reader, _writer, control) = PTY::spawn_async(
+        &pty_args(), OsString::from("/bin/sh"),
+        Some(OsString::from("-c \"trap '' TERM; echo ready; while :; do sleep 0.1; done\"")), None, None).unwrap();
+    let mut output = Vec::new();
+    while !String::from_utf8_lossy(&output).contains("ready") {
+        let mut buf = [0u8; 64];
+        let n = reader.read(&mut buf).await.unwrap();
+        output.extend_from_slice(&buf[..n]);
+    }
+    assert!(!control.terminate(Duration::from_millis(200)).await.unwrap());
+    assert_eq!(control.get_exitstatus().unwrap(), Some(128 + 9));
+}
+
+#[tokio::test]
+async fn send_interrupt_blocking_backend_async() {
+    let mut pty = PTY::new_mock(&pty_args(), MockProgram::from_fn(|io| {
+        io.expect("\x03");
+        130
+    })).unwrap();
+    pty.spawn(OsString::from("mock"), None, None, None).unwrap();
+    let (_reader, _writer, control) = pty.split_async();
+
+    control.send_interrupt().unwrap();
+    assert_eq!(control.wait_for_exit().await.unwrap(), 130);
+    assert!(control.terminate(Duration::from_secs(1)).await.unwrap());
+}
@@ -0,0 +1,184 @@
+use winptyrs::pty::cmdline::{join_args, join_command_line, parse_command_line, quote_arg, quote_program, split_args};
+
+/// Command lines and the arguments they hold, the program name excluded.
+/// The first ones are the examples of the Microsoft C runtime documentation.
+const SPLIT_CASES: &[(&str, &[&str])] = &[
+    (r#""a b c" d e"#, &["a b c", "d", "e"]),
+    (r#""ab\"c" "\\" d"#, &["ab\"c", "\\", "d"]),
+    (r#"a\\\b d"e f"g h"#, &["a\\\\\\b", "de fg", "h"]),
+    (r#"a\\\"b c d"#, &["a\\\"b", "c", "d"]),
+    (r#"a\\\\"b c" d e"#, &["a\\\\b c", "d", "e"]),
+    (r#"a"b"" c d"#, &["ab\" c d"]),
+    // Separators.
+    ("", &[]),
+    ("   ", &[]),
+    ("a", &["a"]),
+    ("  a  b  ", &["a", "b"]),
+    ("a\tb\t\tc", &["a", "b", "c"]),
+    ("a\nb", &["a\nb"]),
+    ("a\x0bb", &["a\x0bb"]),
+    // Quotes.
+    (r#""""#, &[""]),
+    (r#""" """#, &["", ""]),
+    (r#"a """#, &["a", ""]),
+    (r#""a b"#, &["a b"]),
+    (r#""a"b"c""#, &["abc"]),
+    (r#"a""b"#, &["ab"]),
+    (r#"a"""b"#, &["a\"b"]),
+    (r#""a""b""#, &["a\"b"]),
+    (r#""a"""b""#, &["a\"b"]),
+    (r#"""""#, &["\""]),
+    (r#""""""#, &["\""]),
+    (r#"" "a"#, &[" a"]),
+    (r#""a	b""#, &["a\tb"]),
+    // Backslashes.
+    (r"\", &["\\"]),
+    (r"\\", &["\\\\"]),
+    (r"a\b\\c", &["a\\b\\\\c"]),
+    (r#"\""#, &["\""]),
+    (r#"\\""#, &["\\"]),
+    (r#"\\\""#, &["\\\""]),
+    (r#"\\\\""#, &["\\\\"]),
+    (r#""\\""#, &["\\"]),
+    (r#""a\\" b"#, &["a\\", "b"]),
+    (r#""a\" b""#, &["a\" b"]),
+    (r#"C:\path\ "C:\Program Files\\""#, &["C:\\path\\", "C:\\Program Files\\"]),
+    // Not ASCII.
+    ("你好 \"世 界\"", &["你好", "世 界"]),
+];
+
+/// Full command lines, the program name included.
+const PARSE_CASES: &[(&str, &[&str])] = &[
+    ("", &[""]),
+    ("app", &["app"]),
+    ("app a b", &["app", "a", "b"]),
+    (r#""C:\Program Files\app.exe" a"#, &["C:\\Program Files\\app.exe", "a"]),
+    (r#""C:\Program Files\app.exe""#, &["C:\\Program Files\\app.exe"]),
+    // No escapes in the program name.
+    (r"C:\dir\ a", &["C:\\dir\\", "a"]),
+    (r#""C:\dir\" a"#, &["C:\\dir\\", "a"]),
+    (r#"a\"b c"#, &["a\\b c"]),
+    (r#"C:\"Program Files"\app.exe a"#, &["C:\\Program Files\\app.exe", "a"]),
+    (r#""unterminated program"#, &["unterminated program"]),
+    ("app\t\"a b\"\t", &["app", "a b"]),
+];
+
+/// Arguments and how they are quoted.
+const QUOTE_CASES: &[(&str, &str)] = &[
+    ("", r#""""#),
+    ("a", "a"),
+    ("a-b_c.d/e", "a-b_c.d/e"),
+    ("a b", r#""a b""#),
+    ("a\tb", "\"a\tb\""),
+    ("a\nb", "\"a\nb\""),
+    ("a\x0bb", "\"a\x0bb\""),
+    (r"a\b", r"a\b"),
+    (r"a\", r"a\"),
+    (r"a\\", r"a\\"),
+    (r"a \", r#""a \\""#),
+    (r"a \\", r#""a \\\\""#),
+    (r#"a"b"#, r#""a\"b""#),
+    (r#"a\"b"#, r#""a\\\"b""#),
+    (r#"a\\"b"#, r#""a\\\\\"b""#),
+    (r#"""#, r#""\"""#),
+    (r#"\""#, r#""\\\"""#),
+    (r"C:\Program Files\", r#""C:\Program Files\\""#),
+    ("你 好", "\"你 好\""),
+];
+
+#[test]
+fn split_args_table() {
+    for (cmdline, expected) in SPLIT_CASES {
+        assert_eq!(split_args(cmdline), *expected, "splitting {:?}", cmdline);
+    }
+}
+
+#[test]
+fn parse_command_line_table() {
+    for (cmdline, expected) in PARSE_CASES {
+        assert_eq!(parse_command_line(cmdline), *expected, "parsing {:?}", cmdline);
+    }
+}
+
+#[test]
+fn parse_command_line_args_table() {
+    // The arguments after the program name follow the same rules as split_args.
+    for (cmdline, expected) in SPLIT_CASES {
+        let argv = parse_command_line(&format!("app {}", cmdline));
+        assert_eq!(argv[0], "app");
+        assert_eq!(argv[1..], **expected, "parsing {:?}", cmdline);
+    }
+}
+
+#[test]
+fn quote_arg_table() {
+    for (arg, expected) in QUOTE_CASES {
+        assert_eq!(quote_arg(arg), *expected, "quoting {:?}", arg);
+        assert_eq!(split_args(expected), [*arg], "splitting {:?}", expected);
+    }
+}
+
+#[test]
+fn quote_program_table() {
+    for (program, expected) in [
+        ("app", "app"),
+        ("", r#""""#),
+        (r"C:\Program Files\app.exe", r#""C:\Program Files\app.exe""#),
+        (r"C:\dir\app.exe", r"C:\dir\app.exe"),
+        ("C:\\my\tdir\\app.exe", "\"C:\\my\tdir\\app.exe\""),
+    ] {
+        assert_eq!(quote_program(program), expected, "quoting {:?}", program);
+        assert_eq!(parse_command_line(expected), [program], "parsing {:?}", expected);
+    }
+}
+
+#[test]
+fn round_trip_every_case() {
+    let mut args: Vec<&str> = QUOTE_CASES.iter().map(|(arg, _)| *arg).collect();
+    for (_, split) in SPLIT_CASES {
+        args.extend(split.iter());
+    }
+
+    assert_eq!(split_args(&join_args(&args)), args);
+    for arg in &args {
+        assert_eq!(split_args(&join_args([arg, arg])), [*arg, *arg]);
+    }
+
+    let cmdline = join_command_line(r"C:\Program Files\app.exe", &args);
+    let argv = parse_command_line(&cmdline);
+    assert_eq!(argv[0], r"C:\Program Files\app.exe");
+    assert_eq!(argv[1..], args);
+}
+
+#[test]
+fn round_trip_generated_args() {
+    // Every combination of up to four of the characters that matter to the rules.
+    let alphabet = ['a', ' ', '\t', '"', '\\'];
+    let mut args = vec![String::new()];
+    for length in 1..=4 {
+        let mut indices = vec![0; length];
+        loop {
+            args.push(indices.iter().map(|&index| alphabet[index]).collect());
+            let Some(position) = indices.iter().rposition(|&index| index + 1 < alphabet.len()) else {
+                break;
+            };
+            indices[position] += 1;
+            indices[position + 1..].fill(0);
+        }
+    }
+    assert_eq!(args.len(), 1 + 5 + 25 + 125 + 625);
+
+    for arg in &args {
+        assert_eq!(split_args(&quote_arg(arg)), [arg.as_str()], "round trip of {:?}", arg);
+    }
+    assert_eq!(split_args(&join_args(&args)), args);
+    assert_eq!(parse_command_line(&join_command_line("app", &args))[1..], args);
+}
+
+#[test]
+fn join_command_line_without_args() {
+    let no_args: [&str; 0] = [];
+    assert_eq!(join_command_line("app", no_args), "app");
+    assert_eq!(join_command_line("my app", [""]), r#""my app" """#);
+    assert_eq!(join_args(no_args), "");
+}
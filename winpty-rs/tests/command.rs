@@ -0,0 +1,103 @@
+#![cfg(unix)]
+
+use std::ffi::OsStr;
+use std::io::Read;
+use std::os::unix::ffi::OsStrExt;
+
+use winptyrs::{PTY, PTYBackend, PtyCommand, PtyError};
+
+fn read_to_end(pty: PTY) -> String {
+    let (mut reader, _, _) = pty.split();
+    let mut output = String::new();
+    reader.read_to_string(&mut output).unwrap();
+    output
+}
+
+#[test]
+fn args_are_passed_verbatim_unix() {
+    let pty = PtyCommand::new("/bin/sh")
+        .args(["-c", "printf '[%s]' \"$@\"", "sh"])
+        .args(["two words", "a \"quote\"", "back\\slash\\", "", "\ttab"])
+        .backend(PTYBackend::Unix)
+        .spawn()
+        .unwrap();
+    assert!(matches!(pty.get_backend(), PTYBackend::Unix));
+    assert_eq!(read_to_end(pty), "[two words][a \"quote\"][back\\slash\\][][\ttab]");
+}
+
+#[test]
+fn args_are_passed_verbatim_pipes() {
+    let pty = PtyCommand::new("printf")
+        .arg("<%s>")
+        .args(["C:\\Program Files\\", "\"\\\"", "x"])
+        .backend(PTYBackend::Pipes)
+        .spawn()
+        .unwrap();
+    assert_eq!(read_to_end(pty), "<C:\\Program Files\\><\"\\\"><x>");
+}
+
+#[test]
+fn args_that_are_not_unicode_are_passed_unchanged() {
+    for backend in [PTYBackend::Unix, PTYBackend::Pipes] {
+        let pty = PtyCommand::new("/bin/sh")
+            .args(["-c", "printf '[%s]' \"$1\"", "sh"])
+            .arg(OsStr::from_bytes(b"caf\xe9 \"x\""))
+            .backend(backend)
+            .spawn()
+            .unwrap();
+        let (mut reader, _, _) = pty.split();
+        let mut output = Vec::new();
+        reader.read_to_end(&mut output).unwrap();
+        assert_eq!(output, b"[caf\xe9 \"x\"]", "{:?}", backend);
+    }
+}
+
+#[test]
+fn env_inherits_and_overrides_pipes() {
+    std::env::set_var("PTY_COMMAND_INHERITED", "inherited");
+    std::env::set_var("PTY_COMMAND_REMOVED", "removed");
+    let pty = PtyCommand::new("/bin/sh")
+        .args(["-c", "echo \"$PTY_COMMAND_INHERITED|$PTY_COMMAND_SET|${PTY_COMMAND_REMOVED-unset}\""])
+        .env("PTY_COMMAND_SET", "first")
+        .envs([("PTY_COMMAND_SET", "a value"), ("PTY_COMMAND_REMOVED", "set")])
+        .env_remove("PTY_COMMAND_REMOVED")
+        .backend(PTYBackend::Pipes)
+        .spawn()
+        .unwrap();
+    assert_eq!(read_to_end(pty), "inherited|a value|unset\n");
+}
+
+#[test]
+fn env_clear_pipes() {
+    let pty = PtyCommand::new("/usr/bin/env")
+        .env("PTY_COMMAND_DROPPED", "dropped")
+        .env_clear()
+        .env("PTY_COMMAND_ONLY", "1")
+        .backend(PTYBackend::Pipes)
+        .spawn()
+        .unwrap();
+    assert_eq!(read_to_end(pty), "PTY_COMMAND_ONLY=1\n");
+
+    let pty = PtyCommand::new("/usr/bin/env").env_clear().backend(PTYBackend::Pipes).spawn().unwrap();
+    assert_eq!(read_to_end(pty), "");
+}
+
+#[test]
+fn current_dir_and_size_unix() {
+    let pty = PtyCommand::new("/bin/sh")
+        .args(["-c", "pwd; stty size"])
+        .current_dir("/")
+        .size(100, 30)
+        .spawn()
+        .unwrap();
+    assert_eq!(read_to_end(pty), "/\r\n30 100\r\n");
+}
+
+#[test]
+fn spawn_errors_unix() {
+    let err = PtyCommand::new("/this/program/does/not/exist").backend(PTYBackend::Unix).spawn().err();
+    assert!(matches!(err, Some(PtyError::Spawn { code: Some(2), .. })));
+
+    let err = PtyCommand::new("/bin/sh").size(0, 25).spawn().err();
+    assert_eq!(err, Some(PtyError::InvalidSize { cols: 0, rows: 25 }));
+}
@@ -1,4 +1,4 @@
-#![cfg(feature="conpty")]
+#![cfg(all(windows, feature="conpty"))]
 
 use std::ffi::OsString;
 use std::{thread, time};
@@ -135,7 +135,6 @@ fn set_size_conpty() {
     let mut count = 0;
     while count < 5 || (cols != 90 && rows != 30) {
         pty.write("powershell -command \"&{(get-host).ui.rawui.WindowSize;}\"\r\n".into()).unwrap();
-        let regex = Regex::new(r".*Width.*").unwrap();
         let mut output_str = "";
         let mut out: OsString;
 
@@ -147,7 +146,6 @@ fn set_size_conpty() {
         println!("{:?}", output_str);
 
         let parts: Vec<&str> = output_str.split("\r\n").collect();
-        let num_regex = Regex::new(r"\s+(\d+)\s+(\d+).*").unwrap();
         for part in parts {
             if num_regex.is_match(part) {
                 for cap in num_regex.captures_iter(part) {
@@ -183,9 +181,7 @@ fn is_alive_exitstatus_conpty() {
     assert_eq!(pty.get_exitstatus().unwrap(), None);
 
     pty.write("exit\r\n".into()).unwrap();
-    while pty.is_alive().unwrap() {
-        ()
-    }
+    while pty.is_alive().unwrap() {}
     assert!(!pty.is_alive().unwrap());
     assert_eq!(pty.get_exitstatus().unwrap(), Some(0))
 }
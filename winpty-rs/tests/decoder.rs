@@ -0,0 +1,148 @@
+use std::ffi::OsString;
+
+use winptyrs::{PTY, PTYArgs, MouseMode, AgentConfig, MockProgram};
+use winptyrs::pty::Utf8Decoder;
+
+const TEXT: &str = "输出 ok é 😁\r\n中文";
+
+fn pty_args() -> PTYArgs {
+    PTYArgs {
+        cols: 80,
+        rows: 25,
+        mouse_mode: MouseMode::WINPTY_MOUSE_MODE_NONE,
+        timeout: 10000,
+        agent_config: AgentConfig::WINPTY_FLAG_COLOR_ESCAPES
+    }
+}
+
+fn decode_all(decoder: &mut Utf8Decoder, finished: bool) -> String {
+    decoder.decode(usize::MAX, finished)
+}
+
+#[test]
+fn split_at_every_offset_decoder() {
+    let bytes = TEXT.as_bytes();
+    for offset in 0..=bytes.len() {
+        let mut decoder = Utf8Decoder::new();
+        decoder.push(&bytes[..offset]);
+        let mut output = decode_all(&mut decoder, false);
+        assert!(!output.contains(char::REPLACEMENT_CHARACTER), "offset {}", offset);
+        decoder.push(&bytes[offset..]);
+        output.push_str(&decode_all(&mut decoder, false));
+        assert_eq!(output, TEXT, "offset {}", offset);
+        assert!(decoder.is_empty());
+    }
+}
+
+#[test]
+fn byte_by_byte_decoder() {
+    let mut decoder = Utf8Decoder::new();
+    let mut output = String::new();
+    for byte in TEXT.as_bytes() {
+        decoder.push(&[*byte]);
+        output.push_str(&decode_all(&mut decoder, false));
+        assert!(decoder.len() < 4);
+    }
+    assert_eq!(output, TEXT);
+}
+
+#[test]
+fn length_in_utf16_units_decoder() {
+    let mut decoder = Utf8Decoder::new();
+    decoder.push("中😁a".as_bytes());
+    assert_eq!(decoder.decode(1, false), "中");
+    // The emoji takes two units, it is not split.
+    assert_eq!(decoder.decode(1, false), "");
+    assert_eq!(decoder.decode(2, false), "😁");
+    assert_eq!(decoder.decode(0, false), "");
+    assert_eq!(decoder.decode(5, false), "a");
+}
+
+#[test]
+fn invalid_and_truncated_decoder() {
+    let mut decoder = Utf8Decoder::new();
+    decoder.push(b"a\xffb\xe4\xb8");
+    assert_eq!(decoder.available(), 3);
+    assert_eq!(decode_all(&mut decoder, false), "a\u{fffd}b");
+    assert_eq!(decoder.len(), 2);
+
+    // An incomplete sequence followed by something else is invalid.
+    decoder.push(b"c");
+    assert_eq!(decode_all(&mut decoder, false), "\u{fffd}c");
+
+    // Once the stream is finished, a truncated sequence is replaced.
+    decoder.push(b"\xf0\x9f\x98");
+    assert_eq!(decode_all(&mut decoder, false), "");
+    assert_eq!(decode_all(&mut decoder, true), "\u{fffd}");
+    assert!(decoder.is_empty());
+}
+
+#[test]
+fn take_bytes_decoder() {
+    let mut decoder = Utf8Decoder::new();
+    decoder.push("中文".as_bytes());
+    assert_eq!(decoder.take_bytes(2), vec![0xe4, 0xb8]);
+    assert_eq!(decode_all(&mut decoder, false), "\u{fffd}文");
+}
+
+#[test]
+fn drop_bytes_decoder() {
+    let mut decoder = Utf8Decoder::new();
+    decoder.push("中文😁a".as_bytes());
+    // The rest of a cut character is dropped with it.
+    assert_eq!(decoder.drop_bytes(1), 3);
+    assert_eq!(decoder.drop_bytes(0), 0);
+    assert_eq!(decoder.drop_bytes(5), 7);
+    assert_eq!(decode_all(&mut decoder, false), "a");
+
+    // Stray continuation bytes are only skipped up to the length of a character.
+    decoder.push(b"\x80\x80\x80\x80\x80b");
+    assert_eq!(decoder.drop_bytes(0), 3);
+    assert_eq!(decode_all(&mut decoder, false), "\u{fffd}\u{fffd}b");
+    assert_eq!(decoder.drop_bytes(10), 0);
+}
+
+#[test]
+fn pty_output_split_at_every_offset_decoder() {
+    let bytes = TEXT.as_bytes();
+    for offset in 1..bytes.len() {
+        let mut pty = PTY::new_mock(&pty_args(), MockProgram::from_fn(move |io| {
+            io.write_bytes(&bytes[..offset]).unwrap();
+            // The first part is read on its own before the rest is written.
+            io.expect("next");
+            io.write_bytes(&bytes[offset..]).unwrap();
+            0
+        })).unwrap();
+        pty.spawn(OsString::from("mock"), None, None, None).unwrap();
+
+        // Only the complete characters of the first part are returned, a blocking
+        // read would wait for the rest of an incomplete one.
+        let mut output = pty.read(1000, false).unwrap().into_string().unwrap();
+        assert!(bytes[..offset].starts_with(output.as_bytes()), "offset {}", offset);
+        pty.write(OsString::from("next")).unwrap();
+        while let Ok(out) = pty.read(1000, true) {
+            output.push_str(out.to_str().unwrap());
+        }
+        assert_eq!(output, TEXT, "offset {}", offset);
+    }
+}
+
+#[test]
+fn decode_large_backlog() {
+    let text = "a中😁".repeat(10000);
+    let mut decoder = Utf8Decoder::new();
+    decoder.push(text.as_bytes());
+
+    // Every length ends on a character, whatever the width of the next one.
+    let mut output = String::new();
+    for length in (2..9).cycle() {
+        if decoder.is_empty() {
+            break;
+        }
+        let out = decoder.decode(length, true);
+        assert!(!out.is_empty());
+        output.push_str(&out);
+    }
+    assert_eq!(output, text);
+    assert!(decoder.is_empty());
+}
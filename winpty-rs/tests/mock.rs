@@ -0,0 +1,336 @@
+use std::ffi::OsString;
+use std::fs;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use winptyrs::{PTY, PTYArgs, PTYBackend, PTYEvent, PtyError, ExitStatus, MouseMode, AgentConfig, MockProgram, MockScript, OverflowPolicy, ReadOutcome};
+use winptyrs::pty::MockStep;
+
+fn pty_args() -> PTYArgs {
+    PTYArgs {
+        cols: 80,
+        rows: 25,
+        mouse_mode: MouseMode::WINPTY_MOUSE_MODE_NONE,
+        timeout: 10000,
+        agent_config: AgentConfig::WINPTY_FLAG_COLOR_ESCAPES
+    }
+}
+
+fn spawn(program: MockProgram) -> PTY {
+    let mut pty = PTY::new_mock(&pty_args(), program).unwrap();
+    pty.spawn(OsString::from("mock"), None, None, None).unwrap();
+    pty
+}
+
+fn read_to_eof(pty: &PTY) -> String {
+    let mut output = String::new();
+    while let Ok(out) = pty.read(1000, true) {
+        output.push_str(out.to_str().unwrap());
+    }
+    output
+}
+
+#[test]
+fn auto_backend_is_never_mock() {
+    if let Ok(pty) = PTY::new(&pty_args()) {
+        assert!(!matches!(pty.get_backend(), PTYBackend::Mock));
+    }
+}
+
+#[test]
+fn scripted_output_and_exit_mock() {
+    let pty = spawn(MockScript::new().output("This is a test string 😁\r\n").exit(3).into());
+    assert!(matches!(pty.get_backend(), PTYBackend::Mock));
+    assert_ne!(pty.get_pid(), 0);
+
+    assert_eq!(read_to_eof(&pty), "This is a test string 😁\r\n");
+    assert!(!pty.is_alive().unwrap());
+    assert!(pty.is_eof().unwrap());
+    assert_eq!(pty.get_exitstatus().unwrap(), Some(3));
+}
+
+#[test]
+fn closure_receives_input_mock() {
+    let pty = spawn(MockProgram::from_fn(|io| {
+        while let Some(input) = io.read() {
+            let input = String::from_utf8_lossy(&input).into_owned();
+            io.write(&input.to_uppercase()).unwrap();
+            if input.contains("exit") {
+                return 5;
+            }
+        }
+        1
+    }));
+
+    pty.write(OsString::from("echo\r\n")).unwrap();
+    assert_eq!(pty.read(6, true).unwrap(), OsString::from("ECHO\r\n"));
+
+    pty.write(OsString::from("exit\r\n")).unwrap();
+    assert_eq!(read_to_eof(&pty), "EXIT\r\n");
+    assert_eq!(pty.get_exitstatus().unwrap(), Some(5));
+}
+
+#[test]
+fn expect_waits_for_input_mock() {
+    let pty = spawn(MockScript::new().expect("ping\r").output("pong\r\n").into());
+
+    assert!(pty.is_alive().unwrap());
+    assert_eq!(pty.read(1000, false).unwrap(), OsString::new());
+
+    pty.write(OsString::from("pi")).unwrap();
+    pty.write(OsString::from("ng\r")).unwrap();
+    assert_eq!(read_to_eof(&pty), "pong\r\n");
+    assert_eq!(pty.get_exitstatus().unwrap(), Some(0));
+}
+
+#[test]
+fn delayed_output_mock() {
+    let start = Instant::now();
+    let pty = spawn(MockScript::new().delay(300).output("late").into());
+
+    // Nothing is available before the delay elapses.
+    assert_eq!(pty.read(1000, false).unwrap(), OsString::new());
+    assert!(pty.is_alive().unwrap());
+
+    assert_eq!(pty.read(4, true).unwrap(), OsString::from("late"));
+    assert!(start.elapsed() >= Duration::from_millis(300));
+}
+
+#[test]
+fn read_length_is_cached_mock() {
+    let pty = spawn(MockScript::new().output("abcdefgh").expect("done").into());
+
+    let mut output = String::new();
+    while output.is_empty() {
+        output.push_str(pty.read(3, true).unwrap().to_str().unwrap());
+    }
+    assert_eq!(output, "abc");
+    assert_eq!(pty.read(2, false).unwrap(), OsString::from("de"));
+    assert_eq!(pty.read(100, false).unwrap(), OsString::from("fgh"));
+    assert!(!pty.is_eof().unwrap());
+
+    pty.write(OsString::from("done")).unwrap();
+    pty.wait_for_exit().unwrap();
+    assert_eq!(pty.read(100, true), Err(PtyError::Eof));
+}
+
+#[test]
+fn subscribe_delivers_output_and_exit_mock() {
+    let pty = spawn(MockScript::new().output("abcdef").expect("more").output("ghi").exit(4).into());
+
+    // Output cached by a previous read is delivered first.
+    let mut output = String::new();
+    while output.is_empty() {
+        output.push_str(pty.read(2, true).unwrap().to_str().unwrap());
+    }
+    assert_eq!(output, "ab");
+
+    let events = pty.subscribe().unwrap();
+    assert_eq!(pty.subscribe().unwrap_err(), PtyError::Subscribed);
+    assert_eq!(pty.read(10, false), Err(PtyError::Subscribed));
+    let mut first = Vec::new();
+    while first.len() < 4 {
+        match events.recv().unwrap() {
+            PTYEvent::Output(bytes) => first.extend(bytes),
+            event => panic!("unexpected event {:?}", event),
+        }
+    }
+    assert_eq!(first, b"cdef");
+
+    pty.write(OsString::from("more")).unwrap();
+    let mut rest = Vec::new();
+    loop {
+        match events.recv().unwrap() {
+            PTYEvent::Output(bytes) => rest.extend(bytes),
+            PTYEvent::Error(err) => panic!("{:?}", err),
+            PTYEvent::Exit(status) => {
+                assert_eq!(status, Some(4));
+                break;
+            }
+        }
+    }
+    assert_eq!(rest, b"ghi");
+    assert!(events.recv().is_err());
+}
+
+#[test]
+fn size_is_visible_to_process_mock() {
+    let (tx, rx) = mpsc::channel();
+    let mut pty = PTY::new_mock(&pty_args(), MockProgram::from_fn(move |io| {
+        while io.read().is_some() {
+            tx.send(io.size()).unwrap();
+        }
+        0
+    })).unwrap();
+    pty.spawn(OsString::from("mock"), None, None, None).unwrap();
+
+    pty.write(OsString::from("size\r")).unwrap();
+    assert_eq!(rx.recv().unwrap(), (80, 25));
+
+    pty.set_size(90, 30).unwrap();
+    pty.write(OsString::from("size\r")).unwrap();
+    assert_eq!(rx.recv().unwrap(), (90, 30));
+
+    assert!(pty.set_size(0, 30).is_err());
+}
+
+#[test]
+fn replay_session_file_mock() {
+    let script = MockScript::new()
+        .output("\x1b[0mprompt> ")
+        .expect("run\r")
+        .delay(10)
+        .output("done\\ \r\n")
+        .exit(2);
+
+    let dir = std::env::temp_dir();
+    let name = format!("winptyrs-mock-{}.session", std::process::id());
+    fs::write(dir.join(&name), format!("# recorded session\n\n{}", script)).unwrap();
+
+    let mut pty = PTY::new_with_backend(&pty_args(), PTYBackend::Mock).unwrap();
+    pty.spawn(OsString::from(&name), None, Some(dir.clone().into_os_string()), None).unwrap();
+    pty.write(OsString::from("run\r")).unwrap();
+    let output = read_to_eof(&pty);
+    fs::remove_file(dir.join(&name)).unwrap();
+
+    assert_eq!(output, "\x1b[0mprompt> done\\ \r\n");
+    assert_eq!(pty.get_exitstatus().unwrap(), Some(2));
+}
+
+#[test]
+fn parse_session_mock() {
+    let script = MockScript::parse("> a\\tb\\x07\n< \\e[A\r\n@ 5\n= 1\n> ignored").unwrap();
+    assert_eq!(script.steps, vec![
+        MockStep::Output(String::from("a\tb\x07")),
+        MockStep::Expect(String::from("\x1b[A")),
+        MockStep::Delay(5),
+        MockStep::Exit(1),
+        MockStep::Output(String::from("ignored")),
+    ]);
+
+    assert!(MockScript::parse("? what").is_err());
+    assert!(MockScript::parse("@ soon").is_err());
+    assert!(MockScript::parse("> \\q").is_err());
+}
+
+#[test]
+fn spawn_errors_mock() {
+    let mut pty = PTY::new_with_backend(&pty_args(), PTYBackend::Mock).unwrap();
+    assert_eq!(pty.is_alive(), Err(PtyError::NotSpawned));
+    let err = pty.spawn(OsString::from("/this/session/does/not/exist"), None, None, None).unwrap_err();
+    assert!(matches!(err, PtyError::Spawn { code: Some(_), .. }), "{:?}", err);
+
+    let mut pty = spawn(MockScript::new().into());
+    let err = pty.spawn(OsString::from("mock"), None, None, None).unwrap_err();
+    assert!(matches!(err, PtyError::Spawn { code: None, .. }), "{:?}", err);
+
+    assert!(matches!(
+        PTY::new_with_backend(&PTYArgs { cols: 0, ..pty_args() }, PTYBackend::Mock),
+        Err(PtyError::InvalidSize { cols: 0, rows: 25 })
+    ));
+    assert!(matches!(
+        PTY::new_with_backend(&pty_args(), PTYBackend::NoBackend),
+        Err(PtyError::BackendUnavailable { backend: PTYBackend::NoBackend, .. })
+    ));
+}
+
+#[test]
+fn drop_while_waiting_for_input_mock() {
+    let pty = spawn(MockScript::new().expect("never").into());
+    assert!(pty.is_alive().unwrap());
+    drop(pty);
+}
+
+#[test]
+fn send_interrupt_mock() {
+    let pty = spawn(MockProgram::from_fn(|io| {
+        io.expect("\x03");
+        io.write("^C\r\n").unwrap();
+        130
+    }));
+    pty.send_interrupt().unwrap();
+    assert_eq!(read_to_eof(&pty), "^C\r\n");
+    assert_eq!(pty.get_exitstatus().unwrap(), Some(130));
+}
+
+#[test]
+fn kill_mock() {
+    let pty = PTY::new_with_backend(&pty_args(), PTYBackend::Mock).unwrap();
+    assert_eq!(pty.kill(), Err(PtyError::NotSpawned));
+
+    let pty = spawn(MockScript::new().output("started\r\n").expect("never").into());
+    assert!(!pty.terminate(Duration::from_millis(100)).unwrap());
+    assert!(!pty.is_alive().unwrap());
+    assert_eq!(pty.get_exitstatus().unwrap(), Some(137));
+    assert_eq!(pty.exit_status().unwrap(), Some(ExitStatus::Killed));
+    read_to_eof(&pty);
+    assert!(pty.is_eof().unwrap());
+    pty.kill().unwrap();
+}
+
+#[test]
+fn wait_timeout_mock() {
+    let pty = spawn(MockScript::new().expect("exit\r").exit(4).into());
+    let start = Instant::now();
+    assert_eq!(pty.wait_timeout(Duration::from_millis(100)).unwrap(), None);
+    assert!(start.elapsed() >= Duration::from_millis(100));
+
+    pty.write(OsString::from("exit\r")).unwrap();
+    assert_eq!(pty.wait_timeout(Duration::from_secs(5)).unwrap(), Some(ExitStatus::Exited(4)));
+    assert_eq!(pty.exit_status().unwrap(), Some(ExitStatus::Exited(4)));
+}
+
+#[test]
+fn output_buffer_policies_mock() {
+    let spawn_with = |policy| {
+        let mut pty = PTY::new_mock(&pty_args(), MockProgram::from_fn(|io| {
+            io.write("0123456789").unwrap();
+            0
+        })).unwrap();
+        pty.set_output_buffer(4, policy).unwrap();
+        pty.spawn(OsString::from("mock"), None, None, None).unwrap();
+        pty
+    };
+
+    // The process is held back until its output is read.
+    let pty = spawn_with(OverflowPolicy::Block);
+    assert_eq!(read_to_eof(&pty), "0123456789");
+    let stats = pty.output_stats().unwrap();
+    assert_eq!((stats.buffered, stats.capacity, stats.dropped_bytes), (0, 4, 0));
+
+    // Nothing is read until the whole output was pushed into the buffer.
+    let wait_dropped = |pty: &PTY| {
+        while pty.output_stats().unwrap().dropped_bytes < 6 {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    };
+
+    let pty = spawn_with(OverflowPolicy::Error);
+    wait_dropped(&pty);
+    assert_eq!(pty.read(1000, true), Err(PtyError::Overflow { dropped: 6 }));
+    assert_eq!(read_to_eof(&pty), "0123");
+    let stats = pty.output_stats().unwrap();
+    assert_eq!(stats.dropped_bytes, 6);
+    assert!(stats.overflows >= 1);
+
+    let pty = spawn_with(OverflowPolicy::DropOldest);
+    wait_dropped(&pty);
+    assert_eq!(pty.output_stats().unwrap().buffered, 4);
+    assert_eq!(read_to_eof(&pty), "6789");
+
+    assert!(pty.set_output_buffer(0, OverflowPolicy::Block).is_err());
+}
+
+#[test]
+fn read_outcome_mock() {
+    let pty = spawn(MockScript::new().expect("go\r").output("😁").delay(100).output("ok").into());
+    assert_eq!(pty.try_read(1000, false).unwrap(), ReadOutcome::WouldBlock);
+
+    // A blocking read waits for output instead of returning none.
+    pty.write(OsString::from("go\r")).unwrap();
+    assert_eq!(pty.try_read(1, true).unwrap(), ReadOutcome::Data(OsString::from("😁")));
+    assert_eq!(pty.try_read(1000, true).unwrap(), ReadOutcome::Data(OsString::from("ok")));
+    assert_eq!(pty.try_read(1000, true).unwrap(), ReadOutcome::Eof);
+    assert_eq!(pty.try_read(0, true).unwrap(), ReadOutcome::Data(OsString::new()));
+    assert!(matches!(pty.try_read_stderr(1000, true), Err(PtyError::Io { .. })));
+}
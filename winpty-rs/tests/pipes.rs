@@ -0,0 +1,256 @@
+#![cfg(unix)]
+
+use std::ffi::OsString;
+use std::time::{Duration, Instant};
+
+use winptyrs::{PTY, PTYArgs, PTYBackend, PtyError, ExitStatus, MouseMode, AgentConfig, OverflowPolicy, ReadOutcome};
+
+fn pty_args() -> PTYArgs {
+    PTYArgs {
+        cols: 80,
+        rows: 25,
+        mouse_mode: MouseMode::WINPTY_MOUSE_MODE_NONE,
+        timeout: 10000,
+        agent_config: AgentConfig::WINPTY_FLAG_COLOR_ESCAPES
+    }
+}
+
+fn read_until(read: impl Fn() -> Result<OsString, PtyError>, expected: &str) -> String {
+    let mut output = String::new();
+    while !output.contains(expected) {
+        output.push_str(read().unwrap().to_str().unwrap());
+    }
+    output
+}
+
+/// Wait until the process `pid` has exited, `false` if it is still alive
+/// after a few seconds. Orphans may never be reaped inside a container.
+fn wait_gone(pid: libc::pid_t) -> bool {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while Instant::now() < deadline {
+        let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).unwrap_or_default();
+        let zombie = stat.rsplit_once(')').is_some_and(|(_, rest)| rest.trim_start().starts_with('Z'));
+        if zombie || unsafe { libc::kill(pid, 0) } < 0 {
+            return true;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    false
+}
+
+#[test]
+fn read_write_pipes() {
+    let mut pty = PTY::new_with_backend(&pty_args(), PTYBackend::Pipes).unwrap();
+    assert!(matches!(pty.get_backend(), PTYBackend::Pipes));
+    pty.spawn(OsString::from("/bin/sh"), None, None, None).unwrap();
+
+    pty.write(OsString::from("echo \"This is a test string 😁\"\n")).unwrap();
+
+    // No echo, no carriage returns and no escape sequences.
+    let output = read_until(|| pty.read(1000, true), "\n");
+    assert_eq!(output, "This is a test string 😁\n");
+    assert_ne!(pty.get_pid(), 0);
+}
+
+#[test]
+fn stderr_is_separate_pipes() {
+    let mut pty = PTY::new_with_backend(&pty_args(), PTYBackend::Pipes).unwrap();
+    pty.spawn(OsString::from("/bin/sh"), None, None, None).unwrap();
+
+    pty.write(OsString::from("echo error 1>&2; echo output\n")).unwrap();
+    assert_eq!(read_until(|| pty.read_stderr(1000, true), "\n"), "error\n");
+    assert_eq!(read_until(|| pty.read(1000, true), "\n"), "output\n");
+}
+
+#[test]
+fn spawn_args_cwd_env_pipes() {
+    let mut pty = PTY::new_with_backend(&pty_args(), PTYBackend::Pipes).unwrap();
+    pty.spawn(OsString::from("echo"), Some(OsString::from("first second")), None, None).unwrap();
+    assert_eq!(read_until(|| pty.read(1000, true), "\n"), "first second\n");
+
+    let mut pty = PTY::new_with_backend(&pty_args(), PTYBackend::Pipes).unwrap();
+    pty.spawn(OsString::from("pwd"), None, Some(OsString::from("/")), None).unwrap();
+    assert_eq!(read_until(|| pty.read(1000, true), "\n"), "/\n");
+
+    let env = OsString::from("PTY_TEST_VAR=value\0PATH=/bin:/usr/bin\0");
+    let mut pty = PTY::new_with_backend(&pty_args(), PTYBackend::Pipes).unwrap();
+    pty.spawn(OsString::from("env"), None, None, Some(env)).unwrap();
+    pty.wait_for_exit().unwrap();
+    let output = read_until(|| pty.read(1000, true), "PTY_TEST_VAR=value\n");
+    assert!(!output.contains("HOME="));
+}
+
+#[test]
+fn spawn_missing_program_pipes() {
+    let appname = OsString::from("/this/program/does/not/exist");
+    let mut pty = PTY::new_with_backend(&pty_args(), PTYBackend::Pipes).unwrap();
+    let err = pty.spawn(appname, None, None, None).unwrap_err();
+    assert!(matches!(err, PtyError::Spawn { code: Some(2), .. }), "{:?}", err);
+}
+
+#[test]
+fn exitstatus_and_eof_pipes() {
+    let mut pty = PTY::new_with_backend(&pty_args(), PTYBackend::Pipes).unwrap();
+    pty.spawn(OsString::from("/bin/sh"), None, None, None).unwrap();
+
+    pty.write(OsString::from("echo wait\n")).unwrap();
+    assert!(pty.is_alive().unwrap());
+    assert_eq!(pty.get_exitstatus().unwrap(), None);
+
+    pty.write(OsString::from("echo bye; exit 3\n")).unwrap();
+    pty.wait_for_exit().unwrap();
+    assert!(!pty.is_alive().unwrap());
+    assert_eq!(pty.get_exitstatus().unwrap(), Some(3));
+
+    let mut output = String::new();
+    while let Ok(out) = pty.read(1000, true) {
+        output.push_str(out.to_str().unwrap());
+    }
+    assert_eq!(output, "wait\nbye\n");
+    assert!(pty.is_eof().unwrap());
+    assert_eq!(pty.read(1000, true), Err(PtyError::Eof));
+}
+
+#[test]
+fn set_size_pipes() {
+    let pty = PTY::new_with_backend(&pty_args(), PTYBackend::Pipes).unwrap();
+    assert!(pty.set_size(90, 30).is_ok());
+    assert_eq!(pty.set_size(0, 30), Err(PtyError::InvalidSize { cols: 0, rows: 30 }));
+    assert!(matches!(
+        PTY::new_with_backend(&PTYArgs { rows: 0, ..pty_args() }, PTYBackend::Pipes),
+        Err(PtyError::InvalidSize { cols: 80, rows: 0 })
+    ));
+}
+
+#[test]
+fn drop_running_process_pipes() {
+    let start = Instant::now();
+    let mut pty = PTY::new_with_backend(&pty_args(), PTYBackend::Pipes).unwrap();
+    pty.spawn(OsString::from("sleep"), Some(OsString::from("100")), None, None).unwrap();
+    assert!(pty.is_alive().unwrap());
+    drop(pty);
+    assert!(start.elapsed() < Duration::from_secs(10));
+}
+
+#[test]
+fn read_stderr_unsupported_unix() {
+    let pty = PTY::new_with_backend(&pty_args(), PTYBackend::Unix).unwrap();
+    assert!(pty.read_stderr(1000, false).is_err());
+}
+
+#[test]
+fn raw_bytes_pipes() {
+    let mut pty = PTY::new_with_backend(&pty_args(), PTYBackend::Pipes).unwrap();
+    pty.spawn(OsString::from("cat"), None, None, None).unwrap();
+
+    // NUL characters and invalid UTF-8 are passed through unchanged.
+    let input = b"a\0b\xff\xfe\xe4\xb8";
+    assert_eq!(pty.write_bytes(input).unwrap(), input.len() as u32);
+
+    let mut output = Vec::new();
+    let mut buf = [0u8; 3];
+    while output.len() < input.len() {
+        let n = pty.read_bytes(&mut buf, true).unwrap();
+        assert!(n <= buf.len());
+        output.extend_from_slice(&buf[..n]);
+    }
+    assert_eq!(output, input);
+}
+
+#[test]
+fn kill_and_terminate_pipes() {
+    let mut pty = PTY::new_with_backend(&pty_args(), PTYBackend::Pipes).unwrap();
+    pty.spawn(OsString::from("sleep"), Some(OsString::from("100")), None, None).unwrap();
+    pty.kill().unwrap();
+    pty.wait_for_exit().unwrap();
+    assert_eq!(pty.get_exitstatus().unwrap(), Some(128 + 9));
+    pty.kill().unwrap();
+
+    let mut pty = PTY::new_with_backend(&pty_args(), PTYBackend::Pipes).unwrap();
+    pty.spawn(OsString::from("/bin/sh"), None, None, None).unwrap();
+    pty.write(OsString::from("trap 'echo bye; exit 4' TERM; echo ready\n")).unwrap();
+    read_until(|| pty.read(1000, true), "ready\n");
+
+    assert!(pty.terminate(Duration::from_secs(5)).unwrap());
+    assert_eq!(pty.get_exitstatus().unwrap(), Some(4));
+    assert_eq!(read_until(|| pty.read(1000, true), "bye\n"), "bye\n");
+}
+
+#[test]
+fn wait_timeout_and_exit_status_pipes() {
+    let mut pty = PTY::new_with_backend(&pty_args(), PTYBackend::Pipes).unwrap();
+    pty.spawn(OsString::from("/bin/sh"), None, None, None).unwrap();
+    assert_eq!(pty.wait_timeout(Duration::from_millis(100)).unwrap(), None);
+    pty.write(OsString::from("kill -TERM $$\n")).unwrap();
+    assert_eq!(pty.wait_timeout(Duration::from_secs(5)).unwrap(), Some(ExitStatus::Signaled(15)));
+
+    let mut pty = PTY::new_with_backend(&pty_args(), PTYBackend::Pipes).unwrap();
+    pty.spawn(OsString::from("sleep"), Some(OsString::from("100")), None, None).unwrap();
+    let (_, _, control) = pty.split();
+    control.kill().unwrap();
+    assert_eq!(control.wait_timeout(Duration::from_secs(5)).unwrap(), Some(ExitStatus::Killed));
+    assert_eq!(control.exit_status().unwrap(), Some(ExitStatus::Killed));
+}
+
+#[test]
+fn kill_on_drop_pipes() {
+    let mut pty = PTY::new_with_backend(&pty_args(), PTYBackend::Pipes).unwrap();
+    pty.set_kill_on_drop(true).unwrap();
+    pty.spawn(
+        OsString::from("/bin/sh"),
+        Some(OsString::from("-c \"trap '' HUP; sleep 100 & echo $!; wait\"")),
+        None, None
+    ).unwrap();
+    let pid: libc::pid_t = read_until(|| pty.read(1000, true), "\n").trim().parse().unwrap();
+
+    // Only the process group of the process is killed, not the test process.
+    drop(pty);
+    assert!(wait_gone(pid));
+}
+
+#[test]
+fn large_output_small_buffer_pipes() {
+    let mut pty = PTY::new_with_backend(&pty_args(), PTYBackend::Pipes).unwrap();
+    pty.set_output_buffer(1000, OverflowPolicy::Block).unwrap();
+    pty.spawn(
+        OsString::from("/bin/sh"),
+        Some(OsString::from("-c \"head -c 1000000 /dev/zero | tr '\\0' a\"")),
+        None, None
+    ).unwrap();
+
+    let mut total = 0;
+    let mut buf = [0u8; 4096];
+    loop {
+        match pty.read_bytes(&mut buf, true) {
+            Ok(n) => {
+                assert!(buf[..n].iter().all(|&byte| byte == b'a'));
+                total += n;
+            }
+            Err(PtyError::Eof) => break,
+            Err(err) => panic!("{:?}", err),
+        }
+        assert!(pty.output_stats().unwrap().buffered <= 1000);
+    }
+    assert_eq!(total, 1000000);
+    assert_eq!(pty.output_stats().unwrap().dropped_bytes, 0);
+}
+
+#[test]
+fn read_outcome_pipes() {
+    let mut pty = PTY::new_with_backend(&pty_args(), PTYBackend::Pipes).unwrap();
+    pty.spawn(OsString::from("/bin/sh"), None, None, None).unwrap();
+    assert_eq!(pty.try_read(1000, false).unwrap(), ReadOutcome::WouldBlock);
+    assert_eq!(pty.try_read_stderr(1000, false).unwrap(), ReadOutcome::WouldBlock);
+
+    pty.write(OsString::from("printf out; printf err 1>&2; exit\n")).unwrap();
+    assert_eq!(pty.try_read_stderr(1000, true).unwrap(), ReadOutcome::Data(OsString::from("err")));
+    assert_eq!(pty.try_read_stderr(1000, true).unwrap(), ReadOutcome::Eof);
+    assert_eq!(pty.try_read_stderr_bytes(&mut [0; 16], true).unwrap(), ReadOutcome::Eof);
+
+    let mut buf = [0u8; 2];
+    assert_eq!(pty.try_read_bytes(&mut buf, true).unwrap(), ReadOutcome::Data(2));
+    assert_eq!(&buf, b"ou");
+    assert_eq!(pty.try_read(1000, true).unwrap(), ReadOutcome::Data(OsString::from("t")));
+    assert_eq!(pty.try_read(1000, true).unwrap(), ReadOutcome::Eof);
+    assert_eq!(pty.try_read(1000, false).unwrap(), ReadOutcome::Eof);
+}
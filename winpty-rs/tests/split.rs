@@ -0,0 +1,90 @@
+use std::ffi::OsString;
+use std::io::{self, Read, Write};
+use std::sync::mpsc;
+use std::thread;
+
+use winptyrs::{PTY, PTYArgs, PTYBackend, MouseMode, AgentConfig, MockProgram, MockScript};
+use winptyrs::{PtyReader, PtyWriter, PtyControl};
+
+fn pty_args() -> PTYArgs {
+    PTYArgs {
+        cols: 80,
+        rows: 25,
+        mouse_mode: MouseMode::WINPTY_MOUSE_MODE_NONE,
+        timeout: 10000,
+        agent_config: AgentConfig::WINPTY_FLAG_COLOR_ESCAPES
+    }
+}
+
+fn spawn(program: MockProgram) -> (PtyReader, PtyWriter, PtyControl) {
+    let mut pty = PTY::new_mock(&pty_args(), program).unwrap();
+    pty.spawn(OsString::from("mock"), None, None, None).unwrap();
+    pty.split()
+}
+
+fn assert_send<T: Send + 'static>(_: &T) {}
+
+#[test]
+fn halves_are_send_split() {
+    let (reader, writer, control) = spawn(MockScript::new().into());
+    assert_send(&reader);
+    assert_send(&writer);
+    assert_send(&control);
+}
+
+#[test]
+fn concurrent_read_write_split() {
+    // Upper-case every line until "exit" is received.
+    let (mut reader, mut writer, control) = spawn(MockProgram::from_fn(|io| {
+        while let Some(input) = io.read() {
+            let input = String::from_utf8_lossy(&input).into_owned();
+            io.write(&input.to_uppercase()).unwrap();
+            if input.contains("exit") {
+                return 3;
+            }
+        }
+        1
+    }));
+
+    let output = thread::spawn(move || {
+        let mut output = Vec::new();
+        io::copy(&mut reader, &mut output).unwrap();
+        output
+    });
+
+    writer.write_all(b"hello\r\n").unwrap();
+    writer.flush().unwrap();
+    writer.write_all(b"exit\r\n").unwrap();
+
+    assert_eq!(output.join().unwrap(), b"HELLO\r\nEXIT\r\n");
+    control.wait_for_exit().unwrap();
+    assert!(!control.is_alive().unwrap());
+    assert_eq!(control.get_exitstatus().unwrap(), Some(3));
+    assert!(matches!(control.get_backend(), PTYBackend::Mock));
+}
+
+#[test]
+fn read_returns_zero_at_eof_split() {
+    let (mut reader, _writer, control) = spawn(MockScript::new().output("done").into());
+
+    let mut output = String::new();
+    reader.read_to_string(&mut output).unwrap();
+    assert_eq!(output, "done");
+    assert_eq!(reader.read(&mut [0u8; 16]).unwrap(), 0);
+    assert!(control.is_eof().unwrap());
+}
+
+#[test]
+fn control_resizes_from_another_thread_split() {
+    let (tx, rx) = mpsc::channel();
+    let (_reader, mut writer, control) = spawn(MockProgram::from_fn(move |io| {
+        while io.read().is_some() {
+            tx.send(io.size()).unwrap();
+        }
+        0
+    }));
+
+    thread::spawn(move || control.set_size(100, 40).unwrap()).join().unwrap();
+    writer.write_all(b"size").unwrap();
+    assert_eq!(rx.recv().unwrap(), (100, 40));
+}
@@ -0,0 +1,403 @@
+#![cfg(unix)]
+
+use std::ffi::OsString;
+use std::time::{Duration, Instant};
+use regex::Regex;
+
+use winptyrs::{PTY, PTYArgs, PTYBackend, PTYEvent, PtyError, ExitStatus, MouseMode, AgentConfig, ReadOutcome};
+
+fn pty_args() -> PTYArgs {
+    PTYArgs {
+        cols: 80,
+        rows: 25,
+        mouse_mode: MouseMode::WINPTY_MOUSE_MODE_NONE,
+        timeout: 10000,
+        agent_config: AgentConfig::WINPTY_FLAG_COLOR_ESCAPES
+    }
+}
+
+fn read_until(pty: &PTY, regex: &Regex) -> String {
+    let mut output = String::new();
+    while !regex.is_match(&output) {
+        let out = pty.read(1000, false).unwrap();
+        output.push_str(out.to_str().unwrap());
+    }
+    output
+}
+
+/// Wait until the process `pid` has exited, `false` if it is still alive
+/// after a few seconds. Orphans may never be reaped inside a container.
+fn wait_gone(pid: libc::pid_t) -> bool {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while Instant::now() < deadline {
+        let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).unwrap_or_default();
+        let zombie = stat.rsplit_once(')').is_some_and(|(_, rest)| rest.trim_start().starts_with('Z'));
+        if zombie || unsafe { libc::kill(pid, 0) } < 0 {
+            return true;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    false
+}
+
+#[test]
+fn auto_backend_unix() {
+    let pty = PTY::new(&pty_args()).unwrap();
+    assert!(matches!(pty.get_backend(), PTYBackend::Unix));
+
+    // The Windows backends are skipped, each with its reason.
+    let fallbacks = pty.backend_fallbacks();
+    assert_eq!(fallbacks.iter().map(|(backend, _)| *backend).collect::<Vec<_>>(), [PTYBackend::ConPTY, PTYBackend::WinPTY]);
+    assert!(fallbacks.iter().all(|(_, err)| matches!(err, PtyError::BackendUnavailable { .. })));
+    assert!(PTY::new_with_backend(&pty_args(), PTYBackend::Unix).unwrap().backend_fallbacks().is_empty());
+}
+
+#[test]
+fn read_write_unix() {
+    let appname = OsString::from("/bin/sh");
+    let mut pty = PTY::new_with_backend(&pty_args(), PTYBackend::Unix).unwrap();
+    pty.spawn(appname, None, None, None).unwrap();
+
+    pty.write(OsString::from("echo \"This is a test string 😁\"\n")).unwrap();
+
+    let out_regex = Regex::new("This is a test string 😁\r\n").unwrap();
+    let output = read_until(&pty, &out_regex);
+
+    assert!(out_regex.is_match(&output));
+    assert_ne!(pty.get_pid(), 0)
+}
+
+#[test]
+fn spawn_args_cwd_env_unix() {
+    let mut pty = PTY::new_with_backend(&pty_args(), PTYBackend::Unix).unwrap();
+    pty.spawn(OsString::from("echo"), Some(OsString::from("first second")), None, None).unwrap();
+    read_until(&pty, &Regex::new(r"first second\r\n").unwrap());
+
+    let mut pty = PTY::new_with_backend(&pty_args(), PTYBackend::Unix).unwrap();
+    pty.spawn(OsString::from("pwd"), None, Some(OsString::from("/")), None).unwrap();
+    read_until(&pty, &Regex::new(r"^/\r\n").unwrap());
+
+    let env = OsString::from("PTY_TEST_VAR=value\0PATH=/bin:/usr/bin\0");
+    let mut pty = PTY::new_with_backend(&pty_args(), PTYBackend::Unix).unwrap();
+    pty.spawn(OsString::from("env"), None, None, Some(env)).unwrap();
+    let output = read_until(&pty, &Regex::new(r"PTY_TEST_VAR=value\r\n").unwrap());
+    assert!(!output.contains("HOME="));
+}
+
+#[test]
+fn spawn_missing_program_unix() {
+    let appname = OsString::from("/this/program/does/not/exist");
+    let mut pty = PTY::new_with_backend(&pty_args(), PTYBackend::Unix).unwrap();
+    let err = pty.spawn(appname, None, None, None).unwrap_err();
+    assert!(matches!(err, PtyError::Spawn { code: Some(2), .. }), "{:?}", err);
+}
+
+#[test]
+fn windows_backends_unavailable_unix() {
+    for backend in [PTYBackend::ConPTY, PTYBackend::WinPTY] {
+        match PTY::new_with_backend(&pty_args(), backend) {
+            Err(PtyError::BackendUnavailable { backend: unavailable, .. }) => assert_eq!(unavailable, backend),
+            _ => panic!("{:?} should not be available", backend),
+        }
+    }
+}
+
+#[test]
+fn set_size_unix() {
+    let appname = OsString::from("/bin/sh");
+    let mut pty = PTY::new_with_backend(&pty_args(), PTYBackend::Unix).unwrap();
+    pty.spawn(appname, None, None, None).unwrap();
+
+    pty.write("stty size\n".into()).unwrap();
+    let size_regex = Regex::new(r"25 80").unwrap();
+    read_until(&pty, &size_regex);
+
+    pty.set_size(90, 30).unwrap();
+    pty.write("stty size\n".into()).unwrap();
+    let size_regex = Regex::new(r"30 90").unwrap();
+    read_until(&pty, &size_regex);
+
+    assert_eq!(pty.set_size(0, 30), Err(PtyError::InvalidSize { cols: 0, rows: 30 }));
+}
+
+#[test]
+fn is_alive_exitstatus_unix() {
+    let appname = OsString::from("/bin/sh");
+    let mut pty = PTY::new_with_backend(&pty_args(), PTYBackend::Unix).unwrap();
+    pty.spawn(appname, None, None, None).unwrap();
+
+    pty.write("echo wait\n".into()).unwrap();
+    assert!(pty.is_alive().unwrap());
+    assert_eq!(pty.get_exitstatus().unwrap(), None);
+
+    pty.write("exit 3\n".into()).unwrap();
+    while pty.is_alive().unwrap() {}
+    assert!(!pty.is_alive().unwrap());
+    assert_eq!(pty.get_exitstatus().unwrap(), Some(3))
+}
+
+#[test]
+fn wait_for_exit_unix() {
+    let appname = OsString::from("/bin/sh");
+    let mut pty = PTY::new_with_backend(&pty_args(), PTYBackend::Unix).unwrap();
+    pty.spawn(appname, None, None, None).unwrap();
+
+    pty.write("echo wait\n".into()).unwrap();
+    assert!(pty.is_alive().unwrap());
+    assert_eq!(pty.get_exitstatus().unwrap(), None);
+
+    pty.write("exit\n".into()).unwrap();
+    let _ = pty.wait_for_exit();
+
+    assert!(!pty.is_alive().unwrap());
+    assert_eq!(pty.get_exitstatus().unwrap(), Some(0));
+    assert!(pty.is_eof().unwrap() || pty.read(1000, false).is_ok());
+}
+
+#[test]
+fn read_bytes_unix() {
+    let mut pty = PTY::new_with_backend(&pty_args(), PTYBackend::Unix).unwrap();
+    pty.spawn(OsString::from("printf"), Some(OsString::from("a\\000b\\377c")), None, None).unwrap();
+
+    let mut output = Vec::new();
+    let mut buf = [0u8; 1000];
+    while let Ok(n) = pty.read_bytes(&mut buf, true) {
+        output.extend_from_slice(&buf[..n]);
+    }
+    assert_eq!(output, b"a\0b\xffc");
+}
+
+#[test]
+fn subscribe_round_trip_unix() {
+    let mut pty = PTY::new_with_backend(&pty_args(), PTYBackend::Unix).unwrap();
+    pty.spawn(OsString::from("cat"), None, None, None).unwrap();
+    let events = pty.subscribe().unwrap();
+
+    // Every echo is delivered as soon as it is read, without polling.
+    let start = Instant::now();
+    for round in 0..10 {
+        let line = format!("line {}", round);
+        pty.write(OsString::from(format!("{}\n", line))).unwrap();
+        let mut output = String::new();
+        while !output.contains(&line) {
+            match events.recv_timeout(Duration::from_secs(5)).unwrap() {
+                PTYEvent::Output(bytes) => output.push_str(&String::from_utf8_lossy(&bytes)),
+                event => panic!("unexpected event {:?}", event),
+            }
+        }
+    }
+    assert!(start.elapsed() < Duration::from_secs(1));
+
+    pty.write(OsString::from("\x04")).unwrap();
+    loop {
+        match events.recv_timeout(Duration::from_secs(5)).unwrap() {
+            PTYEvent::Output(_) => {}
+            PTYEvent::Error(err) => panic!("{:?}", err),
+            PTYEvent::Exit(status) => {
+                assert_eq!(status, Some(0));
+                break;
+            }
+        }
+    }
+}
+
+#[test]
+fn send_interrupt_unix() {
+    let mut pty = PTY::new_with_backend(&pty_args(), PTYBackend::Unix).unwrap();
+    let cmdline = "-c \"trap 'echo caught; exit 3' INT; echo ready; while :; do sleep 0.1; done\"";
+    pty.spawn(OsString::from("/bin/sh"), Some(OsString::from(cmdline)), None, None).unwrap();
+    read_until(&pty, &Regex::new("ready").unwrap());
+
+    pty.send_interrupt().unwrap();
+    read_until(&pty, &Regex::new("caught").unwrap());
+    pty.wait_for_exit().unwrap();
+    assert_eq!(pty.get_exitstatus().unwrap(), Some(3));
+}
+
+#[test]
+fn terminate_unix() {
+    let mut pty = PTY::new_with_backend(&pty_args(), PTYBackend::Unix).unwrap();
+    pty.spawn(OsString::from("sleep"), Some(OsString::from("100")), None, None).unwrap();
+    assert!(pty.terminate(Duration::from_secs(5)).unwrap());
+    assert_eq!(pty.get_exitstatus().unwrap(), Some(128 + 15));
+
+    // A process that ignores SIGTERM is killed once the grace period is over.
+    let mut pty = PTY::new_with_backend(&pty_args(), PTYBackend::Unix).unwrap();
+    let cmdline = "-c \"trap '' TERM; echo ready; while :; do sleep 0.1; done\"";
+    pty.spawn(OsString::from("/bin/sh"), Some(OsString::from(cmdline)), None, None).unwrap();
+    read_until(&pty, &Regex::new("ready").unwrap());
+
+    let start = Instant::now();
+    assert!(!pty.terminate(Duration::from_millis(300)).unwrap());
+    assert!(start.elapsed() >= Duration::from_millis(300));
+    assert!(!pty.is_alive().unwrap());
+    assert_eq!(pty.get_exitstatus().unwrap(), Some(128 + 9));
+    assert!(pty.terminate(Duration::from_secs(5)).unwrap());
+}
+
+#[test]
+fn kill_unix() {
+    let mut pty = PTY::new_with_backend(&pty_args(), PTYBackend::Unix).unwrap();
+    assert_eq!(pty.kill(), Err(PtyError::NotSpawned));
+    pty.spawn(OsString::from("sleep"), Some(OsString::from("100")), None, None).unwrap();
+
+    pty.kill().unwrap();
+    pty.wait_for_exit().unwrap();
+    assert_eq!(pty.get_exitstatus().unwrap(), Some(128 + 9));
+    // Signalling a process that already exited is not an error.
+    pty.kill().unwrap();
+    pty.send_interrupt().unwrap();
+}
+
+#[test]
+fn signals_blocked_by_caller_unix() {
+    // The signal mask of the spawning thread is not inherited by the process.
+    unsafe {
+        let mut set: libc::sigset_t = std::mem::zeroed();
+        libc::sigemptyset(&mut set);
+        libc::sigaddset(&mut set, libc::SIGINT);
+        libc::sigaddset(&mut set, libc::SIGTERM);
+        libc::pthread_sigmask(libc::SIG_BLOCK, &set, std::ptr::null_mut());
+    }
+    for backend in [PTYBackend::Unix, PTYBackend::Pipes] {
+        let mut pty = PTY::new_with_backend(&pty_args(), backend).unwrap();
+        pty.spawn(OsString::from("sleep"), Some(OsString::from("100")), None, None).unwrap();
+        assert!(pty.terminate(Duration::from_secs(5)).unwrap());
+        assert_eq!(pty.get_exitstatus().unwrap(), Some(128 + 15));
+    }
+}
+
+#[test]
+fn wait_timeout_and_exit_status_unix() {
+    let mut pty = PTY::new_with_backend(&pty_args(), PTYBackend::Unix).unwrap();
+    assert_eq!(pty.wait_timeout(Duration::ZERO), Err(PtyError::NotSpawned));
+    pty.spawn(OsString::from("/bin/sh"), Some(OsString::from("-c \"exit 3\"")), None, None).unwrap();
+    assert_eq!(pty.wait_timeout(Duration::from_secs(5)).unwrap(), Some(ExitStatus::Exited(3)));
+    assert_eq!(pty.exit_status().unwrap(), Some(ExitStatus::Exited(3)));
+
+    let mut pty = PTY::new_with_backend(&pty_args(), PTYBackend::Unix).unwrap();
+    pty.spawn(OsString::from("sleep"), Some(OsString::from("100")), None, None).unwrap();
+    let start = Instant::now();
+    assert_eq!(pty.wait_timeout(Duration::from_millis(200)).unwrap(), None);
+    assert!(start.elapsed() >= Duration::from_millis(200));
+    assert_eq!(pty.exit_status().unwrap(), None);
+
+    // A signal that was not sent through the PTY is reported as is.
+    unsafe {
+        libc::kill(pty.get_pid() as libc::pid_t, libc::SIGKILL);
+    }
+    let status = pty.wait_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(status, Some(ExitStatus::Signaled(libc::SIGKILL)));
+    assert_eq!(status.unwrap().exit_code(), 137);
+    assert_eq!(pty.get_exitstatus().unwrap(), Some(137));
+}
+
+#[test]
+fn killed_exit_status_unix() {
+    let mut pty = PTY::new_with_backend(&pty_args(), PTYBackend::Unix).unwrap();
+    pty.spawn(OsString::from("sleep"), Some(OsString::from("100")), None, None).unwrap();
+    assert!(pty.terminate(Duration::from_secs(5)).unwrap());
+    assert_eq!(pty.exit_status().unwrap(), Some(ExitStatus::Signaled(libc::SIGTERM)));
+
+    let mut pty = PTY::new_with_backend(&pty_args(), PTYBackend::Unix).unwrap();
+    pty.spawn(OsString::from("sleep"), Some(OsString::from("100")), None, None).unwrap();
+    pty.kill().unwrap();
+    assert_eq!(pty.wait_timeout(Duration::from_secs(5)).unwrap(), Some(ExitStatus::Killed));
+    assert_eq!(pty.get_exitstatus().unwrap(), Some(137));
+}
+
+#[test]
+fn kill_on_drop_unix() {
+    let mut pty = PTY::new_with_backend(&pty_args(), PTYBackend::Unix).unwrap();
+    pty.set_kill_on_drop(true).unwrap();
+    // The background job ignores the hangup sent when the PTY is closed.
+    pty.spawn(
+        OsString::from("/bin/sh"),
+        Some(OsString::from("-c \"trap '' HUP; sleep 100 & echo pid=$!; wait\"")),
+        None, None
+    ).unwrap();
+    let output = read_until(&pty, &Regex::new(r"pid=\d+\r?\n").unwrap());
+    let pid: libc::pid_t = output.trim().trim_start_matches("pid=").parse().unwrap();
+
+    drop(pty);
+    assert!(wait_gone(pid));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn kill_on_drop_parent_death_unix() {
+    // The process outlives a PTY that is never dropped, but not the thread
+    // that spawned it.
+    let pid = std::thread::spawn(|| {
+        let mut pty = PTY::new_with_backend(&pty_args(), PTYBackend::Unix).unwrap();
+        pty.set_kill_on_drop(true).unwrap();
+        pty.spawn(OsString::from("sleep"), Some(OsString::from("100")), None, None).unwrap();
+        let pid = pty.get_pid() as libc::pid_t;
+        std::mem::forget(pty);
+        pid
+    }).join().unwrap();
+    assert!(wait_gone(pid));
+}
+
+#[test]
+fn read_outcome_unix() {
+    let mut pty = PTY::new_with_backend(&pty_args(), PTYBackend::Unix).unwrap();
+    pty.spawn(
+        OsString::from("/bin/sh"),
+        Some(OsString::from("-c \"read line; printf 'last words'\"")),
+        None, None
+    ).unwrap();
+    assert_eq!(pty.try_read(1000, false).unwrap(), ReadOutcome::WouldBlock);
+
+    // The output written right before exiting is returned before EOF.
+    pty.write(OsString::from("go\n")).unwrap();
+    pty.wait_for_exit().unwrap();
+    let mut output = String::new();
+    loop {
+        match pty.try_read(1000, true).unwrap() {
+            ReadOutcome::Data(out) => output.push_str(out.to_str().unwrap()),
+            ReadOutcome::WouldBlock => panic!("a blocking read returned no output"),
+            ReadOutcome::Eof => break,
+        }
+    }
+    assert!(output.contains("last words"), "{:?}", output);
+    assert_eq!(pty.try_read(1000, false).unwrap(), ReadOutcome::Eof);
+    assert_eq!(pty.try_read_bytes(&mut [0; 16], true).unwrap(), ReadOutcome::Eof);
+    assert_eq!(pty.read(1000, false), Err(PtyError::Eof));
+}
+
+#[test]
+fn separate_stderr_unix() {
+    let args = PTYArgs { agent_config: AgentConfig::WINPTY_FLAG_CONERR, ..pty_args() };
+    let mut pty = PTY::new_with_backend(&args, PTYBackend::Unix).unwrap();
+    pty.spawn(
+        OsString::from("/bin/sh"),
+        Some(OsString::from("-c \"echo error 1>&2; echo output; test -t 0 && test -t 1\"")),
+        None, None
+    ).unwrap();
+
+    assert_eq!(pty.try_read_stderr(1000, true).unwrap(), ReadOutcome::Data(OsString::from("error\n")));
+    assert_eq!(pty.try_read_stderr(1000, true).unwrap(), ReadOutcome::Eof);
+    let output = read_until(&pty, &Regex::new("output\r\n").unwrap());
+    assert!(!output.contains("error"));
+    // The standard input and output are still the terminal.
+    pty.wait_for_exit().unwrap();
+    assert_eq!(pty.get_exitstatus().unwrap(), Some(0));
+
+    let pty = PTY::new_with_backend(&pty_args(), PTYBackend::Unix).unwrap();
+    assert!(pty.read_stderr(1000, false).is_err());
+    assert!(pty.read_stderr_bytes(&mut [0; 16], false).is_err());
+}
+
+#[test]
+fn separate_stderr_bytes_unix() {
+    let args = PTYArgs { agent_config: AgentConfig::WINPTY_FLAG_CONERR, ..pty_args() };
+    let mut pty = PTY::new_with_backend(&args, PTYBackend::Unix).unwrap();
+    pty.spawn(OsString::from("/bin/sh"), Some(OsString::from("-c \"printf 'caf\\351' 1>&2\"")), None, None).unwrap();
+
+    // Invalid UTF-8 is returned as is, it is not replaced.
+    let mut buf = [0u8; 16];
+    assert_eq!(pty.try_read_stderr_bytes(&mut buf, true).unwrap(), ReadOutcome::Data(4));
+    assert_eq!(&buf[..4], b"caf\xe9");
+    assert_eq!(pty.try_read_stderr_bytes(&mut buf, true).unwrap(), ReadOutcome::Eof);
+}